@@ -37,11 +37,19 @@ def benchmark_buffer_based_fn():
     print(f"size: {len(buffer) / 1024 / 1024:.1f}MB")
 
 
-def create_test_image():
-    pixels = np.random.rand(1024, 1024, 1).reshape(-1).astype(np.float32).copy()
+def benchmark_threaded_save(threads: int):
+    image = create_test_image(size=4096)
+    with tempfile.NamedTemporaryFile(suffix=".exr") as f:
+        start = time.time()
+        image.save_to_path(f.name, compression="piz", threads=threads)
+        print(f"piz save with threads={threads}: {(time.time() - start) * 1000:.1f}ms")
+
+
+def create_test_image(size: int = 1024):
+    pixels = np.random.rand(size, size, 1).reshape(-1).astype(np.float32).copy()
     layer = RustLayer("test")
-    layer.with_width(1024)
-    layer.with_height(1024)
+    layer.with_width(size)
+    layer.with_height(size)
     layer.with_channel("R", pixels)
     layer.with_channel("G", pixels)
     layer.with_channel("B", pixels)
@@ -54,3 +62,5 @@ def create_test_image():
 if __name__ == "__main__":
     benchmark("buffer_based", benchmark_buffer_based_fn)
     benchmark("pypath_based", benchmark_pypath_based_fn)
+    for threads in [1, 2, 4, 8]:
+        benchmark_threaded_save(threads)
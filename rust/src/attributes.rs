@@ -5,7 +5,7 @@ use exr::{
         Chromaticities, EnvironmentMap, KeyCode, Matrix4x4, Preview, Rational, TimeCode,
     },
     prelude::{
-        AttributeValue, ImageAttributes, IntegerBounds, LayerAttributes, Result, Text, Vec2,
+        AttributeValue, Error, ImageAttributes, IntegerBounds, LayerAttributes, Result, Text, Vec2,
     },
 };
 
@@ -19,6 +19,7 @@ fn extract_text(value: &AttributeValue) -> Option<Text> {
 fn extract_f32(value: &AttributeValue) -> Option<f32> {
     match value {
         AttributeValue::F32(f32) => Some(f32.clone()),
+        AttributeValue::I32(integer) => Some(*integer as f32),
         _ => None,
     }
 }
@@ -26,6 +27,7 @@ fn extract_f32(value: &AttributeValue) -> Option<f32> {
 fn extract_vec2_f32(value: &AttributeValue) -> Option<Vec2<f32>> {
     match value {
         AttributeValue::FloatVec2(vec) => Some(vec.clone()),
+        AttributeValue::IntVec2(vec) => Some(Vec2(vec.0 as f32, vec.1 as f32)),
         _ => None,
     }
 }
@@ -93,6 +95,105 @@ fn extract_time_code(value: &AttributeValue) -> Option<TimeCode> {
     }
 }
 
+/// Standard attributes are exposed under snake_case names and written by exr under their
+/// canonical, case-sensitive EXR names (e.g. `pixel_aspect_ratio` is `pixelAspectRatio` on
+/// disk). Pairs are `(exrio name, EXR name)`; names that are identical are omitted.
+pub const STANDARD_ATTRIBUTE_NAMES: &[(&str, &str)] = &[
+    ("display_window", "displayWindow"),
+    ("pixel_aspect_ratio", "pixelAspectRatio"),
+    ("time_code", "timeCode"),
+    ("screen_window_width", "screenWindowWidth"),
+    ("screen_window_center", "screenWindowCenter"),
+    ("white_luminance", "whiteLuminance"),
+    ("adopted_neutral", "adoptedNeutral"),
+    ("horizontal_density", "xDensity"),
+    ("utc_offset", "utcOffset"),
+    ("exposure", "expTime"),
+    ("iso_speed", "isoSpeed"),
+    ("near_clip_plane", "near"),
+    ("far_clip_plane", "far"),
+    ("horizontal_field_of_view", "fieldOfViewHorizontal"),
+    ("vertical_field_of_view", "fieldOfViewVertical"),
+    ("layer_name", "name"),
+    ("rendering_transform_name", "renderingTransform"),
+    ("look_modification_transform_name", "lookModTransform"),
+    ("capture_date", "capDate"),
+    ("wrap_mode_name", "wrapmodes"),
+    ("view_name", "view"),
+    ("software_name", "software"),
+    ("world_to_camera", "worldToCamera"),
+    ("world_to_normalized_device", "worldToNDC"),
+    ("environment_map", "envmap"),
+    ("film_key_code", "keyCode"),
+    ("frames_per_second", "framesPerSecond"),
+    ("deep_image_state", "deepImageState"),
+    ("original_data_window", "originalDataWindow"),
+];
+
+/// Looks up a standard attribute by its exrio name, falling back to its EXR name for
+/// attributes that were set under the canonical name and not normalized yet.
+pub fn standard_attribute<'a>(
+    attributes: &'a HashMap<Text, AttributeValue>,
+    name: &str,
+) -> Option<&'a AttributeValue> {
+    attributes.get(&Text::from(name)).or_else(|| {
+        STANDARD_ATTRIBUTE_NAMES
+            .iter()
+            .find(|(exrio_name, _)| *exrio_name == name)
+            .and_then(|(_, exr_name)| attributes.get(&Text::from(*exr_name)))
+    })
+}
+
+/// Stores a standard attribute under its exrio name, dropping any copy under its EXR name.
+pub fn set_standard_attribute(
+    attributes: &mut HashMap<Text, AttributeValue>,
+    name: &str,
+    value: AttributeValue,
+) {
+    if let Some((_, exr_name)) = STANDARD_ATTRIBUTE_NAMES
+        .iter()
+        .find(|(exrio_name, _)| *exrio_name == name)
+    {
+        attributes.remove(&Text::from(*exr_name));
+    }
+    attributes.insert(Text::from(name), value);
+}
+
+/// The `screenWindowCenter` and `screenWindowWidth` of a layer, which default to (0, 0) and 1
+/// as in the EXR spec when missing.
+pub fn screen_window(attributes: &HashMap<Text, AttributeValue>) -> (Vec2<f32>, f32) {
+    let center = standard_attribute(attributes, "screen_window_center").and_then(extract_vec2_f32);
+    let width = standard_attribute(attributes, "screen_window_width").and_then(extract_f32);
+    (center.unwrap_or(Vec2(0.0, 0.0)), width.unwrap_or(1.0))
+}
+
+/// The `xDensity` of a layer in pixels per inch, if set.
+pub fn horizontal_density(attributes: &HashMap<Text, AttributeValue>) -> Option<f32> {
+    standard_attribute(attributes, "horizontal_density").and_then(extract_f32)
+}
+
+/// Extrapolation keywords allowed in the `wrapmodes` texture attribute.
+const WRAP_MODES: &[&str] = &["black", "clamp", "periodic", "mirror"];
+
+/// Whether `value` is a valid `wrapmodes` attribute: a single keyword for both directions, or
+/// a comma-separated horizontal and vertical pair such as "periodic,clamp". Whitespace around
+/// the keywords is allowed.
+pub fn is_valid_wrap_modes(value: &str) -> bool {
+    let modes: Vec<&str> = value.split(',').map(str::trim).collect();
+    modes.len() <= 2 && modes.iter().all(|mode| WRAP_MODES.contains(mode))
+}
+
+/// Moves standard attributes given under their canonical EXR name to the exrio name, so
+/// they are written through the typed field instead of as a duplicate custom attribute.
+/// An explicit exrio name takes precedence.
+fn normalize_standard_names(attributes: &mut HashMap<Text, AttributeValue>) {
+    for (name, exr_name) in STANDARD_ATTRIBUTE_NAMES {
+        if let Some(value) = attributes.remove(&Text::from(*exr_name)) {
+            attributes.entry(Text::from(*name)).or_insert(value);
+        }
+    }
+}
+
 struct LayerAttributeHandler<T> {
     name: &'static str,
     extract: fn(&AttributeValue) -> Option<T>,
@@ -508,6 +609,7 @@ pub fn layer_attributes_from_attributes(
     attributes: &HashMap<Text, AttributeValue>,
 ) -> Result<()> {
     let mut attributes = attributes.clone();
+    normalize_standard_names(&mut attributes);
 
     for handler in FLOAT_LAYER_ATTRIBUTES {
         let extracted_value = attributes
@@ -648,23 +750,30 @@ const IMAGE_ATTRIBUTES: &[ImageAttributeHandler] = &[
     ImageAttributeHandler {
         name: "display_window",
         get: |attrs| Some(AttributeValue::IntegerBounds(attrs.display_window.clone())),
-        set: |attrs, value| {
-            if let AttributeValue::IntegerBounds(bounds) = value {
+        // Saving keeps a display window set here and only defaults it to the first layer's
+        // size while it is still empty, so a value that isn't integer bounds is an error rather
+        // than silently falling back to that default.
+        set: |attrs, value| match value {
+            AttributeValue::IntegerBounds(bounds) => {
                 attrs.display_window = bounds;
+                Ok(())
             }
-
-            Ok(())
+            _ => Err(Error::Invalid(
+                "display_window must be (x, y, width, height) bounds".into(),
+            )),
         },
     },
     ImageAttributeHandler {
         name: "pixel_aspect_ratio",
         get: |attrs| Some(AttributeValue::F32(attrs.pixel_aspect.clone())),
-        set: |attrs, value| {
-            if let AttributeValue::F32(pixel_aspect) = value {
+        // Written as the header's `pixelAspectRatio` float, so anything but a number is an
+        // error instead of being dropped and saved as the default of 1.
+        set: |attrs, value| match extract_f32(&value) {
+            Some(pixel_aspect) => {
                 attrs.pixel_aspect = pixel_aspect;
+                Ok(())
             }
-
-            Ok(())
+            None => Err(Error::Invalid("pixel_aspect_ratio must be a number".into())),
         },
     },
     ImageAttributeHandler {
@@ -711,11 +820,15 @@ pub fn attributes_from_image(attributes: &ImageAttributes) -> HashMap<Text, Attr
     return image_attributes;
 }
 
+/// Applies `_attributes` to `image_attributes`: standard names go through their handlers and
+/// the rest replace `other`, or are added to it when `merge` is set.
 pub fn image_attributes_from_attributes(
     image_attributes: &mut ImageAttributes,
     _attributes: &HashMap<Text, AttributeValue>,
+    merge: bool,
 ) -> Result<()> {
     let mut attributes = _attributes.clone();
+    normalize_standard_names(&mut attributes);
 
     for handler in IMAGE_ATTRIBUTES {
         match attributes.remove(&Text::from(handler.name)) {
@@ -727,7 +840,11 @@ pub fn image_attributes_from_attributes(
         }
     }
 
-    image_attributes.other = attributes;
+    if merge {
+        image_attributes.other.extend(attributes);
+    } else {
+        image_attributes.other = attributes;
+    }
 
     Ok(())
 }
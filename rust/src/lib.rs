@@ -1,23 +1,43 @@
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
 
 use smallvec::{Array, SmallVec};
 
+use exr::block::reader::{ChunksReader, FilteredChunksReader};
+use exr::block::writer::{ChunksWriter, ParallelBlocksCompressor};
+use exr::block::UncompressedBlock;
+use exr::image::write::channels::{ChannelsWriter, WritableChannels};
+use exr::image::write::layers::WritableLayers;
+use exr::meta::attribute::{ChannelList, Chromaticities, EnvironmentMap, LevelMode};
+use exr::meta::header::Header;
+use exr::meta::{mip_map_levels, rip_map_levels, BlockDescription, Requirements};
 use exr::prelude::read::any_channels::ReadAnyChannels;
 use exr::prelude::read::layers::ReadAllLayers;
 use exr::prelude::read::samples::ReadFlatSamples;
 use exr::prelude::*;
 use half::f16;
+use memmap2::Mmap;
 use numpy::{
-    Complex64, IntoPyArray, PyArray1, PyArrayDyn, PyArrayMethods, PyReadonlyArray1,
-    PyReadonlyArrayDyn, PyReadwriteArray1, PyReadwriteArrayDyn,
+    Complex64, IntoPyArray, PyArray1, PyArrayDyn, PyReadonlyArrayDyn, PyReadwriteArray1,
+    PyReadwriteArrayDyn,
 };
 use pyo3::{
-    exceptions::PyIOError,
-    pyclass, pymethods, pymodule,
+    exceptions::{
+        PyIOError, PyIndexError, PyKeyError, PyMemoryError, PyTypeError, PyUserWarning,
+        PyValueError,
+    },
+    pyclass, pyfunction, pymethods, pymodule,
     types::{PyAnyMethods, PyBytes, PyDict, PyDictMethods, PyModule, PyModuleMethods},
-    Bound, FromPyObject, Py, PyAny, PyErr, PyObject, PyResult, Python,
+    wrap_pyfunction, Bound, FromPyObject, Py, PyAny, PyErr, PyObject, PyRef, PyRefMut, PyResult,
+    Python,
 };
-use std::io::{self, BufWriter, Cursor, Write};
+use rayon_core::ThreadPoolBuilder;
+use std::ffi::{CString, OsString};
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
 use std::vec::Vec;
 
 fn get_inmemory_writer() -> BufWriter<Cursor<Vec<u8>>> {
@@ -28,11 +48,15 @@ fn get_inmemory_writer() -> BufWriter<Cursor<Vec<u8>>> {
 }
 
 mod pyattributes;
-use pyattributes::{from_python, to_python, AttributeValueHandler, ATTRIBUTE_HANDLERS};
+use pyattributes::{
+    from_python, to_python, AttributeValueHandler, Bounds, Box2i, ATTRIBUTE_HANDLERS,
+};
 
 mod attributes;
 use attributes::{attributes_from_image, attributes_from_layer, image_attributes_from_attributes};
 
+/// Reads every part of a file, whether it is stored as scanlines or tiles. Multipart files may
+/// mix both, and each layer keeps its own block layout so saving it again writes it the same way.
 fn get_image_reader() -> ReadImage<fn(f64), ReadAllLayers<ReadAnyChannels<ReadFlatSamples>>> {
     let image = read()
         .no_deep_data()
@@ -44,343 +68,4553 @@ fn get_image_reader() -> ReadImage<fn(f64), ReadAllLayers<ReadAnyChannels<ReadFl
     image
 }
 
+/// Arrays are always returned in native byte order, whatever the byte order on disk.
 fn vec_to_numpy_array<'py>(py: Python<'py>, array_data: &PixelData) -> Bound<'py, PyAny> {
+    // Copy straight from the stored samples so half-float data never passes through an f32 buffer.
     match array_data {
-        PixelData::F32(vec) => {
-            PyArray1::from_iter(py, vec.iter().map(|value| *value as f32)).into_any()
-        }
+        PixelData::F32(vec) => PyArray1::from_slice(py, vec).into_any(),
+        PixelData::F16(vec) => PyArray1::from_slice(py, vec).into_any(),
+        PixelData::U32(vec) => PyArray1::from_slice(py, vec).into_any(),
+    }
+}
+
+fn vec_to_numpy_array_f32<'py>(py: Python<'py>, array_data: &PixelData) -> Bound<'py, PyAny> {
+    match array_data {
+        PixelData::F32(vec) => PyArray1::from_slice(py, vec).into_any(),
         PixelData::F16(vec) => {
-            PyArray1::from_iter(py, vec.iter().map(|value| *value as f16)).into_any()
+            PyArray1::from_iter(py, vec.iter().map(|value| value.to_f32())).into_any()
         }
         PixelData::U32(vec) => {
-            PyArray1::from_iter(py, vec.iter().map(|value| *value as u32)).into_any()
+            PyArray1::from_iter(py, vec.iter().map(|value| *value as f32)).into_any()
         }
     }
 }
 
-fn to_rust_layer(layer: &ExrLayer) -> Option<Layer<AnyChannels<FlatSamples>>> {
-    let width = match &layer.width {
-        Some(width) => width,
-        None => return None,
-    };
-
-    let height = match &layer.height {
-        Some(height) => height,
-        None => return None,
-    };
-
-    let pixels = match &layer.pixels {
-        Some(pixels) => pixels.clone(),
-        None => return None,
-    };
-
-    let mut channels_list = Vec::<AnyChannel<FlatSamples>>::new();
-
-    for (index, channel) in pixels.iter().enumerate() {
-        let channel_name = match layer.channels.get(index) {
-            Some(channel_name) => channel_name,
-            None => return None,
-        };
-
-        let samples = match channel {
-            PixelData::F32(vec) => FlatSamples::F32(vec.clone()),
-            PixelData::F16(vec) => FlatSamples::F16(vec.clone()),
-            PixelData::U32(vec) => FlatSamples::U32(vec.clone()),
-        };
-
-        channels_list.push(AnyChannel::new(channel_name.as_str(), samples));
+fn line_samples_f32(sample_type: SampleType, bytes: &[u8]) -> Vec<f32> {
+    match sample_type {
+        SampleType::F16 => bytes
+            .chunks_exact(2)
+            .map(|sample| f16::from_le_bytes([sample[0], sample[1]]).to_f32())
+            .collect(),
+        SampleType::F32 => bytes
+            .chunks_exact(4)
+            .map(|sample| f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]))
+            .collect(),
+        SampleType::U32 => bytes
+            .chunks_exact(4)
+            .map(|sample| u32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]) as f32)
+            .collect(),
     }
+}
 
-    let channels_builder = AnyChannels::sort(SmallVec::from_vec(channels_list));
-
-    let image_with_channels = Image::from_channels(Vec2(*width, *height), channels_builder);
-
-    let mut attributes = match &layer.name {
-        Some(name) => LayerAttributes::named(Text::from(name.as_str())),
-        None => LayerAttributes::default(),
-    };
-    let _ = attributes::layer_attributes_from_attributes(&mut attributes, &layer.attributes);
+/// Converts the little-endian samples in `bytes` to f32 into every `stride`-th element of
+/// `out`, e.g. one channel of an interleaved `(height, width, channels)` row.
+fn write_strided_f32(sample_type: SampleType, bytes: &[u8], out: &mut [f32], stride: usize) {
+    let out = out.iter_mut().step_by(stride);
+    match sample_type {
+        SampleType::F16 => {
+            for (value, sample) in out.zip(bytes.chunks_exact(2)) {
+                *value = f16::from_le_bytes([sample[0], sample[1]]).to_f32();
+            }
+        }
+        SampleType::F32 => {
+            for (value, sample) in out.zip(bytes.chunks_exact(4)) {
+                *value = f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]);
+            }
+        }
+        SampleType::U32 => {
+            for (value, sample) in out.zip(bytes.chunks_exact(4)) {
+                *value = u32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]) as f32;
+            }
+        }
+    }
+}
 
-    let layer_out = Layer::new(
-        Vec2(*width, *height),
-        attributes,
-        Encoding {
-            // Requirements for ACES-compliance https://openexr.com/en/latest/bin/exr2aces.html
-            compression: Compression::PIZ,
-            blocks: Blocks::ScanLines,
-            line_order: LineOrder::Increasing,
+/// Sizes of the resolution levels of a part, starting with the full resolution. Rip-mapped
+/// parts list only their diagonal levels, which are reduced equally along both axes.
+fn level_sizes(header: &Header) -> Vec<Vec2<usize>> {
+    match header.blocks {
+        BlockDescription::Tiles(tiles) => match tiles.level_mode {
+            LevelMode::Singular => vec![header.layer_size],
+            LevelMode::MipMap => mip_map_levels(tiles.rounding_mode, header.layer_size)
+                .map(|(_, size)| size)
+                .collect(),
+            LevelMode::RipMap => rip_map_levels(tiles.rounding_mode, header.layer_size)
+                .filter(|(index, _)| index.0 == index.1)
+                .map(|(_, size)| size)
+                .collect(),
         },
-        image_with_channels.layer_data.channel_data,
-    );
+        BlockDescription::ScanLines => vec![header.layer_size],
+    }
+}
 
-    Some(layer_out)
+/// The layer attribute holding a channel's default value. EXR channels have no field for it,
+/// so it is stored next to them as an f32 `defaultValue.<channel>` attribute.
+fn default_value_attribute(channel: &str) -> Text {
+    Text::from(format!("defaultValue.{}", channel).as_str())
 }
 
-#[pyclass]
-#[derive(Clone)]
-struct ExrLayer {
-    name: Option<String>,
-    channels: Vec<String>,
-    width: Option<usize>,
-    height: Option<usize>,
-    pixels: Option<Vec<PixelData>>,
-    attributes: HashMap<Text, AttributeValue>,
+/// Moves the default values of `channels` out of a loaded layer's attributes.
+fn take_default_values(
+    attributes: &mut HashMap<Text, AttributeValue>,
+    channels: &[String],
+) -> HashMap<String, f32> {
+    channels
+        .iter()
+        .filter_map(
+            |channel| match attributes.remove(&default_value_attribute(channel)) {
+                Some(AttributeValue::F32(value)) => Some((channel.clone(), value)),
+                _ => None,
+            },
+        )
+        .collect()
 }
 
-fn layer_from_exr(exr_layer: Layer<AnyChannels<FlatSamples>>) -> ExrLayer {
-    let attributes = attributes_from_layer(&exr_layer.attributes);
-    let name = exr_layer.attributes.layer_name.map(|name| name.to_string());
-    let channels = exr_layer
-        .channel_data
-        .list
+/// A layer for the part described by `header` at resolution `size`, with zeroed pixels to be
+/// filled by `decode_level`.
+fn empty_layer(header: &Header, size: Vec2<usize>) -> ExrLayer {
+    let channel_list = &header.channels.list;
+    let sampling = channel_list
         .iter()
+        .filter(|channel| channel.sampling != Vec2(1, 1))
+        .map(|channel| {
+            let sampling = (channel.sampling.0, channel.sampling.1);
+            (channel.name.to_string(), sampling)
+        })
+        .collect();
+    let perceptually_linear = channel_list
+        .iter()
+        .filter(|channel| channel.quantize_linearly)
         .map(|channel| channel.name.to_string())
         .collect();
-    let pixels = Some(
-        exr_layer
-            .channel_data
-            .list
-            .iter()
-            .map(|channel| match &channel.sample_data {
-                FlatSamples::F32(vec) => PixelData::F32(vec.clone()),
-                FlatSamples::F16(vec) => PixelData::F16(vec.clone()),
-                FlatSamples::U32(vec) => PixelData::U32(vec.clone()),
-            })
-            .collect(),
-    );
+    let pixels = channel_list
+        .iter()
+        .map(|channel| PixelData::zeroed(channel.sample_type, (size / channel.sampling).area()))
+        .collect();
+    let channels: Vec<String> = channel_list
+        .iter()
+        .map(|channel| channel.name.to_string())
+        .collect();
+    let mut attributes = attributes_from_layer(&header.own_attributes);
+    let default_values = take_default_values(&mut attributes, &channels);
 
     ExrLayer {
-        name,
+        name: header
+            .own_attributes
+            .layer_name
+            .as_ref()
+            .map(Text::to_string),
         channels,
-        width: Some(exr_layer.size.0),
-        height: Some(exr_layer.size.1),
-        pixels,
+        width: Some(size.0),
+        height: Some(size.1),
+        pixels: Some(pixels),
         attributes,
+        sampling,
+        perceptually_linear,
+        default_values,
+        file_name: None,
+        line_order: header.line_order,
+        compression: header.compression,
+        blocks: match header.blocks {
+            BlockDescription::Tiles(tiles) => Blocks::Tiles(tiles.tile_size),
+            BlockDescription::ScanLines => Blocks::ScanLines,
+        },
+        level_mode: header_level_mode(header),
     }
 }
 
-#[derive(Clone)]
-enum PixelData {
-    F16(Vec<f16>),
-    F32(Vec<f32>),
-    U32(Vec<u32>),
-}
-
-fn _validate_width_height_pixels(
-    width_option: Option<usize>,
-    height_option: Option<usize>,
-    pixels: &PixelData,
+/// Decodes resolution level `level` of `parts` of the file at `path` into `layers`, which hold
+/// one `empty_layer` per part in that range. Chunks of other parts and levels are skipped.
+fn decode_level(
+    path: &Path,
+    level: usize,
+    parts: std::ops::Range<usize>,
+    layers: &mut [ExrLayer],
 ) -> PyResult<()> {
-    if width_option.is_none() || height_option.is_none() {
-        return Err(PyIOError::new_err(
-            "Layer width and height must be set before adding a channel",
-        ));
-    }
-
-    let width = width_option.unwrap();
-    let height = height_option.unwrap();
-    let expected_pixels = width * height;
-    let actual_pixels = match pixels {
-        PixelData::F32(vec) => vec.len(),
-        PixelData::F16(vec) => vec.len(),
-        PixelData::U32(vec) => vec.len(),
+    let file = File::open(path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+    let reader = match exr::block::read(io::BufReader::new(file), false) {
+        Ok(reader) => reader,
+        Err(e) => return Err(exr_error(e)),
+    };
+    let chunks = match reader.filter_chunks(false, |_, _, block| {
+        parts.contains(&block.layer) && block.level == Vec2(level, level)
+    }) {
+        Ok(chunks) => chunks,
+        Err(e) => return Err(exr_error(e)),
     };
 
-    if expected_pixels != actual_pixels {
-        return Err(PyIOError::new_err(
-            "Width * height must match the number of pixels",
-        ));
+    let decoded = chunks.decompress_sequential(false, |meta_data, block| {
+        let header = &meta_data.headers[block.index.layer];
+        layers[block.index.layer - parts.start].copy_block(&block, &header.channels);
+        Ok(())
+    });
+    match decoded {
+        Ok(()) => Ok(()),
+        Err(e) => Err(exr_error(e)),
     }
+}
 
-    Ok(())
+/// Source pixels covered by output pixel `index` when shrinking `size` pixels to `out_size`.
+fn box_filter_range(index: usize, out_size: usize, size: usize) -> std::ops::Range<usize> {
+    let start = index * size / out_size;
+    let end = ((index + 1) * size / out_size).max(start + 1);
+    start..end
 }
 
-fn convert_numpy_array<'py>(py: Python<'py>, array: &Bound<'py, PyAny>) -> PyResult<PixelData> {
-    if let Ok(array) = array.extract::<PyReadonlyArray1<f32>>() {
-        return Ok(PixelData::F32(array.to_vec()?));
-    }
-    if let Ok(array) = array.extract::<PyReadonlyArray1<f16>>() {
-        return Ok(PixelData::F16(array.to_vec()?));
+/// Passes one decoded block to a `read_blocks` callback as per-channel `(height, width)` arrays.
+fn call_block_callback<'py>(
+    py: Python<'py>,
+    callback: &Bound<'py, PyAny>,
+    block: &UncompressedBlock,
+    channels: &ChannelList,
+) -> PyResult<()> {
+    let mut samples: Vec<PixelData> = channels
+        .list
+        .iter()
+        .map(|channel| PixelData::empty(channel.sample_type))
+        .collect();
+    let mut row_lengths = vec![0; channels.list.len()];
+    for line in block.lines(channels) {
+        samples[line.location.channel].extend_from_le_bytes(line.value);
+        row_lengths[line.location.channel] = line.location.sample_count;
     }
-    if let Ok(array) = array.extract::<PyReadonlyArray1<u32>>() {
-        return Ok(PixelData::U32(array.to_vec()?));
+
+    let arrays = PyDict::new(py);
+    for ((channel, data), row_length) in channels.list.iter().zip(&samples).zip(row_lengths) {
+        let rows = data.len().checked_div(row_length).unwrap_or(0);
+        let array = vec_to_numpy_array(py, data).call_method1("reshape", ((rows, row_length),))?;
+        arrays.set_item(channel.name.to_string(), array)?;
     }
 
-    Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(
-        "Unsupported array type",
-    ))
+    let index = block.index;
+    let bounds = (
+        index.pixel_position.0,
+        index.pixel_position.1,
+        index.pixel_size.0,
+        index.pixel_size.1,
+    );
+    callback.call1((index.layer, bounds, arrays))?;
+    Ok(())
+}
+
+/// Yields `(tile_x, tile_y, pixels)` for one channel of one tiled part, decoding a single tile
+/// per step. Returned by `ExrImage.iter_tiles`.
+#[pyclass]
+struct TileIterator {
+    chunks: FilteredChunksReader<io::BufReader<File>>,
+    channel: usize,
+    tile_size: Vec2<usize>,
 }
 
 #[pymethods]
-impl ExrLayer {
-    #[new]
-    #[pyo3(signature = (name = None))]
-    fn new(name: Option<String>) -> Self {
-        Self {
-            name,
-            channels: Vec::new(),
-            width: None,
-            height: None,
-            pixels: None,
-            attributes: HashMap::new(),
-        }
+impl TileIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
     }
 
-    fn name(&self) -> Option<String> {
-        self.name.clone()
-    }
+    fn __next__<'py>(
+        &mut self,
+        py: Python<'py>,
+    ) -> PyResult<Option<(usize, usize, Bound<'py, PyAny>)>> {
+        let chunk = match self.chunks.next() {
+            Some(chunk) => chunk.map_err(exr_error)?,
+            None => return Ok(None),
+        };
+        let meta_data = self.chunks.meta_data();
+        let block =
+            UncompressedBlock::decompress_chunk(chunk, meta_data, false).map_err(exr_error)?;
+        let channels = &meta_data.headers[block.index.layer].channels;
 
-    fn channels(&self) -> Vec<String> {
-        self.channels.clone()
-    }
+        let mut samples = PixelData::empty(channels.list[self.channel].sample_type);
+        let mut row_length = 0;
+        for line in block.lines(channels) {
+            if line.location.channel == self.channel {
+                samples.extend_from_le_bytes(line.value);
+                row_length = line.location.sample_count;
+            }
+        }
 
-    fn width(&self) -> Option<usize> {
-        self.width
+        let rows = samples.len().checked_div(row_length).unwrap_or(0);
+        let array =
+            vec_to_numpy_array(py, &samples).call_method1("reshape", ((rows, row_length),))?;
+        let position = block.index.pixel_position;
+        Ok(Some((
+            position.0 / self.tile_size.0,
+            position.1 / self.tile_size.1,
+            array,
+        )))
     }
+}
 
-    fn with_width(&mut self, width: usize) {
-        self.width = Some(width);
-    }
+fn sorted_keys(attributes: &HashMap<Text, AttributeValue>) -> Vec<String> {
+    let mut keys: Vec<String> = attributes.keys().map(|key| key.to_string()).collect();
+    keys.sort();
+    keys
+}
 
-    fn height(&self) -> Option<usize> {
-        self.height
-    }
+/// Suffixes repeated layer names ("beauty", "beauty.1") so every layer stays addressable,
+/// keeping the name from the file in `file_name`. Returns a warning for each renamed layer.
+fn suffix_duplicate_layer_names(layers: &mut [ExrLayer]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let mut taken: HashSet<String> = layers
+        .iter()
+        .filter_map(|layer| layer.name.clone())
+        .collect();
+    let mut seen: HashSet<String> = HashSet::new();
+    for layer in layers.iter_mut() {
+        let Some(name) = layer.name.clone() else {
+            continue;
+        };
+        if seen.insert(name.clone()) {
+            continue;
+        }
 
-    fn with_height(&mut self, height: usize) {
-        self.height = Some(height);
+        let mut suffix = 1;
+        while taken.contains(&format!("{}.{}", name, suffix)) {
+            suffix += 1;
+        }
+        let unique = format!("{}.{}", name, suffix);
+        taken.insert(unique.clone());
+        seen.insert(unique.clone());
+        warnings.push(format!("Renamed duplicate layer {} to {}", name, unique));
+        layer.name = Some(unique);
+        layer.file_name = Some(name);
     }
+    warnings
+}
 
-    fn pixels<'py>(&self, py: Python<'py>) -> PyResult<Option<Vec<Bound<'py, PyAny>>>> {
-        let pixels = self.pixels.clone().map(|channels| {
-            channels
-                .iter()
-                .map(|channel| vec_to_numpy_array(py, channel))
-                .collect()
-        });
+/// Warnings for loaded attributes that have no Python representation. `attributes()` leaves
+/// them out, but they are kept and written back when the image is saved.
+fn attribute_warnings(layers: &[ExrLayer], image_attributes: &ImageAttributes) -> Vec<String> {
+    Python::with_gil(|py| {
+        let mut warnings = Vec::new();
+        let mut check = |owner: String, attributes: &HashMap<Text, AttributeValue>| {
+            for key in sorted_keys(attributes) {
+                let value = &attributes[&Text::from(key.as_str())];
+                if pyattributes::to_python(&key, value, py).is_err() {
+                    warnings.push(format!(
+                        "Skipped attribute {} of {}, {} values are not supported",
+                        key,
+                        owner,
+                        String::from_utf8_lossy(value.kind_name())
+                    ));
+                }
+            }
+        };
 
-        Ok(pixels)
-    }
+        check(
+            "the image".to_string(),
+            &attributes_from_image(image_attributes),
+        );
+        for (index, layer) in layers.iter().enumerate() {
+            check(format!("layer {}", index), &layer.attributes);
+        }
+        warnings
+    })
+}
 
-    fn with_channel<'py>(
-        &mut self,
-        py: Python<'py>,
-        channel: String,
-        pixels: &Bound<'py, PyAny>,
-    ) -> PyResult<()> {
-        let array_data = convert_numpy_array(py, pixels)?;
+/// Converts an exr error to an `IOError` whose `kind` attribute names the exr error variant:
+/// "Aborted", "NotSupported", "Invalid", or "Io". Lets callers tell corrupt files apart from
+/// unsupported features without parsing the message.
+fn exr_error(error: exr::error::Error) -> PyErr {
+    exr_error_at(error, ReadPosition::default())
+}
 
-        if let Err(e) = _validate_width_height_pixels(self.width, self.height, &array_data) {
-            return Err(e);
-        }
+/// Like `exr_error`, and also sets the `part_index`, `chunk_index`, and `offset` attributes
+/// (each `None` when unknown) to where in the file the error occurred.
+fn exr_error_at(error: exr::error::Error, position: ReadPosition) -> PyErr {
+    let kind = match &error {
+        exr::error::Error::Aborted => "Aborted",
+        exr::error::Error::NotSupported(_) => "NotSupported",
+        exr::error::Error::Invalid(_) => "Invalid",
+        exr::error::Error::Io(_) => "Io",
+    };
 
-        if self.pixels.is_none() {
-            self.pixels = Some(vec![array_data]);
-        } else {
-            self.pixels.as_mut().unwrap().push(array_data);
+    let message = match (position.part_index, position.chunk_index, position.offset) {
+        (Some(part), Some(chunk), Some(offset)) => format!(
+            "{} (part {}, chunk {}, byte offset {})",
+            error, part, chunk, offset
+        ),
+        (None, Some(chunk), Some(offset)) => {
+            format!("{} (chunk {}, byte offset {})", error, chunk, offset)
         }
-        self.channels.push(channel);
+        (_, _, Some(offset)) => format!("{} (byte offset {})", error, offset),
+        _ => error.to_string(),
+    };
 
-        Ok(())
-    }
+    let error = PyIOError::new_err(message);
+    Python::with_gil(|py| {
+        // Setting attributes on a fresh OSError cannot fail.
+        let value = error.value(py);
+        let _ = value.setattr("kind", kind);
+        let _ = value.setattr("part_index", position.part_index);
+        let _ = value.setattr("chunk_index", position.chunk_index);
+        let _ = value.setattr("offset", position.offset);
+    });
+    error
+}
 
-    fn attributes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
-        pyattributes::pydict_from_attributes(py, &self.attributes)
-    }
+/// Where in a file reading failed. exr errors carry only a message, so this is found by
+/// `locate_read_error` re-reading the file.
+#[derive(Default)]
+struct ReadPosition {
+    part_index: Option<usize>,
+    chunk_index: Option<usize>,
+    offset: Option<u64>,
+}
 
-    fn with_attributes<'py>(&mut self, py: Python<'py>, dict: &Bound<PyDict>) -> PyResult<()> {
-        match pyattributes::attributes_from_pydict(py, dict) {
-            Ok(attributes) => {
-                for (key, value) in attributes.iter() {
-                    self.attributes.insert(key.clone(), value.clone());
-                }
-            }
-            Err(e) => return Err(e),
-        }
+/// A cursor that shares its position, so it can still be read after exr takes the cursor.
+struct TrackedCursor<'a> {
+    cursor: Cursor<&'a [u8]>,
+    position: Rc<Cell<u64>>,
+}
 
-        Ok(())
+impl Read for TrackedCursor<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.cursor.read(buf)?;
+        self.position.set(self.cursor.position());
+        Ok(count)
     }
 }
 
-#[pyclass]
-struct ExrImage {
-    layers: Vec<ExrLayer>,
-    attributes: ImageAttributes,
+impl Seek for TrackedCursor<'_> {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let position = self.cursor.seek(position)?;
+        self.position.set(position);
+        Ok(position)
+    }
 }
 
-#[pymethods]
-impl ExrImage {
-    #[new]
-    fn new() -> Self {
-        Self {
-            layers: Vec::new(),
-            attributes: ImageAttributes::new(IntegerBounds::from_dimensions((0, 0))),
+/// Passes writes through while remembering the version flags byte (offset 5, after the magic
+/// number), so the long names flag can be set once exr has written the header.
+struct FlagPatchingWriter<W> {
+    inner: W,
+    position: u64,
+    flags: u8,
+}
+
+impl<W: Write + Seek> FlagPatchingWriter<W> {
+    fn new(inner: W) -> Self {
+        FlagPatchingWriter {
+            inner,
+            position: 0,
+            flags: 0,
         }
     }
 
-    fn attributes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
-        pyattributes::pydict_from_attributes(py, &attributes_from_image(&self.attributes))
+    /// Forces bit 10 of the version field, which exr only sets for names over 31 bytes.
+    fn set_long_names_flag(&mut self) -> io::Result<()> {
+        let end = self.position;
+        let flags = self.flags | 0x04;
+        self.seek(SeekFrom::Start(5))?;
+        self.write_all(&[flags])?;
+        self.seek(SeekFrom::Start(end))?;
+        Ok(())
     }
+}
 
-    fn with_attributes<'py>(&mut self, py: Python<'py>, dict: &Bound<PyDict>) -> PyResult<()> {
-        match pyattributes::attributes_from_pydict(py, dict) {
-            Ok(attributes) => image_attributes_from_attributes(&mut self.attributes, &attributes)
-                .map_err(|e| PyIOError::new_err(e.to_string())),
-            Err(e) => return Err(e),
+impl<W: Write> Write for FlagPatchingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = self.inner.write(buf)?;
+        if (self.position..self.position + count as u64).contains(&5) {
+            self.flags = buf[(5 - self.position) as usize];
         }
+        self.position += count as u64;
+        Ok(count)
     }
 
-    fn layers(&self) -> Vec<ExrLayer> {
-        self.layers.clone()
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
     }
+}
 
-    fn with_layer(&mut self, layer: ExrLayer) {
-        self.layers.push(layer);
+impl<W: Seek> Seek for FlagPatchingWriter<W> {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.position = self.inner.seek(position)?;
+        Ok(self.position)
     }
+}
 
-    fn save_to_buffer<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
-        let first_layer = self.layers.first().unwrap();
-        let rust_layers: Vec<Layer<AnyChannels<FlatSamples>>> = self
-            .layers
-            .iter()
-            .flat_map(|layer| to_rust_layer(layer))
-            .collect();
+/// Adapts a seekable Python file-like object (`io.BytesIO`, an open file, ...) to
+/// `Write + Seek`. Positions are relative to where the object was when writing started, so
+/// the file can follow other data. Python exceptions become `io::Error`s.
+struct PyFileWriter<'py> {
+    file: Bound<'py, PyAny>,
+    start: u64,
+}
 
-        let mut attributes = self.attributes.clone();
-        attributes.display_window.size.0 = first_layer.width.unwrap();
-        attributes.display_window.size.1 = first_layer.height.unwrap();
+impl<'py> PyFileWriter<'py> {
+    fn new(file: Bound<'py, PyAny>) -> PyResult<Self> {
+        let start = file.call_method0("tell")?.extract::<u64>()?;
+        Ok(PyFileWriter { file, start })
+    }
+}
 
-        let image = Image::from_layers(attributes, rust_layers);
-        let mut writer = get_inmemory_writer();
-        match image.write().to_buffered(&mut writer) {
-            Ok(_) => (),
-            Err(e) => return Err(PyIOError::new_err(e.to_string())),
-        }
+fn python_io_error(error: PyErr) -> io::Error {
+    io::Error::other(error.to_string())
+}
 
-        match writer.into_inner() {
-            Ok(buffer) => Ok(PyBytes::new(py, buffer.into_inner().as_slice())),
-            Err(e) => Err(PyIOError::new_err(e.to_string())),
+impl Write for PyFileWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let bytes = PyBytes::new(self.file.py(), buf);
+        let written = self
+            .file
+            .call_method1("write", (bytes,))
+            .map_err(python_io_error)?;
+        // Buffered writers return the full length, raw ones may return less or None.
+        Ok(written.extract::<usize>().unwrap_or(buf.len()))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // Plain objects with only a `write` method have nothing to flush.
+        if !self.file.hasattr("flush").map_err(python_io_error)? {
+            return Ok(());
+        }
+        self.file
+            .call_method0("flush")
+            .map(|_| ())
+            .map_err(python_io_error)
+    }
+}
+
+impl Seek for PyFileWriter<'_> {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let (offset, whence) = match position {
+            SeekFrom::Start(offset) => ((self.start + offset) as i64, 0),
+            SeekFrom::Current(offset) => (offset, 1),
+            SeekFrom::End(offset) => (offset, 2),
+        };
+        let position = self
+            .file
+            .call_method1("seek", (offset, whence))
+            .and_then(|position| position.extract::<u64>())
+            .map_err(python_io_error)?;
+        position
+            .checked_sub(self.start)
+            .ok_or_else(|| io::Error::other("Cannot seek before the start of the EXR file"))
+    }
+}
+
+/// Re-reads `bytes` chunk by chunk, in file order, to find the first chunk that cannot be read
+/// or decompressed. The offset is where that chunk starts, or where the header stopped being
+/// readable. Only called once decoding has already failed.
+fn locate_read_error(bytes: &[u8]) -> ReadPosition {
+    let position = Rc::new(Cell::new(0));
+    let cursor = TrackedCursor {
+        cursor: Cursor::new(bytes),
+        position: position.clone(),
+    };
+    let failed_at = |part_index, chunk_index, offset| ReadPosition {
+        part_index,
+        chunk_index,
+        offset: Some(offset),
+    };
+
+    let mut chunks =
+        match exr::block::read(cursor, false).and_then(|reader| reader.all_chunks(false)) {
+            Ok(chunks) => chunks,
+            Err(_) => return failed_at(None, None, position.get()),
+        };
+
+    let meta_data = chunks.meta_data().clone();
+    let mut chunk_index = 0;
+    loop {
+        // Taken before the chunk is read, so the offset is where the failing chunk starts.
+        let offset = position.get();
+        let chunk = match chunks.next() {
+            None => break,
+            Some(Ok(chunk)) => chunk,
+            Some(Err(_)) => return failed_at(None, Some(chunk_index), offset),
+        };
+        let part_index = chunk.layer_index;
+        if UncompressedBlock::decompress_chunk(chunk, &meta_data, false).is_err() {
+            return failed_at(Some(part_index), Some(chunk_index), offset);
+        }
+        chunk_index += 1;
+    }
+
+    ReadPosition::default()
+}
+
+fn read_meta_data(path: PathBuf) -> PyResult<MetaData> {
+    match MetaData::read_from_file(path, false) {
+        Ok(meta_data) => Ok(meta_data),
+        Err(e) => Err(exr_error(e)),
+    }
+}
+
+/// Written as the `software` attribute of layers that don't have one yet.
+const DEFAULT_SOFTWARE: &str = concat!("exrio ", env!("CARGO_PKG_VERSION"));
+
+/// What a save does when narrowing channels to f16 would turn samples beyond ±65504, such as
+/// bright HDR highlights, into infinity.
+#[derive(Clone, Copy, Default, PartialEq)]
+enum HalfOverflow {
+    Ignore,
+    #[default]
+    Warn,
+    Error,
+}
+
+fn parse_half_overflow(name: &str) -> PyResult<HalfOverflow> {
+    match name {
+        "ignore" => Ok(HalfOverflow::Ignore),
+        "warn" => Ok(HalfOverflow::Warn),
+        "error" => Ok(HalfOverflow::Error),
+        _ => Err(PyValueError::new_err(format!(
+            "Unsupported half_overflow {}, expected ignore, warn, or error",
+            name
+        ))),
+    }
+}
+
+#[derive(Default)]
+struct SaveOptions {
+    display_window: Option<(i32, i32, usize, usize)>,
+    force_dtype: Option<SampleType>,
+    channel_dtypes: HashMap<String, SampleType>,
+    long_names: bool,
+    line_order: Option<LineOrder>,
+    pixel_aspect_ratio: Option<f32>,
+    flip_y: bool,
+    compression: Option<Compression>,
+    smart_compression: bool,
+    data_channels: Option<Vec<String>>,
+    encoding: Option<Encoding>,
+    software: Option<String>,
+    clamp: Option<(f32, f32)>,
+    // Bytes a save may copy before it raises a MemoryError; `None` uses the available memory.
+    memory_limit: Option<usize>,
+    // Worker threads compressing blocks in `write_layers_to`; `None` compresses on the caller.
+    threads: Option<usize>,
+    // Raise for layers whose channels are not already alphabetical instead of sorting them.
+    require_sorted_channels: bool,
+    half_overflow: HalfOverflow,
+}
+
+impl SaveOptions {
+    /// Parses the keyword arguments shared by `save_to_buffer`, `save_to_path`, and
+    /// `save_to_file`. Options that are left out or `None` keep their defaults.
+    fn from_kwargs(kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<SaveOptions> {
+        let mut options = SaveOptions::default();
+        let kwargs = match kwargs {
+            Some(kwargs) => kwargs,
+            None => return Ok(options),
+        };
+
+        for (key, value) in kwargs.iter() {
+            if value.is_none() {
+                continue;
+            }
+            let key: String = key.extract()?;
+            match key.as_str() {
+                "display_window" => {
+                    let Bounds(bounds) = value.extract()?;
+                    options.display_window = Some(bounds);
+                }
+                "force_dtype" => {
+                    options.force_dtype = Some(parse_sample_type(&value.extract::<String>()?)?)
+                }
+                "long_names" => options.long_names = value.extract()?,
+                "line_order" => {
+                    options.line_order = Some(parse_line_order(&value.extract::<String>()?)?)
+                }
+                "channel_dtypes" => {
+                    options.channel_dtypes = value
+                        .extract::<HashMap<String, String>>()?
+                        .into_iter()
+                        .map(|(name, dtype)| Ok((name, parse_sample_type(&dtype)?)))
+                        .collect::<PyResult<HashMap<_, _>>>()?
+                }
+                "pixel_aspect_ratio" => options.pixel_aspect_ratio = Some(value.extract()?),
+                "flip_y" => options.flip_y = value.extract()?,
+                "memory_limit" => options.memory_limit = Some(value.extract()?),
+                "compression" => {
+                    options.compression = Some(parse_compression(&value.extract::<String>()?)?)
+                }
+                "smart_compression" => options.smart_compression = value.extract()?,
+                "data_channels" => options.data_channels = Some(value.extract()?),
+                "encoding" => options.encoding = Some(parse_encoding(&value.extract::<String>()?)?),
+                "software" => options.software = Some(value.extract()?),
+                "clamp" => {
+                    let (low, high): (f32, f32) = value.extract()?;
+                    if low.is_nan() || high.is_nan() || low > high {
+                        return Err(PyValueError::new_err(format!(
+                            "Invalid clamp range ({}, {}), the low bound must not exceed the \
+                             high bound",
+                            low, high
+                        )));
+                    }
+                    options.clamp = Some((low, high));
+                }
+                "threads" => {
+                    let threads: usize = value.extract()?;
+                    if threads == 0 {
+                        return Err(PyValueError::new_err("threads must be at least 1"));
+                    }
+                    options.threads = Some(threads);
+                }
+                "require_sorted_channels" => options.require_sorted_channels = value.extract()?,
+                "half_overflow" => {
+                    options.half_overflow = parse_half_overflow(&value.extract::<String>()?)?
+                }
+                _ => {
+                    return Err(PyTypeError::new_err(format!(
+                        "Unexpected save option {}",
+                        key
+                    )))
+                }
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Counts the samples that `force_dtype` or `channel_dtypes` would narrow to f16 beyond
+    /// its range, after clamping, and warns or raises a ValueError about them as
+    /// `half_overflow` asks. Runs before anything is written.
+    fn check_half_overflow(&self, py: Python<'_>, layers: &[ExrLayer]) -> PyResult<()> {
+        if self.half_overflow == HalfOverflow::Ignore {
+            return Ok(());
+        }
+
+        let mut count = 0;
+        for layer in layers {
+            let pixels = layer.pixels.iter().flatten();
+            for (name, channel) in layer.channels.iter().zip(pixels) {
+                let sample_type = self.channel_dtypes.get(name).copied().or(self.force_dtype);
+                if sample_type == Some(SampleType::F16) {
+                    count += channel.half_overflow_count(self.clamp);
+                }
+            }
+        }
+        if count == 0 {
+            return Ok(());
+        }
+
+        let message = format!(
+            "{} sample(s) exceed the float16 range of ±65504 and would be written as infinity",
+            count
+        );
+        match self.half_overflow {
+            HalfOverflow::Error => Err(PyValueError::new_err(format!(
+                "{}; clamp them, keep those channels as f32, or pass half_overflow=\"ignore\"",
+                message
+            ))),
+            _ => {
+                let message = CString::new(message).unwrap_or_default();
+                PyErr::warn(py, &py.get_type::<PyUserWarning>(), &message, 1)
+            }
+        }
+    }
+
+    /// Rejects channel flags that don't fit the sample type the channel is written as: u32
+    /// channels hold ids or counts, so they can't be `pLinear`, and their default values must
+    /// be whole numbers in the u32 range.
+    fn check_channel_flags(&self, layers: &[ExrLayer]) -> PyResult<()> {
+        for layer in layers {
+            let pixels = layer.pixels.iter().flatten();
+            for (name, channel) in layer.channels.iter().zip(pixels) {
+                let sample_type = self
+                    .channel_dtypes
+                    .get(name)
+                    .copied()
+                    .or(self.force_dtype)
+                    .unwrap_or(channel.sample_type());
+                if sample_type != SampleType::U32 {
+                    continue;
+                }
+                if layer.perceptually_linear.contains(name) {
+                    return Err(PyValueError::new_err(format!(
+                        "Channel {} is u32 and can't be perceptually linear",
+                        name
+                    )));
+                }
+                if let Some(value) = layer.default_values.get(name) {
+                    if value.fract() != 0.0 || *value < 0.0 || *value > u32::MAX as f32 {
+                        return Err(PyValueError::new_err(format!(
+                            "Channel {} is u32 and can't default to {}",
+                            name, value
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The compression for a layer. With `smart_compression`, layers made up only of data
+    /// channels (see `is_data_channel`) use lossless ZIP whatever codec was requested.
+    fn layer_compression(&self, layer: &ExrLayer) -> Compression {
+        let preset = self.encoding.map(|encoding| encoding.compression);
+        let requested = self.compression.or(preset).unwrap_or(layer.compression);
+        if !self.smart_compression || layer.channels.is_empty() {
+            return requested;
+        }
+
+        let pixels = layer.pixels.as_deref().unwrap_or_default();
+        let data_channels = self.data_channels.as_deref();
+        let is_data = |(index, name): (usize, &String)| {
+            let sample_type = pixels.get(index).map(|pixels| pixels.sample_type());
+            is_data_channel(name, sample_type, data_channels)
+        };
+        if layer.channels.iter().enumerate().all(is_data) {
+            Compression::ZIP16
+        } else {
+            requested
+        }
+    }
+}
+
+/// Names `ExrLayer.alpha()` looks for, in order.
+const ALPHA_CHANNEL_NAMES: &[&str] = &["A", "a", "alpha"];
+
+/// Channel names, compared case-insensitively after the last ".", that `smart_compression`
+/// treats as data (depth, masks, and IDs) rather than color.
+const DATA_CHANNEL_NAMES: &[&str] = &[
+    "z",
+    "zback",
+    "depth",
+    "a",
+    "alpha",
+    "mask",
+    "matte",
+    "id",
+    "objectid",
+    "materialid",
+];
+
+/// A channel holds data that must stay lossless when it stores integers (u32 IDs) or its name
+/// is in `data_channels`, which defaults to `DATA_CHANNEL_NAMES`.
+fn is_data_channel(
+    name: &str,
+    sample_type: Option<SampleType>,
+    data_channels: Option<&[String]>,
+) -> bool {
+    if sample_type == Some(SampleType::U32) {
+        return true;
+    }
+
+    let suffix = name.rsplit('.').next().unwrap_or(name).to_lowercase();
+    match data_channels {
+        Some(names) => names.iter().any(|name| name.to_lowercase() == suffix),
+        None => DATA_CHANNEL_NAMES.contains(&suffix.as_str()),
+    }
+}
+
+/// Writes the file at `path` through a temporary file next to it, which replaces `path` only
+/// once `write` has succeeded, so a failed save leaves an existing file untouched.
+fn write_file_atomically<T>(
+    path: &Path,
+    write: impl FnOnce(BufWriter<File>) -> PyResult<T>,
+) -> PyResult<T> {
+    let io_error = |e: io::Error| PyIOError::new_err(e.to_string());
+    let file_name = match path.file_name() {
+        Some(file_name) => file_name,
+        None => {
+            return Err(PyIOError::new_err(format!(
+                "{} is not a file path",
+                path.display()
+            )))
+        }
+    };
+    let mut temp_name = OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let file = File::create(&temp_path).map_err(io_error)?;
+    let result = write(BufWriter::new(file)).and_then(|value| {
+        std::fs::rename(&temp_path, path)
+            .map_err(io_error)
+            .map(|_| value)
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Bytes the OS reports as available for new allocations: `MemAvailable` on Linux, capped by
+/// the cgroup's memory limit in containers such as render farm jobs.
+#[cfg(target_os = "linux")]
+fn available_memory() -> Option<usize> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))?;
+    let kilobytes: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+
+    // cgroup v2 reports "max" when the container is unlimited.
+    let read_bytes = |name: &str| -> Option<usize> {
+        let text = std::fs::read_to_string(format!("/sys/fs/cgroup/{}", name)).ok()?;
+        text.trim().parse().ok()
+    };
+    let cgroup_available = match (read_bytes("memory.max"), read_bytes("memory.current")) {
+        (Some(max), Some(current)) => Some(max.saturating_sub(current)),
+        _ => None,
+    };
+    Some(cgroup_available.map_or(kilobytes * 1024, |bytes| bytes.min(kilobytes * 1024)))
+}
+
+/// The physical memory of the machine, as macOS doesn't report available memory as directly.
+#[cfg(target_os = "macos")]
+fn available_memory() -> Option<usize> {
+    let output = std::process::Command::new("sysctl")
+        .args(["-n", "hw.memsize"])
+        .output()
+        .ok()?;
+    String::from_utf8(output.stdout).ok()?.trim().parse().ok()
+}
+
+/// The free physical memory as reported by `wmic`, in kilobytes.
+#[cfg(windows)]
+fn available_memory() -> Option<usize> {
+    let output = std::process::Command::new("wmic")
+        .args(["OS", "get", "FreePhysicalMemory", "/Value"])
+        .output()
+        .ok()?;
+    let text = String::from_utf8(output.stdout).ok()?;
+    let kilobytes: usize = text
+        .trim()
+        .strip_prefix("FreePhysicalMemory=")?
+        .parse()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn available_memory() -> Option<usize> {
+    None
+}
+
+/// Chunks of a part's full resolution level. Tiled parts with mip or rip maps store more
+/// chunks for their smaller levels, which loading skips.
+fn full_resolution_chunk_count(header: &Header) -> usize {
+    match &header.blocks {
+        BlockDescription::Tiles(tiles) => {
+            let size = header.layer_size;
+            size.0.div_ceil(tiles.tile_size.0) * size.1.div_ceil(tiles.tile_size.1)
+        }
+        BlockDescription::ScanLines => header.chunk_count,
+    }
+}
+
+/// Fails early with a `MemoryError` when a save would need more than `limit` bytes, which
+/// defaults to the available memory. Nothing is checked when that can't be determined.
+/// `hint` suggests a cheaper way to save.
+fn check_save_memory(required: usize, limit: Option<usize>, hint: &str) -> PyResult<()> {
+    let Some(limit) = limit.or_else(available_memory) else {
+        return Ok(());
+    };
+    if required > limit {
+        return Err(PyMemoryError::new_err(format!(
+            "Saving needs about {} MiB but the limit is {} MiB; {}",
+            required >> 20,
+            limit >> 20,
+            hint
+        )));
+    }
+    Ok(())
+}
+
+fn parse_sample_type(dtype: &str) -> PyResult<SampleType> {
+    match dtype {
+        "f16" | "float16" => Ok(SampleType::F16),
+        "f32" | "float32" => Ok(SampleType::F32),
+        "u32" | "uint32" => Ok(SampleType::U32),
+        _ => Err(PyValueError::new_err(format!(
+            "Unsupported dtype {}, expected f16, f32, or u32",
+            dtype
+        ))),
+    }
+}
+
+fn parse_line_order(name: &str) -> PyResult<LineOrder> {
+    match name {
+        "increasing" => Ok(LineOrder::Increasing),
+        "decreasing" => Ok(LineOrder::Decreasing),
+        "unspecified" => Ok(LineOrder::Unspecified),
+        _ => Err(PyValueError::new_err(format!(
+            "Unsupported line order {}, expected increasing, decreasing, or unspecified",
+            name
+        ))),
+    }
+}
+
+fn line_order_name(line_order: LineOrder) -> &'static str {
+    match line_order {
+        LineOrder::Increasing => "increasing",
+        LineOrder::Decreasing => "decreasing",
+        LineOrder::Unspecified => "unspecified",
+    }
+}
+
+fn level_mode_name(level_mode: LevelMode) -> &'static str {
+    match level_mode {
+        LevelMode::Singular => "one_level",
+        LevelMode::MipMap => "mipmap",
+        LevelMode::RipMap => "ripmap",
+    }
+}
+
+/// The resolution levels a part is stored with. Scanline parts only have one level.
+fn header_level_mode(header: &Header) -> LevelMode {
+    match header.blocks {
+        BlockDescription::Tiles(tiles) => tiles.level_mode,
+        BlockDescription::ScanLines => LevelMode::Singular,
+    }
+}
+
+fn compression_name(compression: Compression) -> &'static str {
+    match compression {
+        Compression::Uncompressed => "none",
+        Compression::RLE => "rle",
+        Compression::ZIP1 => "zips",
+        Compression::ZIP16 => "zip",
+        Compression::PIZ => "piz",
+        Compression::PXR24 => "pxr24",
+        Compression::B44 => "b44",
+        Compression::B44A => "b44a",
+        Compression::DWAA(_) => "dwaa",
+        Compression::DWAB(_) => "dwab",
+    }
+}
+
+fn parse_compression(name: &str) -> PyResult<Compression> {
+    match name.to_lowercase().as_str() {
+        "none" | "uncompressed" => Ok(Compression::Uncompressed),
+        "rle" => Ok(Compression::RLE),
+        "zips" => Ok(Compression::ZIP1),
+        "zip" => Ok(Compression::ZIP16),
+        "piz" => Ok(Compression::PIZ),
+        "pxr24" => Ok(Compression::PXR24),
+        "b44" => Ok(Compression::B44),
+        "b44a" => Ok(Compression::B44A),
+        "dwaa" => Ok(Compression::DWAA(None)),
+        "dwab" => Ok(Compression::DWAB(None)),
+        _ => Err(PyValueError::new_err(format!(
+            "Unsupported compression {}",
+            name
+        ))),
+    }
+}
+
+/// The exr encoding presets, which set the compression, block layout, and line order at once:
+/// - "fast_lossless": RLE in 64x64 tiles, unspecified line order
+/// - "small_lossless": ZIP in scanline blocks of 16, increasing line order
+/// - "small_fast_lossless": PIZ in 256x256 tiles, unspecified line order
+/// - "uncompressed": no compression in scanlines, increasing line order
+fn parse_encoding(name: &str) -> PyResult<Encoding> {
+    match name.to_lowercase().as_str() {
+        "fast_lossless" => Ok(Encoding::FAST_LOSSLESS),
+        "small_lossless" => Ok(Encoding::SMALL_LOSSLESS),
+        "small_fast_lossless" => Ok(Encoding::SMALL_FAST_LOSSLESS),
+        "uncompressed" => Ok(Encoding::UNCOMPRESSED),
+        _ => Err(PyValueError::new_err(format!(
+            "Unsupported encoding {}, expected fast_lossless, small_lossless, \
+             small_fast_lossless, or uncompressed",
+            name
+        ))),
+    }
+}
+
+/// Rough compressed/uncompressed size ratios for typical render output.
+fn estimated_compression_ratio(compression: Compression) -> f64 {
+    match compression {
+        Compression::Uncompressed => 1.0,
+        Compression::RLE => 0.8,
+        Compression::ZIP1 => 0.55,
+        Compression::ZIP16 => 0.5,
+        Compression::PIZ => 0.45,
+        Compression::PXR24 => 0.4,
+        Compression::B44 | Compression::B44A => 0.35,
+        Compression::DWAA(_) | Compression::DWAB(_) => 0.15,
+    }
+}
+
+/// Converts `layer` for writing, along with whether any sample was clamped to `options.clamp`.
+fn to_rust_layer(
+    layer: &ExrLayer,
+    options: &SaveOptions,
+) -> Option<(Layer<AnyChannels<FlatSamples>>, bool)> {
+    let width = match &layer.width {
+        Some(width) => width,
+        None => return None,
+    };
+
+    let height = match &layer.height {
+        Some(height) => height,
+        None => return None,
+    };
+
+    let pixels = match &layer.pixels {
+        Some(pixels) => pixels,
+        None => return None,
+    };
+
+    let mut channels_list = Vec::<AnyChannel<FlatSamples>>::new();
+    let mut clamped = false;
+
+    for (index, channel) in pixels.iter().enumerate() {
+        let channel_name = match layer.channels.get(index) {
+            Some(channel_name) => channel_name,
+            None => return None,
+        };
+
+        // A per-channel override wins over forcing the whole file to one dtype.
+        let sample_type = options
+            .channel_dtypes
+            .get(channel_name)
+            .copied()
+            .or(options.force_dtype);
+        let mut channel = match sample_type {
+            Some(sample_type) => channel.converted(sample_type),
+            None => channel.clone(),
+        };
+        if options.flip_y {
+            channel.flip_rows(layer.row_length(channel_name));
+        }
+        if let Some((low, high)) = options.clamp {
+            clamped |= channel.clamp(low, high);
+        }
+
+        let samples = match channel {
+            PixelData::F32(vec) => FlatSamples::F32(vec),
+            PixelData::F16(vec) => FlatSamples::F16(vec),
+            PixelData::U32(vec) => FlatSamples::U32(vec),
+        };
+
+        let mut channel = AnyChannel::new(channel_name.as_str(), samples);
+        if let Some((x, y)) = layer.sampling.get(channel_name) {
+            channel.sampling = Vec2(*x, *y);
+        }
+        channel.quantize_linearly = layer.perceptually_linear.contains(channel_name);
+        channels_list.push(channel);
+    }
+
+    let channels_builder = AnyChannels::sort(SmallVec::from_vec(channels_list));
+
+    let image_with_channels = Image::from_channels(Vec2(*width, *height), channels_builder);
+
+    let mut attributes = LayerAttributes::default();
+    let _ = attributes::layer_attributes_from_attributes(&mut attributes, &layer.attributes);
+    for (channel, value) in &layer.default_values {
+        attributes.other.insert(
+            default_value_attribute(channel),
+            AttributeValue::F32(*value),
+        );
+    }
+    // Loaded layers carry their original `layer_name` attribute, which must not undo a rename.
+    if let Some(name) = &layer.name {
+        attributes.layer_name = Some(Text::from(name.as_str()));
+    }
+    match &options.software {
+        Some(software) => attributes.software_name = Some(Text::from(software.as_str())),
+        None if attributes.software_name.is_none() => {
+            attributes.software_name = Some(Text::from(DEFAULT_SOFTWARE))
+        }
+        None => {}
+    }
+
+    let layer_out = Layer::new(
+        Vec2(*width, *height),
+        attributes,
+        Encoding {
+            compression: options.layer_compression(layer),
+            blocks: options
+                .encoding
+                .map_or(layer.blocks, |encoding| encoding.blocks),
+            line_order: options
+                .line_order
+                .or(options.encoding.map(|encoding| encoding.line_order))
+                .unwrap_or(layer.line_order),
+        },
+        image_with_channels.layer_data.channel_data,
+    );
+
+    Some((layer_out, clamped))
+}
+
+#[pyclass]
+#[derive(Clone)]
+struct ExrLayer {
+    name: Option<String>,
+    channels: Vec<String>,
+    width: Option<usize>,
+    height: Option<usize>,
+    pixels: Option<Vec<PixelData>>,
+    attributes: HashMap<Text, AttributeValue>,
+    // Channels stored at reduced resolution, keyed by name. Others are sampled at (1, 1).
+    sampling: HashMap<String, (usize, usize)>,
+    // Channels whose `pLinear` flag is set, e.g. perceptually linear chroma. Others are unset.
+    perceptually_linear: HashSet<String>,
+    // Values of channels outside the data window, keyed by name. Others default to zero.
+    default_values: HashMap<String, f32>,
+    // The name in the file when loading suffixed it as a duplicate, e.g. "beauty" for "beauty.1".
+    file_name: Option<String>,
+    line_order: LineOrder,
+    // Kept from the file on load so that saving a loaded image doesn't change its encoding.
+    compression: Compression,
+    blocks: Blocks,
+    // Levels of the tiled part the layer was loaded from. Saving writes only the full resolution.
+    level_mode: LevelMode,
+}
+
+fn layer_from_exr(exr_layer: Layer<AnyChannels<FlatSamples>>) -> PyResult<ExrLayer> {
+    let mut attributes = attributes_from_layer(&exr_layer.attributes);
+    let name = exr_layer.attributes.layer_name.map(|name| name.to_string());
+    let channels: Vec<String> = exr_layer
+        .channel_data
+        .list
+        .iter()
+        .map(|channel| channel.name.to_string())
+        .collect();
+    let default_values = take_default_values(&mut attributes, &channels);
+    let sampling = exr_layer
+        .channel_data
+        .list
+        .iter()
+        .filter(|channel| channel.sampling != Vec2(1, 1))
+        .map(|channel| {
+            let sampling = (channel.sampling.0, channel.sampling.1);
+            (channel.name.to_string(), sampling)
+        })
+        .collect();
+    let perceptually_linear = exr_layer
+        .channel_data
+        .list
+        .iter()
+        .filter(|channel| channel.quantize_linearly)
+        .map(|channel| channel.name.to_string())
+        .collect();
+    let pixels = Some(
+        exr_layer
+            .channel_data
+            .list
+            .iter()
+            .map(|channel| match &channel.sample_data {
+                FlatSamples::F32(vec) => PixelData::F32(vec.clone()),
+                FlatSamples::F16(vec) => PixelData::F16(vec.clone()),
+                FlatSamples::U32(vec) => PixelData::U32(vec.clone()),
+            })
+            .collect(),
+    );
+
+    let layer = ExrLayer {
+        name,
+        channels,
+        width: Some(exr_layer.size.0),
+        height: Some(exr_layer.size.1),
+        pixels,
+        attributes,
+        sampling,
+        perceptually_linear,
+        default_values,
+        file_name: None,
+        line_order: exr_layer.encoding.line_order,
+        compression: exr_layer.encoding.compression,
+        blocks: exr_layer.encoding.blocks,
+        level_mode: LevelMode::Singular,
+    };
+
+    // Malformed files can decode a channel without all of its samples, which would leave the
+    // channel names out of step with the pixels of every later channel.
+    match layer.check_layout() {
+        Ok(()) => Ok(layer),
+        Err(message) => Err(exr_error(exr::error::Error::Invalid(
+            format!(
+                "layer {}: {}",
+                layer.name.as_deref().unwrap_or("(unnamed)"),
+                message
+            )
+            .into(),
+        ))),
+    }
+}
+
+/// Stable hasher behind `content_hash`: each 64-bit word is mixed in with a rotate and a
+/// multiply, and the result is finished with the MurmurHash3 avalanche.
+struct ContentHasher(u64);
+
+impl ContentHasher {
+    fn new() -> ContentHasher {
+        ContentHasher(0)
+    }
+
+    fn write_word(&mut self, word: u64) {
+        self.0 = (self.0.rotate_left(5) ^ word).wrapping_mul(0x517cc1b727220a95);
+    }
+
+    /// Packs the low `bits` of each sample into 64-bit words, so a word holds 4 f16 or 2 f32
+    /// samples rather than hashing byte by byte.
+    fn write_samples(&mut self, samples: impl Iterator<Item = u64>, bits: u32) {
+        let per_word = 64 / bits as usize;
+        let (mut word, mut count) = (0u64, 0);
+        for sample in samples {
+            word |= sample << (bits * count as u32);
+            count += 1;
+            if count == per_word {
+                self.write_word(word);
+                (word, count) = (0, 0);
+            }
+        }
+        if count > 0 {
+            self.write_word(word);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        let mut hash = self.0;
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+        hash ^ (hash >> 33)
+    }
+}
+
+#[derive(Clone)]
+enum PixelData {
+    F16(Vec<f16>),
+    F32(Vec<f32>),
+    U32(Vec<u32>),
+}
+
+impl PixelData {
+    fn empty(sample_type: SampleType) -> PixelData {
+        match sample_type {
+            SampleType::F16 => PixelData::F16(Vec::new()),
+            SampleType::F32 => PixelData::F32(Vec::new()),
+            SampleType::U32 => PixelData::U32(Vec::new()),
+        }
+    }
+
+    /// Copies `len` samples of `source` from `from` on over this buffer's samples from `to` on.
+    /// Both must have the same sample type; `converted` the source first otherwise.
+    fn copy_samples(&mut self, to: usize, source: &PixelData, from: usize, len: usize) {
+        match (self, source) {
+            (PixelData::F16(target), PixelData::F16(source)) => {
+                target[to..to + len].copy_from_slice(&source[from..from + len])
+            }
+            (PixelData::F32(target), PixelData::F32(source)) => {
+                target[to..to + len].copy_from_slice(&source[from..from + len])
+            }
+            (PixelData::U32(target), PixelData::U32(source)) => {
+                target[to..to + len].copy_from_slice(&source[from..from + len])
+            }
+            _ => {}
+        }
+    }
+
+    /// `len` samples of `value`, saturated to u32 for u32 buffers.
+    fn filled(sample_type: SampleType, len: usize, value: f32) -> PixelData {
+        match sample_type {
+            SampleType::F16 => PixelData::F16(vec![f16::from_f32(value); len]),
+            SampleType::F32 => PixelData::F32(vec![value; len]),
+            SampleType::U32 => PixelData::U32(vec![value as u32; len]),
+        }
+    }
+
+    fn zeroed(sample_type: SampleType, len: usize) -> PixelData {
+        match sample_type {
+            SampleType::F16 => PixelData::F16(vec![f16::ZERO; len]),
+            SampleType::F32 => PixelData::F32(vec![0.0; len]),
+            SampleType::U32 => PixelData::U32(vec![0; len]),
+        }
+    }
+
+    /// Overwrites the samples from `start` on with little-endian bytes of the same sample type.
+    fn copy_from_le_bytes(&mut self, start: usize, bytes: &[u8]) {
+        match self {
+            PixelData::F16(vec) => {
+                let samples = bytes.chunks_exact(2);
+                for (target, sample) in vec[start..].iter_mut().zip(samples) {
+                    *target = f16::from_le_bytes([sample[0], sample[1]]);
+                }
+            }
+            PixelData::F32(vec) => {
+                let samples = bytes.chunks_exact(4);
+                for (target, sample) in vec[start..].iter_mut().zip(samples) {
+                    *target = f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]);
+                }
+            }
+            PixelData::U32(vec) => {
+                let samples = bytes.chunks_exact(4);
+                for (target, sample) in vec[start..].iter_mut().zip(samples) {
+                    *target = u32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]);
+                }
+            }
+        }
+    }
+
+    /// Appends samples stored as little-endian bytes of the same sample type.
+    fn extend_from_le_bytes(&mut self, bytes: &[u8]) {
+        match self {
+            PixelData::F16(vec) => vec.extend(
+                bytes
+                    .chunks_exact(2)
+                    .map(|sample| f16::from_le_bytes([sample[0], sample[1]])),
+            ),
+            PixelData::F32(vec) => vec.extend(
+                bytes
+                    .chunks_exact(4)
+                    .map(|sample| f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]])),
+            ),
+            PixelData::U32(vec) => vec.extend(
+                bytes
+                    .chunks_exact(4)
+                    .map(|sample| u32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]])),
+            ),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            PixelData::F32(vec) => vec.len(),
+            PixelData::F16(vec) => vec.len(),
+            PixelData::U32(vec) => vec.len(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Clamps float samples into `[low, high]`, leaving NaNs and u32 samples untouched.
+    /// Returns whether any sample was changed.
+    fn clamp(&mut self, low: f32, high: f32) -> bool {
+        let mut clamped = false;
+        match self {
+            PixelData::F32(vec) => {
+                for value in vec
+                    .iter_mut()
+                    .filter(|value| **value < low || **value > high)
+                {
+                    *value = value.clamp(low, high);
+                    clamped = true;
+                }
+            }
+            PixelData::F16(vec) => {
+                for value in vec.iter_mut() {
+                    let sample = value.to_f32();
+                    if sample < low || sample > high {
+                        *value = f16::from_f32(sample.clamp(low, high));
+                        clamped = true;
+                    }
+                }
+            }
+            PixelData::U32(_) => {}
+        }
+        clamped
+    }
+
+    /// Number of finite samples that become infinite when narrowed to f16, after clamping to
+    /// `clamp` as a save would. Always 0 for f16 samples.
+    fn half_overflow_count(&self, clamp: Option<(f32, f32)>) -> usize {
+        let overflows = |value: f32| {
+            let value = clamp.map_or(value, |(low, high)| value.clamp(low, high));
+            value.is_finite() && f16::from_f32(value).is_infinite()
+        };
+        match self {
+            PixelData::F16(_) => 0,
+            PixelData::F32(vec) => vec.iter().filter(|value| overflows(**value)).count(),
+            PixelData::U32(vec) => vec.iter().filter(|value| overflows(**value as f32)).count(),
+        }
+    }
+
+    fn byte_size(&self) -> usize {
+        match self {
+            PixelData::F16(vec) => vec.len() * 2,
+            PixelData::F32(vec) => vec.len() * 4,
+            PixelData::U32(vec) => vec.len() * 4,
+        }
+    }
+
+    fn sample_type(&self) -> SampleType {
+        match self {
+            PixelData::F16(_) => SampleType::F16,
+            PixelData::F32(_) => SampleType::F32,
+            PixelData::U32(_) => SampleType::U32,
+        }
+    }
+
+    /// The numpy name of the sample type, e.g. for `np.frombuffer`.
+    fn dtype_name(&self) -> &'static str {
+        match self {
+            PixelData::F16(_) => "float16",
+            PixelData::F32(_) => "float32",
+            PixelData::U32(_) => "uint32",
+        }
+    }
+
+    fn write_le_bytes(&self, mut write: impl FnMut(&[u8])) {
+        match self {
+            PixelData::F16(vec) => vec.iter().for_each(|value| write(&value.to_le_bytes())),
+            PixelData::F32(vec) => vec.iter().for_each(|value| write(&value.to_le_bytes())),
+            PixelData::U32(vec) => vec.iter().for_each(|value| write(&value.to_le_bytes())),
+        }
+    }
+
+    /// Mixes a dtype tag and the sample count in ahead of the samples, so channels with equal
+    /// bytes but different dtypes or lengths hash differently.
+    fn hash_into(&self, hasher: &mut ContentHasher) {
+        let tag = match self {
+            PixelData::F16(_) => 1,
+            PixelData::F32(_) => 2,
+            PixelData::U32(_) => 3,
+        };
+        hasher.write_word(tag);
+        hasher.write_word(self.len() as u64);
+        match self {
+            PixelData::F16(vec) => {
+                hasher.write_samples(vec.iter().map(|value| value.to_bits() as u64), 16)
+            }
+            PixelData::F32(vec) => {
+                hasher.write_samples(vec.iter().map(|value| value.to_bits() as u64), 32)
+            }
+            PixelData::U32(vec) => hasher.write_samples(vec.iter().map(|value| *value as u64), 32),
+        }
+    }
+
+    /// Whether no two samples differ by more than `tolerance`. NaNs only count as constant
+    /// when every sample is NaN; u32 samples are compared exactly.
+    fn is_constant(&self, tolerance: f64) -> bool {
+        if let PixelData::U32(vec) = self {
+            let (min, max) = (vec.iter().min(), vec.iter().max());
+            return match (min, max) {
+                (Some(min), Some(max)) => (max - min) as f64 <= tolerance,
+                _ => true,
+            };
+        }
+
+        let (mut min, mut max, mut nans) = (f64::INFINITY, f64::NEG_INFINITY, 0);
+        for index in 0..self.len() {
+            let value = self.sample_f32(index) as f64;
+            if value.is_nan() {
+                nans += 1;
+            } else {
+                min = min.min(value);
+                max = max.max(value);
+            }
+        }
+        match nans {
+            0 => self.is_empty() || min == max || max - min <= tolerance,
+            nans => nans == self.len(),
+        }
+    }
+
+    fn sample_f32(&self, index: usize) -> f32 {
+        match self {
+            PixelData::F32(vec) => vec[index],
+            PixelData::F16(vec) => vec[index].to_f32(),
+            PixelData::U32(vec) => vec[index] as f32,
+        }
+    }
+
+    /// Converts the samples to another type. Floats are rounded to the nearest half when
+    /// narrowed to f16, and truncated toward zero (saturating, NaN becomes 0) when cast to u32.
+    fn converted(&self, sample_type: SampleType) -> PixelData {
+        match (self, sample_type) {
+            (PixelData::F16(vec), SampleType::F16) => PixelData::F16(vec.clone()),
+            (PixelData::F16(vec), SampleType::F32) => {
+                PixelData::F32(vec.iter().map(|value| value.to_f32()).collect())
+            }
+            (PixelData::F16(vec), SampleType::U32) => {
+                PixelData::U32(vec.iter().map(|value| value.to_f32() as u32).collect())
+            }
+            (PixelData::F32(vec), SampleType::F16) => {
+                PixelData::F16(vec.iter().map(|value| f16::from_f32(*value)).collect())
+            }
+            (PixelData::F32(vec), SampleType::F32) => PixelData::F32(vec.clone()),
+            (PixelData::F32(vec), SampleType::U32) => {
+                PixelData::U32(vec.iter().map(|value| *value as u32).collect())
+            }
+            (PixelData::U32(vec), SampleType::F16) => PixelData::F16(
+                vec.iter()
+                    .map(|value| f16::from_f32(*value as f32))
+                    .collect(),
+            ),
+            (PixelData::U32(vec), SampleType::F32) => {
+                PixelData::F32(vec.iter().map(|value| *value as f32).collect())
+            }
+            (PixelData::U32(vec), SampleType::U32) => PixelData::U32(vec.clone()),
+        }
+    }
+
+    fn sample_f64(&self, index: usize) -> f64 {
+        match self {
+            PixelData::F16(vec) => vec[index].to_f64(),
+            PixelData::F32(vec) => vec[index] as f64,
+            PixelData::U32(vec) => vec[index] as f64,
+        }
+    }
+
+    /// Replaces every sample with `f(index, sample)`, computed in f64 and stored back in the
+    /// buffer's own type like `converted` would.
+    fn map_samples(&mut self, mut f: impl FnMut(usize, f64) -> f64) {
+        match self {
+            PixelData::F16(vec) => vec
+                .iter_mut()
+                .enumerate()
+                .for_each(|(index, value)| *value = f16::from_f64(f(index, value.to_f64()))),
+            PixelData::F32(vec) => vec
+                .iter_mut()
+                .enumerate()
+                .for_each(|(index, value)| *value = f(index, *value as f64) as f32),
+            PixelData::U32(vec) => vec
+                .iter_mut()
+                .enumerate()
+                .for_each(|(index, value)| *value = f(index, *value as f64) as u32),
+        }
+    }
+
+    /// Reverses the order of the rows, e.g. to convert between top-down and bottom-up layouts.
+    fn flip_rows(&mut self, width: usize) {
+        match self {
+            PixelData::F32(vec) => reverse_rows(vec, width),
+            PixelData::F16(vec) => reverse_rows(vec, width),
+            PixelData::U32(vec) => reverse_rows(vec, width),
+        }
+    }
+
+    fn rotate_rows_right(&mut self, width: usize, shift: usize) {
+        match self {
+            PixelData::F32(vec) => vec
+                .chunks_exact_mut(width)
+                .for_each(|row| row.rotate_right(shift)),
+            PixelData::F16(vec) => vec
+                .chunks_exact_mut(width)
+                .for_each(|row| row.rotate_right(shift)),
+            PixelData::U32(vec) => vec
+                .chunks_exact_mut(width)
+                .for_each(|row| row.rotate_right(shift)),
+        }
+    }
+}
+
+fn reverse_rows<T>(samples: &mut [T], width: usize) {
+    if width == 0 {
+        return;
+    }
+
+    let rows = samples.len() / width;
+    for row in 0..rows / 2 {
+        let (top, bottom) = samples.split_at_mut((rows - 1 - row) * width);
+        top[row * width..(row + 1) * width].swap_with_slice(&mut bottom[..width]);
+    }
+}
+
+fn _validate_width_height_pixels(
+    width_option: Option<usize>,
+    height_option: Option<usize>,
+    pixels: &PixelData,
+) -> PyResult<()> {
+    if width_option.is_none() || height_option.is_none() {
+        return Err(PyIOError::new_err(
+            "Layer width and height must be set before adding a channel",
+        ));
+    }
+
+    let width = width_option.unwrap();
+    let height = height_option.unwrap();
+    let expected_pixels = width * height;
+    let actual_pixels = pixels.len();
+
+    if expected_pixels != actual_pixels {
+        return Err(PyIOError::new_err(
+            "Width * height must match the number of pixels",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Copies the samples of an array of any shape in row-major (C) order. Arrays that are not
+/// C-contiguous, e.g. transposed or strided views, are copied element by element instead of in
+/// memory order, so they are written exactly as numpy presents them.
+fn numpy_samples<T: numpy::Element + Copy>(array: PyReadonlyArrayDyn<T>) -> Vec<T> {
+    let view = array.as_array();
+    match view.as_slice() {
+        Some(samples) => samples.to_vec(),
+        None => view.iter().copied().collect(),
+    }
+}
+
+/// The sample type a numpy array is stored as: float16, float32, and uint32 as is, other
+/// floats (e.g. float64) as f32, and integers as u32 as long as every value fits. Arrays must
+/// be in the machine's native byte order; byte-swapped arrays (e.g. a `>f4` dtype on
+/// little-endian machines) are rejected rather than reinterpreted. EXR files are always
+/// little-endian on disk, which exr handles when writing.
+fn numpy_sample_type(array: &Bound<'_, PyAny>) -> PyResult<SampleType> {
+    let unsupported = || PyErr::new::<pyo3::exceptions::PyTypeError, _>("Unsupported array type");
+    let dtype = array.getattr("dtype").map_err(|_| unsupported())?;
+    if !dtype.getattr("isnative")?.extract::<bool>()? {
+        return Err(PyValueError::new_err(format!(
+            "Pixels must be in native byte order, got dtype {}. Convert them with \
+             array.astype(array.dtype.newbyteorder(\"=\"))",
+            dtype.str()?
+        )));
+    }
+
+    let kind = dtype.getattr("kind")?.extract::<String>()?;
+    let itemsize = dtype.getattr("itemsize")?.extract::<usize>()?;
+    match (kind.as_str(), itemsize) {
+        ("f", 2) => Ok(SampleType::F16),
+        ("f", _) => Ok(SampleType::F32),
+        ("u", 4) => Ok(SampleType::U32),
+        ("i" | "u", _) => {
+            let size = array.getattr("size")?.extract::<usize>()?;
+            let fits = size == 0
+                || (array
+                    .call_method0("min")?
+                    .extract::<i64>()
+                    .is_ok_and(|min| min >= 0)
+                    && array
+                        .call_method0("max")?
+                        .extract::<u64>()
+                        .is_ok_and(|max| max <= u32::MAX as u64));
+            if !fits {
+                return Err(PyValueError::new_err(
+                    "Integer pixels must be between 0 and 4294967295 to be stored as u32",
+                ));
+            }
+            Ok(SampleType::U32)
+        }
+        _ => Err(unsupported()),
+    }
+}
+
+fn numpy_dtype_name(sample_type: SampleType) -> &'static str {
+    match sample_type {
+        SampleType::F16 => "float16",
+        SampleType::F32 => "float32",
+        SampleType::U32 => "uint32",
+    }
+}
+
+/// Copies a numpy array into pixel samples of the type `numpy_sample_type` picks.
+fn convert_numpy_array(array: &Bound<'_, PyAny>) -> PyResult<PixelData> {
+    let sample_type = numpy_sample_type(array)?;
+    let kwargs = PyDict::new(array.py());
+    kwargs.set_item("copy", false)?;
+    let array = array.call_method("astype", (numpy_dtype_name(sample_type),), Some(&kwargs))?;
+
+    Ok(match sample_type {
+        SampleType::F16 => PixelData::F16(numpy_samples(array.extract()?)),
+        SampleType::F32 => PixelData::F32(numpy_samples(array.extract()?)),
+        SampleType::U32 => PixelData::U32(numpy_samples(array.extract()?)),
+    })
+}
+
+impl ExrLayer {
+    fn add_channel<'py>(
+        &mut self,
+        channel: String,
+        pixels: &Bound<'py, PyAny>,
+        dtype: Option<&str>,
+    ) -> PyResult<()> {
+        let mut array_data = convert_numpy_array(pixels)?;
+        if let Some(dtype) = dtype {
+            array_data = array_data.converted(parse_sample_type(dtype)?);
+        }
+        _validate_width_height_pixels(self.width, self.height, &array_data)?;
+
+        if self.pixels.is_none() {
+            self.pixels = Some(vec![array_data]);
+        } else {
+            self.pixels.as_mut().unwrap().push(array_data);
+        }
+        self.channels.push(channel);
+
+        Ok(())
+    }
+
+    /// Checks that every channel name has a pixel buffer with one sample per pixel of the
+    /// channel's (possibly subsampled) resolution, and that no name repeats.
+    fn check_layout(&self) -> std::result::Result<(), String> {
+        let (width, height) = match (self.width, self.height) {
+            (Some(width), Some(height)) => (width, height),
+            _ => return Err("Layer width and height must be set".to_string()),
+        };
+
+        let pixels = self.pixels.as_deref().unwrap_or_default();
+        if pixels.len() != self.channels.len() {
+            return Err(format!(
+                "Layer has {} channel names but {} pixel buffers",
+                self.channels.len(),
+                pixels.len()
+            ));
+        }
+
+        for (index, (channel, channel_pixels)) in self.channels.iter().zip(pixels).enumerate() {
+            if self.channels[..index].contains(channel) {
+                return Err(format!("Channel {} appears more than once", channel));
+            }
+
+            let (x_sampling, y_sampling) = self.sampling_factors(channel);
+            let (channel_width, channel_height) = (width / x_sampling, height / y_sampling);
+            if channel_pixels.is_empty() && channel_width * channel_height > 0 {
+                return Err(format!("Channel {} has not been filled", channel));
+            }
+            if channel_pixels.len() != channel_width * channel_height {
+                return Err(format!(
+                    "Channel {} has {} pixels, expected {}x{}",
+                    channel,
+                    channel_pixels.len(),
+                    channel_width,
+                    channel_height
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A copy of the layer without pixels that keeps each channel's sample type, to infer the
+    /// file headers from without duplicating any pixel data.
+    fn layout(&self) -> ExrLayer {
+        let pixels = self.pixels.as_ref().map(|pixels| {
+            pixels
+                .iter()
+                .map(|channel| PixelData::empty(channel.sample_type()))
+                .collect()
+        });
+
+        ExrLayer {
+            name: self.name.clone(),
+            channels: self.channels.clone(),
+            width: self.width,
+            height: self.height,
+            pixels,
+            attributes: self.attributes.clone(),
+            sampling: self.sampling.clone(),
+            perceptually_linear: self.perceptually_linear.clone(),
+            default_values: self.default_values.clone(),
+            file_name: self.file_name.clone(),
+            line_order: self.line_order,
+            compression: self.compression,
+            blocks: self.blocks,
+            level_mode: self.level_mode,
+        }
+    }
+
+    /// Subsampling factors of a channel, (1, 1) unless it is stored at reduced resolution.
+    fn sampling_factors(&self, name: &str) -> (usize, usize) {
+        self.sampling.get(name).copied().unwrap_or((1, 1))
+    }
+
+    /// Whether the layer is called `name`, now or in the file it was loaded from.
+    fn is_named(&self, name: &str) -> bool {
+        self.name.as_deref() == Some(name) || self.file_name.as_deref() == Some(name)
+    }
+
+    fn set_position(&mut self, x: i32, y: i32) {
+        self.attributes.insert(
+            Text::from("layer_position"),
+            AttributeValue::IntVec2(Vec2(x, y)),
+        );
+    }
+
+    fn set_data_window(&mut self, x: i32, y: i32, width: usize, height: usize) -> PyResult<()> {
+        let has_pixels = self
+            .pixels
+            .as_ref()
+            .is_some_and(|pixels| !pixels.is_empty());
+        if has_pixels && (self.width, self.height) != (Some(width), Some(height)) {
+            return Err(PyValueError::new_err(format!(
+                "Data window size {}x{} does not match the layer size {}x{}",
+                width,
+                height,
+                self.width.unwrap_or(0),
+                self.height.unwrap_or(0)
+            )));
+        }
+
+        self.width = Some(width);
+        self.height = Some(height);
+        self.set_position(x, y);
+        Ok(())
+    }
+
+    /// Samples per row of a channel, which is smaller than the width for subsampled channels.
+    fn row_length(&self, channel: &str) -> usize {
+        let x_sampling = self.sampling.get(channel).map_or(1, |(x, _)| *x).max(1);
+        self.width.unwrap_or(0) / x_sampling
+    }
+
+    /// Copies the samples of a decoded block into the layer's zeroed pixels, as laid out by
+    /// `empty_layer`.
+    fn copy_block(&mut self, block: &UncompressedBlock, channels: &ChannelList) {
+        for line in block.lines(channels) {
+            let channel = line.location.channel;
+            let row_length = self.row_length(&self.channels[channel]);
+            let start = line.location.position.1 * row_length + line.location.position.0;
+            if let Some(pixels) = self.pixels.as_mut() {
+                pixels[channel].copy_from_le_bytes(start, line.value);
+            }
+        }
+    }
+
+    fn flip_y(&mut self) {
+        let row_lengths: Vec<usize> = self
+            .channels
+            .iter()
+            .map(|channel| self.row_length(channel))
+            .collect();
+        for (channel, row_length) in self.pixels.iter_mut().flatten().zip(row_lengths) {
+            channel.flip_rows(row_length);
+        }
+    }
+}
+
+#[pymethods]
+impl ExrLayer {
+    #[new]
+    #[pyo3(signature = (name = None))]
+    fn new(name: Option<String>) -> Self {
+        Self {
+            name,
+            channels: Vec::new(),
+            width: None,
+            height: None,
+            pixels: None,
+            attributes: HashMap::new(),
+            sampling: HashMap::new(),
+            perceptually_linear: HashSet::new(),
+            default_values: HashMap::new(),
+            file_name: None,
+            line_order: LineOrder::Increasing,
+            // Requirements for ACES-compliance https://openexr.com/en/latest/bin/exr2aces.html
+            compression: Compression::PIZ,
+            blocks: Blocks::ScanLines,
+            level_mode: LevelMode::Singular,
+        }
+    }
+
+    /// Builds a single-channel layer (depth, masks, ...) from a 2D `(height, width)` array.
+    /// The sample type follows the array's dtype (see `with_channel`) unless `dtype` is given.
+    #[staticmethod]
+    #[pyo3(signature = (name, pixels, channel = "Y".to_string(), dtype = None))]
+    fn from_grayscale<'py>(
+        name: Option<String>,
+        pixels: &Bound<'py, PyAny>,
+        channel: String,
+        dtype: Option<&str>,
+    ) -> PyResult<Self> {
+        let (height, width) = match pixels.getattr("shape")?.extract::<(usize, usize)>() {
+            Ok(shape) => shape,
+            Err(_) => return Err(PyValueError::new_err("Expected a 2D (height, width) array")),
+        };
+
+        let mut layer = Self::new(name);
+        layer.width = Some(width);
+        layer.height = Some(height);
+        layer.add_channel(channel, &pixels.call_method0("ravel")?, dtype)?;
+        Ok(layer)
+    }
+
+    /// Declares a layer's channels as `(name, dtype)` pairs, e.g. `[("R", "f16"), ("Z", "f32")]`,
+    /// without any pixels, so a fixed schema can be filled frame by frame with `set_channel`.
+    /// Saving raises until every channel has been filled.
+    #[staticmethod]
+    #[pyo3(signature = (name, width, height, channels))]
+    fn from_schema(
+        name: Option<String>,
+        width: usize,
+        height: usize,
+        channels: Vec<(String, String)>,
+    ) -> PyResult<Self> {
+        let mut layer = Self::new(name);
+        layer.width = Some(width);
+        layer.height = Some(height);
+
+        let mut pixels = Vec::with_capacity(channels.len());
+        for (channel, dtype) in channels {
+            if layer.channels.contains(&channel) {
+                return Err(PyValueError::new_err(format!(
+                    "Channel {} is declared more than once",
+                    channel
+                )));
+            }
+            pixels.push(PixelData::empty(parse_sample_type(&dtype)?));
+            layer.channels.push(channel);
+        }
+        layer.pixels = Some(pixels);
+        Ok(layer)
+    }
+
+    /// Builds a layer from a channel-first `(channels, height, width)` array, e.g. an ML tensor,
+    /// naming the planes along axis 0 with `channels`. Each plane is copied straight from the
+    /// array, so no transpose to `(height, width, channels)` is needed. The sample type follows
+    /// the array's dtype (see `with_channel`) unless `dtype` is given.
+    #[staticmethod]
+    #[pyo3(signature = (name, pixels, channels, dtype = None))]
+    fn from_chw<'py>(
+        name: Option<String>,
+        pixels: &Bound<'py, PyAny>,
+        channels: Vec<String>,
+        dtype: Option<&str>,
+    ) -> PyResult<Self> {
+        let (count, height, width) =
+            match pixels.getattr("shape")?.extract::<(usize, usize, usize)>() {
+                Ok(shape) => shape,
+                Err(_) => {
+                    return Err(PyValueError::new_err(
+                        "Expected a 3D (channels, height, width) array",
+                    ))
+                }
+            };
+        if count != channels.len() {
+            return Err(PyValueError::new_err(format!(
+                "Array has {} channels but {} channel names were given",
+                count,
+                channels.len()
+            )));
+        }
+
+        let mut layer = Self::new(name);
+        layer.width = Some(width);
+        layer.height = Some(height);
+        for (index, channel) in channels.into_iter().enumerate() {
+            layer.add_channel(channel, &pixels.get_item(index)?, dtype)?;
+        }
+        Ok(layer)
+    }
+
+    /// Returns a channel as a 2D `(height, width)` array. Without a name, the layer must
+    /// have exactly one channel.
+    #[pyo3(signature = (channel = None))]
+    fn as_grayscale<'py>(
+        &self,
+        py: Python<'py>,
+        channel: Option<&str>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let index = match channel {
+            Some(channel) => self.channel_index(channel)?,
+            None if self.channels.len() == 1 => 0,
+            None => {
+                return Err(PyValueError::new_err(format!(
+                    "Layer has {} channels, pass the channel to use",
+                    self.channels.len()
+                )))
+            }
+        };
+
+        let pixels = match self.pixels.as_ref().and_then(|pixels| pixels.get(index)) {
+            Some(pixels) => pixels,
+            None => return Err(PyIOError::new_err("Layer has no pixels")),
+        };
+        let shape = (self.height.unwrap_or(0), self.width.unwrap_or(0));
+
+        vec_to_numpy_array(py, pixels).call_method1("reshape", (shape,))
+    }
+
+    fn name(&self) -> Option<String> {
+        self.name.clone()
+    }
+
+    /// The layer's name in the file it was loaded from when it repeated an earlier part's name
+    /// and was suffixed on load, e.g. "beauty" for "beauty.1"; `None` otherwise.
+    fn file_name(&self) -> Option<String> {
+        self.file_name.clone()
+    }
+
+    /// Renames the layer. The name takes precedence over any `layer_name` attribute that was
+    /// loaded with the layer, and clears `file_name`.
+    #[pyo3(signature = (name = None))]
+    fn with_name(mut slf: PyRefMut<'_, Self>, name: Option<String>) -> PyRefMut<'_, Self> {
+        slf.name = name;
+        slf.file_name = None;
+        slf
+    }
+
+    fn channels(&self) -> Vec<String> {
+        self.channels.clone()
+    }
+
+    /// Moves the named channels to the front in the given order, keeping the relative order of
+    /// the rest. The EXR format stores channels alphabetically, so the written file is always
+    /// sorted; use this (or `channel_order` when loading) to restore e.g. R, G, B, A after reading.
+    #[pyo3(signature = (order = vec!["R".to_string(), "G".to_string(), "B".to_string(), "A".to_string()]))]
+    fn reorder_channels(&mut self, order: Vec<String>) {
+        let rank = |channel: &String| {
+            order
+                .iter()
+                .position(|name| name == channel)
+                .unwrap_or(order.len())
+        };
+
+        let mut indices: Vec<usize> = (0..self.channels.len()).collect();
+        indices.sort_by_key(|index| rank(&self.channels[*index]));
+
+        self.channels = indices
+            .iter()
+            .map(|index| self.channels[*index].clone())
+            .collect();
+        if let Some(pixels) = self.pixels.as_mut() {
+            let mut taken: Vec<Option<PixelData>> = pixels.drain(..).map(Some).collect();
+            *pixels = indices
+                .iter()
+                .flat_map(|index| taken.get_mut(*index).and_then(Option::take))
+                .collect();
+        }
+    }
+
+    /// Subsampling factors `(x, y)` of a channel, e.g. (2, 2) for a half resolution chroma
+    /// channel. Channels stored at full resolution report (1, 1). Raises a `KeyError` for a
+    /// channel the layer doesn't have.
+    fn channel_sampling(&self, name: &str) -> PyResult<(usize, usize)> {
+        self.channel_index(name)?;
+        Ok(self.sampling_factors(name))
+    }
+
+    fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Returns the position of `name` in `channels()`, i.e. its index into `pixels()`.
+    /// If the name appears more than once, the first match is returned.
+    fn channel_index(&self, name: &str) -> PyResult<usize> {
+        self.channels
+            .iter()
+            .position(|channel| channel == name)
+            .ok_or_else(|| PyKeyError::new_err(format!("Channel {} not found", name)))
+    }
+
+    fn width(&self) -> Option<usize> {
+        self.width
+    }
+
+    fn with_width(mut slf: PyRefMut<'_, Self>, width: usize) -> PyRefMut<'_, Self> {
+        slf.width = Some(width);
+        slf
+    }
+
+    fn height(&self) -> Option<usize> {
+        self.height
+    }
+
+    fn with_height(mut slf: PyRefMut<'_, Self>, height: usize) -> PyRefMut<'_, Self> {
+        slf.height = Some(height);
+        slf
+    }
+
+    /// Order in which scanlines are stored: "increasing", "decreasing", or "unspecified".
+    fn line_order(&self) -> &'static str {
+        line_order_name(self.line_order)
+    }
+
+    fn with_line_order<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        line_order: &str,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        slf.line_order = parse_line_order(line_order)?;
+        Ok(slf)
+    }
+
+    /// Compression used when saving: "none", "rle", "zips", "zip", "piz", "pxr24", "b44",
+    /// "b44a", "dwaa", or "dwab". Loaded layers keep the compression of the file.
+    fn compression(&self) -> &'static str {
+        compression_name(self.compression)
+    }
+
+    fn with_compression<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        compression: &str,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        slf.compression = parse_compression(compression)?;
+        Ok(slf)
+    }
+
+    /// Tile size used when saving as `(width, height)`, or `None` for scanlines. Loaded layers
+    /// keep the block layout of the file.
+    fn tile_size(&self) -> Option<(usize, usize)> {
+        match self.blocks {
+            Blocks::ScanLines => None,
+            Blocks::Tiles(size) => Some((size.0, size.1)),
+        }
+    }
+
+    /// Tiling as `(tile_width, tile_height, level_mode)`, or `None` for scanlines. The level
+    /// mode is "one_level", "mipmap", or "ripmap" as stored in the file the layer was loaded
+    /// from; saving only writes the full resolution, so `with_tile_size` resets it to
+    /// "one_level".
+    fn tile_description(&self) -> Option<(usize, usize, &'static str)> {
+        match self.blocks {
+            Blocks::ScanLines => None,
+            Blocks::Tiles(size) => Some((size.0, size.1, level_mode_name(self.level_mode))),
+        }
+    }
+
+    #[pyo3(signature = (tile_size = None))]
+    fn with_tile_size(
+        mut slf: PyRefMut<'_, Self>,
+        tile_size: Option<(usize, usize)>,
+    ) -> PyResult<PyRefMut<'_, Self>> {
+        slf.level_mode = LevelMode::Singular;
+        slf.blocks = match tile_size {
+            None => Blocks::ScanLines,
+            Some((0, _)) | Some((_, 0)) => {
+                return Err(PyValueError::new_err("Tile size must be positive"));
+            }
+            Some((width, height)) => Blocks::Tiles(Vec2(width, height)),
+        };
+        Ok(slf)
+    }
+
+    /// Position of the layer's data window. Defaults to the origin.
+    fn position(&self) -> (i32, i32) {
+        match self.attributes.get(&Text::from("layer_position")) {
+            Some(AttributeValue::IntVec2(position)) => (position.0, position.1),
+            _ => (0, 0),
+        }
+    }
+
+    /// The camera's screen window for projection math, as `{"center": (x, y), "width": w}`
+    /// from the `screenWindowCenter` and `screenWindowWidth` attributes. Missing values take
+    /// the EXR defaults, a center of (0, 0) and a width of 1.
+    fn screen_window<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let (center, width) = attributes::screen_window(&self.attributes);
+        let window = PyDict::new(py);
+        window.set_item("center", (center.0, center.1))?;
+        window.set_item("width", width)?;
+        Ok(window)
+    }
+
+    /// Sets both screen window attributes. The width must be positive.
+    fn with_screen_window(
+        mut slf: PyRefMut<'_, Self>,
+        center: (f32, f32),
+        width: f32,
+    ) -> PyResult<PyRefMut<'_, Self>> {
+        if !(width.is_finite() && width > 0.0) {
+            return Err(PyValueError::new_err(format!(
+                "Screen window width must be positive, got {}",
+                width
+            )));
+        }
+
+        let center = AttributeValue::FloatVec2(Vec2(center.0, center.1));
+        attributes::set_standard_attribute(&mut slf.attributes, "screen_window_center", center);
+        let width = AttributeValue::F32(width);
+        attributes::set_standard_attribute(&mut slf.attributes, "screen_window_width", width);
+        Ok(slf)
+    }
+
+    /// The horizontal output density from the `xDensity` attribute, which EXR stores in pixels
+    /// per inch. `unit` is "inch" or "cm" and only changes the unit the value is reported in;
+    /// the vertical density is this times the image's pixel aspect ratio.
+    #[pyo3(signature = (unit = "inch"))]
+    fn horizontal_density(&self, unit: &str) -> PyResult<Option<f32>> {
+        let inches_per_unit = inches_per_density_unit(unit)?;
+        Ok(attributes::horizontal_density(&self.attributes)
+            .map(|density| density * inches_per_unit))
+    }
+
+    /// Sets the `xDensity` attribute from a density in pixels per `unit` ("inch" or "cm").
+    #[pyo3(signature = (density, unit = "inch"))]
+    fn with_horizontal_density<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        density: f32,
+        unit: &str,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        if !(density.is_finite() && density > 0.0) {
+            return Err(PyValueError::new_err(format!(
+                "Density must be positive, got {}",
+                density
+            )));
+        }
+
+        let density = AttributeValue::F32(density / inches_per_density_unit(unit)?);
+        attributes::set_standard_attribute(&mut slf.attributes, "horizontal_density", density);
+        Ok(slf)
+    }
+
+    /// Moves the layer's data window, e.g. to a negative origin for overscan pixels that lie
+    /// outside the display window.
+    fn with_position(mut slf: PyRefMut<'_, Self>, x: i32, y: i32) -> PyRefMut<'_, Self> {
+        slf.set_position(x, y);
+        slf
+    }
+
+    /// The layer's data window, i.e. its position and size.
+    fn data_window(&self) -> Box2i {
+        let (x, y) = self.position();
+        Box2i {
+            x,
+            y,
+            width: self.width.unwrap_or(0),
+            height: self.height.unwrap_or(0),
+        }
+    }
+
+    /// Sets the position and size of the layer's data window. The size must match the pixels
+    /// already added to the layer. A `data_window` (or `dataWindow`) attribute passed to
+    /// `with_attributes` is applied the same way.
+    fn with_data_window(
+        mut slf: PyRefMut<'_, Self>,
+        x: i32,
+        y: i32,
+        width: usize,
+        height: usize,
+    ) -> PyResult<PyRefMut<'_, Self>> {
+        slf.set_data_window(x, y, width, height)?;
+        Ok(slf)
+    }
+
+    /// The alpha channel as a 2D `(height, width)` array in its stored sample type, or `None`
+    /// if the layer has none. Looks for "A", then "a", then "alpha", and returns the first
+    /// that exists.
+    fn alpha<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyAny>>> {
+        let index = ALPHA_CHANNEL_NAMES
+            .iter()
+            .find_map(|name| self.channels.iter().position(|channel| channel == name));
+        let (index, pixels) =
+            match index.and_then(|index| Some((index, self.pixels.as_ref()?.get(index)?))) {
+                Some(found) => found,
+                None => return Ok(None),
+            };
+
+        let row_length = self.row_length(&self.channels[index]);
+        let rows = pixels.len().checked_div(row_length).unwrap_or(0);
+        let array =
+            vec_to_numpy_array(py, pixels).call_method1("reshape", ((rows, row_length),))?;
+        Ok(Some(array))
+    }
+
+    /// Trims the borders where `channel` is zero, e.g. the transparent margin around a sprite
+    /// or FX element. Returns the cropped layer and the `(x, y)` offset of the kept region
+    /// within this layer; the cropped layer's data window is moved by that offset, so it lines
+    /// up with the original when saved. NaN samples count as non-zero. Raises a `ValueError`
+    /// when `channel` is zero everywhere or the layer has subsampled channels.
+    #[pyo3(signature = (channel = "A"))]
+    fn auto_crop(&self, channel: &str) -> PyResult<(ExrLayer, (i32, i32))> {
+        let index = self.channel_index(channel)?;
+        self.assert_valid()?;
+        if self
+            .channels
+            .iter()
+            .any(|name| self.sampling_factors(name) != (1, 1))
+        {
+            return Err(PyValueError::new_err(
+                "auto_crop does not support subsampled channels",
+            ));
+        }
+
+        let (width, height) = (self.width.unwrap_or(0), self.height.unwrap_or(0));
+        let pixels = self.pixels.as_deref().unwrap_or_default();
+        let mask = &pixels[index];
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+        for y in 0..height {
+            for x in 0..width {
+                if mask.sample_f32(y * width + x) == 0.0 {
+                    continue;
+                }
+                bounds = Some(match bounds {
+                    Some((left, top, right, bottom)) => {
+                        (left.min(x), top.min(y), right.max(x + 1), bottom.max(y + 1))
+                    }
+                    None => (x, y, x + 1, y + 1),
+                });
+            }
+        }
+        let (left, top, right, bottom) = match bounds {
+            Some(bounds) => bounds,
+            None => {
+                return Err(PyValueError::new_err(format!(
+                    "Channel {} is zero everywhere, there is nothing to keep",
+                    channel
+                )))
+            }
+        };
+
+        let (crop_width, crop_height) = (right - left, bottom - top);
+        let cropped_pixels = pixels
+            .iter()
+            .map(|data| {
+                let mut cropped = PixelData::zeroed(data.sample_type(), crop_width * crop_height);
+                for row in 0..crop_height {
+                    let source = (top + row) * width + left;
+                    cropped.copy_samples(row * crop_width, data, source, crop_width);
+                }
+                cropped
+            })
+            .collect();
+
+        let mut cropped = self.layout();
+        cropped.width = Some(crop_width);
+        cropped.height = Some(crop_height);
+        cropped.pixels = Some(cropped_pixels);
+        let (x, y) = self.position();
+        let offset = (left as i32, top as i32);
+        cropped.set_position(x + offset.0, y + offset.1);
+        Ok((cropped, offset))
+    }
+
+    fn pixels<'py>(&self, py: Python<'py>) -> PyResult<Option<Vec<Bound<'py, PyAny>>>> {
+        let pixels = self.pixels.as_ref().map(|channels| {
+            channels
+                .iter()
+                .map(|channel| vec_to_numpy_array(py, channel))
+                .collect()
+        });
+
+        Ok(pixels)
+    }
+
+    fn pixels_f32<'py>(&self, py: Python<'py>) -> PyResult<Option<Vec<Bound<'py, PyAny>>>> {
+        let pixels = self.pixels.as_ref().map(|channels| {
+            channels
+                .iter()
+                .map(|channel| vec_to_numpy_array_f32(py, channel))
+                .collect()
+        });
+
+        Ok(pixels)
+    }
+
+    /// Returns the channels named in `channel_order` as one float32 `(height, width, channels)`
+    /// array with the samples of each pixel side by side, e.g. `["R", "G", "B", "A"]` for an
+    /// RGBA texture upload. `pixels_f32()` keeps returning one planar array per channel.
+    fn interleaved<'py>(
+        &self,
+        py: Python<'py>,
+        channel_order: Vec<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let (width, height) = (self.width.unwrap_or(0), self.height.unwrap_or(0));
+        let planes = channel_order
+            .iter()
+            .map(|name| {
+                let index = self.channel_index(name)?;
+                if self.sampling_factors(name) != (1, 1) {
+                    return Err(PyValueError::new_err(format!(
+                        "Channel {} is subsampled and can't be interleaved",
+                        name
+                    )));
+                }
+                match self.pixels.as_ref().and_then(|pixels| pixels.get(index)) {
+                    Some(pixels) if pixels.len() == width * height => Ok(pixels),
+                    Some(_) => Err(PyIOError::new_err(format!(
+                        "Channel {} doesn't match the layer size",
+                        name
+                    ))),
+                    None => Err(PyIOError::new_err("Layer has no pixels")),
+                }
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+
+        let mut data = Vec::with_capacity(width * height * planes.len());
+        for index in 0..width * height {
+            data.extend(planes.iter().map(|plane| plane.sample_f32(index)));
+        }
+
+        PyArray1::from_vec(py, data)
+            .into_any()
+            .call_method1("reshape", ((height, width, planes.len()),))
+    }
+
+    /// Adds a channel stored with the sample type of the array's dtype: float16 as f16, float32
+    /// as f32, and uint32 as u32. Other floats (e.g. float64) are stored as f32 and other
+    /// integers as u32, which raises a `ValueError` for values outside its range. `dtype`
+    /// ("f16", "f32", or "u32") overrides the inferred sample type.
+    /// Byte-swapped arrays (non-native byte order, e.g. `>f4`) raise a `ValueError`.
+    /// `perceptually_linear` sets the channel's `pLinear` flag (see `perceptually_linear`) and
+    /// `default_value` its value outside the data window (see `default_value`).
+    #[pyo3(signature = (channel, pixels, dtype = None, perceptually_linear = false, default_value = None))]
+    fn with_channel<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        channel: String,
+        pixels: &Bound<'py, PyAny>,
+        dtype: Option<&str>,
+        perceptually_linear: bool,
+        default_value: Option<f32>,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        slf.add_channel(channel.clone(), pixels, dtype)?;
+        if perceptually_linear {
+            slf.perceptually_linear.insert(channel.clone());
+        }
+        if let Some(value) = default_value {
+            slf.default_values.insert(channel, value);
+        }
+        Ok(slf)
+    }
+
+    /// Whether the channel's `pLinear` flag is set, which tells B44 compression that its values
+    /// are already perceptually linear, e.g. chroma, rather than linear light, so they are
+    /// quantized evenly instead of through B44's log curve.
+    fn perceptually_linear(&self, name: &str) -> PyResult<bool> {
+        self.channel_index(name)?;
+        Ok(self.perceptually_linear.contains(name))
+    }
+
+    /// Sets or clears the `pLinear` flag of an existing channel, written on the next save.
+    #[pyo3(signature = (name, enabled = true))]
+    fn with_perceptually_linear<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        name: &str,
+        enabled: bool,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        slf.channel_index(name)?;
+        if enabled {
+            slf.perceptually_linear.insert(name.to_string());
+        } else {
+            slf.perceptually_linear.remove(name);
+        }
+        Ok(slf)
+    }
+
+    /// The channel's value outside the data window, e.g. 0 for an empty mask or 1 for depth
+    /// that should read as far away, or `None` if unset, which readers treat as zero. Stored
+    /// as an f32 `defaultValue.<channel>` layer attribute since EXR channels have no such
+    /// field, and kept out of `attributes()` on load.
+    fn default_value(&self, name: &str) -> PyResult<Option<f32>> {
+        self.channel_index(name)?;
+        Ok(self.default_values.get(name).copied())
+    }
+
+    /// Sets or, with `None`, clears the default value of an existing channel.
+    #[pyo3(signature = (name, value = None))]
+    fn with_default_value<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        name: &str,
+        value: Option<f32>,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        slf.channel_index(name)?;
+        match value {
+            Some(value) => slf.default_values.insert(name.to_string(), value),
+            None => slf.default_values.remove(name),
+        };
+        Ok(slf)
+    }
+
+    /// Drops all pixel buffers while keeping the channel names, sample types, and dimensions,
+    /// so the layer can be refilled with `set_channel` for the next frame.
+    fn clear_pixels(&mut self) {
+        if let Some(pixels) = self.pixels.as_mut() {
+            for channel in pixels.iter_mut() {
+                *channel = PixelData::empty(channel.sample_type());
+            }
+        }
+    }
+
+    /// Returns a copy of the layer with every channel converted to `dtype` ("f16", "f32", or
+    /// "u32"), which is also the sample type the channels are written with on the next save.
+    /// Narrowing to f16 rounds to the nearest half (values beyond ±65504 become infinite).
+    /// Floats cast to u32 are truncated toward zero like numpy's `astype`, saturating at 0 and
+    /// 2**32 - 1, with NaN becoming 0; round first, e.g. with `np.rint`, for nearest ids.
+    fn astype(&self, dtype: &str) -> PyResult<Self> {
+        let sample_type = parse_sample_type(dtype)?;
+
+        let mut layer = self.clone();
+        if let Some(pixels) = layer.pixels.as_mut() {
+            for channel in pixels.iter_mut() {
+                *channel = channel.converted(sample_type);
+            }
+        }
+
+        Ok(layer)
+    }
+
+    /// Fills a channel declared with `from_schema` (or kept by `clear_pixels`), replacing any
+    /// pixels it had. The array must already have the channel's sample type, after the usual
+    /// mapping of other floats to float32 and integers to uint32, and one sample per pixel of
+    /// the channel's resolution, which subsampled channels divide by `channel_sampling`.
+    /// An unknown channel raises a `KeyError` and mismatched pixels a `ValueError`.
+    fn set_channel<'py>(&mut self, name: &str, pixels: &Bound<'py, PyAny>) -> PyResult<()> {
+        let index = self.channel_index(name)?;
+        let array_data = convert_numpy_array(pixels)?;
+        let slot = match self
+            .pixels
+            .as_mut()
+            .and_then(|pixels| pixels.get_mut(index))
+        {
+            Some(slot) => slot,
+            None => {
+                return Err(PyIOError::new_err(format!(
+                    "Channel {} has no pixel buffer",
+                    name
+                )))
+            }
+        };
+        if slot.sample_type() != array_data.sample_type() {
+            return Err(PyValueError::new_err(format!(
+                "Channel {} is declared as {}, got {} pixels",
+                name,
+                slot.dtype_name(),
+                array_data.dtype_name()
+            )));
+        }
+
+        let (x_sampling, y_sampling) = self.sampling.get(name).copied().unwrap_or((1, 1));
+        let (width, height) = (
+            self.width.unwrap_or(0) / x_sampling,
+            self.height.unwrap_or(0) / y_sampling,
+        );
+        if array_data.len() != width * height {
+            return Err(PyValueError::new_err(format!(
+                "Channel {} needs {}x{} pixels, got {}",
+                name,
+                width,
+                height,
+                array_data.len()
+            )));
+        }
+
+        *slot = array_data;
+        Ok(())
+    }
+
+    /// Multiplies every sample of the channel by `factor` in place, e.g. for an exposure
+    /// adjustment, without copying the pixels out to numpy. The channel keeps its sample type:
+    /// f16 results are rounded to the nearest half and u32 results truncated and saturated.
+    fn scale_channel<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        name: &str,
+        factor: f64,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        let index = slf.channel_index(name)?;
+        match slf.pixels.as_mut().and_then(|pixels| pixels.get_mut(index)) {
+            Some(pixels) => pixels.map_samples(|_, value| value * factor),
+            None => return Err(PyIOError::new_err("Layer has no pixels")),
+        }
+        Ok(slf)
+    }
+
+    /// Sums channels `a` and `b` sample by sample into `out`, e.g. to combine light passes.
+    /// An existing `out` channel, which may be `a` or `b`, is overwritten in its own sample
+    /// type; otherwise `out` is added with the sample type of `a`. The channels must have the
+    /// same number of samples.
+    fn add_channels<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        a: &str,
+        b: &str,
+        out: &str,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        let (a_index, b_index) = (slf.channel_index(a)?, slf.channel_index(b)?);
+        let out_index = slf.channels.iter().position(|channel| channel == out);
+        let pixels = match slf.pixels.as_mut() {
+            Some(pixels) => pixels,
+            None => return Err(PyIOError::new_err("Layer has no pixels")),
+        };
+        if pixels[a_index].len() != pixels[b_index].len() {
+            return Err(PyValueError::new_err(format!(
+                "Channels {} and {} have different sample counts",
+                a, b
+            )));
+        }
+
+        let sample_type = match out_index {
+            Some(index) => pixels[index].sample_type(),
+            None => pixels[a_index].sample_type(),
+        };
+        let mut sum = pixels[a_index].converted(sample_type);
+        let addend = &pixels[b_index];
+        sum.map_samples(|index, value| value + addend.sample_f64(index));
+
+        match out_index {
+            Some(index) => pixels[index] = sum,
+            None => {
+                pixels.push(sum);
+                slf.channels.push(out.to_string());
+            }
+        }
+        Ok(slf)
+    }
+
+    /// Returns `(data, dtype, sample_count)` where `data` holds the channel's samples as
+    /// little-endian bytes in their stored type, e.g. for uploading into a GPU buffer. The
+    /// samples are copied once, straight into the returned `bytes`.
+    fn channel_bytes<'py>(
+        &self,
+        py: Python<'py>,
+        name: &str,
+    ) -> PyResult<(Bound<'py, PyBytes>, &'static str, usize)> {
+        let index = self.channel_index(name)?;
+        let pixels = match self.pixels.as_ref().and_then(|pixels| pixels.get(index)) {
+            Some(pixels) => pixels,
+            None => return Err(PyIOError::new_err("Layer has no pixels")),
+        };
+
+        let data = PyBytes::new_with(py, pixels.byte_size(), |buffer| {
+            let mut offset = 0;
+            pixels.write_le_bytes(|bytes| {
+                buffer[offset..offset + bytes.len()].copy_from_slice(bytes);
+                offset += bytes.len();
+            });
+            Ok(())
+        })?;
+
+        Ok((data, pixels.dtype_name(), pixels.len()))
+    }
+
+    /// Stable 64-bit hash, as hex, of every channel's dtype, sample count, and samples in order.
+    /// Only pixel data is hashed; names and attributes are not.
+    fn content_hash(&self) -> String {
+        let mut hasher = ContentHasher::new();
+        for channel in self.pixels.iter().flatten() {
+            channel.hash_into(&mut hasher);
+        }
+
+        format!("{:016x}", hasher.finish())
+    }
+
+    fn assert_valid(&self) -> PyResult<()> {
+        self.check_layout().map_err(PyIOError::new_err)
+    }
+
+    /// Whether every channel is (near-)constant: no two samples of a channel differ by more
+    /// than `tolerance`, with 0 meaning exactly constant. Channels may hold different
+    /// constants, e.g. an AOV cleared to black with an alpha of 1. False without pixels.
+    #[pyo3(signature = (tolerance = 0.0))]
+    fn is_constant(&self, tolerance: f64) -> bool {
+        match &self.pixels {
+            Some(pixels) => pixels.iter().all(|channel| channel.is_constant(tolerance)),
+            None => false,
+        }
+    }
+
+    /// The layer attributes as a dict. Values without a Python representation are left out
+    /// and listed in the loaded image's `warnings()`.
+    fn attributes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        pyattributes::pydict_from_attributes(py, &self.attributes)
+    }
+
+    /// Sorted attribute names, including those `attributes` skips because their values have
+    /// no Python representation.
+    fn attribute_keys(&self) -> Vec<String> {
+        sorted_keys(&self.attributes)
+    }
+
+    fn with_attributes<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        dict: &Bound<'py, PyDict>,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        let attributes = pyattributes::attributes_from_pydict(dict.py(), dict)?;
+        for (key, value) in attributes.iter() {
+            match (key.to_string().as_str(), value) {
+                ("data_window" | "dataWindow", AttributeValue::IntegerBounds(bounds)) => {
+                    slf.set_data_window(
+                        bounds.position.0,
+                        bounds.position.1,
+                        bounds.size.0,
+                        bounds.size.1,
+                    )?;
+                }
+                ("wrap_mode_name" | "wrapmodes", AttributeValue::Text(modes))
+                    if !attributes::is_valid_wrap_modes(&modes.to_string()) =>
+                {
+                    return Err(PyValueError::new_err(format!(
+                        "Invalid wrapmodes {:?}, expected black, clamp, periodic, or mirror, \
+                         optionally as a horizontal,vertical pair",
+                        modes.to_string()
+                    )));
+                }
+                _ => {
+                    slf.attributes.insert(key.clone(), value.clone());
+                }
+            }
+        }
+
+        Ok(slf)
+    }
+}
+
+const KNOWN_PRIMARIES: &[(&str, Chromaticities)] = &[
+    // https://pub.smpte.org/pub/st2065-1/st2065-1-2021.pdf
+    (
+        "ACES",
+        Chromaticities {
+            red: Vec2(0.7347, 0.2653),
+            green: Vec2(0.0, 1.0),
+            blue: Vec2(0.0001, -0.077),
+            white: Vec2(0.32168, 0.33767),
+        },
+    ),
+    // https://docs.acescentral.com/specifications/acescg/
+    (
+        "ACEScg",
+        Chromaticities {
+            red: Vec2(0.713, 0.293),
+            green: Vec2(0.165, 0.83),
+            blue: Vec2(0.128, 0.044),
+            white: Vec2(0.32168, 0.33767),
+        },
+    ),
+    // https://www.itu.int/rec/R-REC-BT.709
+    ("Rec.709", pyattributes::REC_709_CHROMATICITIES),
+];
+
+/// Looks up primaries by their `KNOWN_PRIMARIES` name or by the names the Python wrapper uses
+/// ("AP0", "AP1", "sRGB"), ignoring case.
+fn parse_primaries(name: &str) -> PyResult<Chromaticities> {
+    let known_name = match name.to_lowercase().as_str() {
+        "ap0" => "ACES",
+        "ap1" => "ACEScg",
+        "srgb" => "Rec.709",
+        _ => name,
+    };
+
+    KNOWN_PRIMARIES
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(known_name))
+        .map(|(_, primaries)| *primaries)
+        .ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "Unknown primaries {}, expected ACES (AP0), ACEScg (AP1), or Rec.709 (sRGB)",
+                name
+            ))
+        })
+}
+
+type ChromaticityPoints = ((f32, f32), (f32, f32), (f32, f32), (f32, f32));
+
+fn chromaticity_points(c: &Chromaticities) -> ChromaticityPoints {
+    (
+        (c.red.0, c.red.1),
+        (c.green.0, c.green.1),
+        (c.blue.0, c.blue.1),
+        (c.white.0, c.white.1),
+    )
+}
+
+fn chromaticities_close(a: &Chromaticities, b: &Chromaticities) -> bool {
+    let points = |c: &Chromaticities| [c.red, c.green, c.blue, c.white];
+    points(a)
+        .iter()
+        .zip(points(b).iter())
+        .all(|(a, b)| (a.0 - b.0).abs() <= 1e-3 && (a.1 - b.1).abs() <= 1e-3)
+}
+
+/// The `KNOWN_PRIMARIES` name of primaries within 1e-3 of `chromaticities`, if any.
+fn primaries_name(chromaticities: &Chromaticities) -> Option<&'static str> {
+    KNOWN_PRIMARIES
+        .iter()
+        .find(|(_, primaries)| chromaticities_close(chromaticities, primaries))
+        .map(|(name, _)| *name)
+}
+
+/// Converts a density per inch to a density per `unit`, the print resolution units exrio
+/// accepts on top of the pixels per inch the EXR spec uses for `xDensity`.
+fn inches_per_density_unit(unit: &str) -> PyResult<f32> {
+    match unit {
+        "inch" => Ok(1.0),
+        "cm" => Ok(1.0 / 2.54),
+        _ => Err(PyValueError::new_err(format!(
+            "Unsupported density unit {}, expected inch or cm",
+            unit
+        ))),
+    }
+}
+
+fn rational_from_frame_rate(fps: f64) -> PyResult<(i32, u32)> {
+    // Rates are stored in thousandths, so the numerator must fit an i32 and be nonzero.
+    if !fps.is_finite() || (fps * 1000.0).round() < 1.0 || fps * 1000.0 > i32::MAX as f64 {
+        return Err(PyValueError::new_err(format!(
+            "Frame rate must be between 0.001 and {} fps, got {}",
+            i32::MAX / 1000,
+            fps
+        )));
+    }
+
+    // NTSC-style rates such as 23.976 and 29.97 are exactly n * 1000 / 1001.
+    let ntsc = (fps * 1.001).round();
+    if (fps - fps.round()).abs() > 1e-3 && (fps - ntsc / 1.001).abs() < 1e-2 {
+        return Ok((ntsc as i32 * 1000, 1001));
+    }
+
+    let numerator = (fps * 1000.0).round() as i32;
+    let mut a = numerator.unsigned_abs();
+    let mut b = 1000;
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    let divisor = a.max(1);
+
+    Ok((numerator / divisor as i32, 1000 / divisor))
+}
+
+#[pyclass]
+struct ExrImage {
+    layers: Vec<ExrLayer>,
+    attributes: ImageAttributes,
+    // Non-fatal issues found while loading, see `warnings()`.
+    warnings: Vec<String>,
+    // Whether a tolerant load left out chunks that could not be decoded, see `partial()`.
+    partial: bool,
+    // The version field of the file the image was loaded from, see `version_flags()`.
+    requirements: Option<Requirements>,
+}
+
+impl ExrImage {
+    /// Runs the checks every save makes against `options` before anything is written.
+    fn check_save_options(&self, py: Python<'_>, options: &SaveOptions) -> PyResult<()> {
+        if options.require_sorted_channels {
+            self.check_channels_sorted()?;
+        }
+        options.check_channel_flags(&self.layers)?;
+        options.check_half_overflow(py, &self.layers)
+    }
+
+    /// Raises a ValueError naming the first layer whose channels are not in the alphabetical
+    /// order the EXR format requires, for saves with `require_sorted_channels`.
+    fn check_channels_sorted(&self) -> PyResult<()> {
+        for layer in &self.layers {
+            if layer.channels.windows(2).any(|pair| pair[0] > pair[1]) {
+                return Err(PyValueError::new_err(format!(
+                    "Layer {} has channels [{}] out of alphabetical order, which EXR files \
+                     require; sort them or save without require_sorted_channels",
+                    layer.name.as_deref().unwrap_or("(unnamed)"),
+                    layer.channels.join(", ")
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn decode(
+        bytes: &[u8],
+        channel_order: Option<&Vec<String>>,
+        flip_y: bool,
+        tolerant: bool,
+    ) -> PyResult<ExrImage> {
+        let cursor = Cursor::new(bytes);
+        let image = match get_image_reader().from_buffered(cursor) {
+            Ok(image) => image,
+            Err(exr::error::Error::Invalid(_) | exr::error::Error::Io(_)) if tolerant => {
+                return Self::decode_partial(bytes, channel_order, flip_y)
+            }
+            Err(e @ (exr::error::Error::Invalid(_) | exr::error::Error::Io(_))) => {
+                return Err(exr_error_at(e, locate_read_error(bytes)))
+            }
+            Err(e) => return Err(exr_error(e)),
+        };
+
+        // The decoded layers only keep the full resolution, so take the levels from the header,
+        // which the successful decode has already read once.
+        let meta_data =
+            MetaData::read_from_buffered(Cursor::new(bytes), false).map_err(exr_error)?;
+
+        let mut layers: Vec<ExrLayer> = Vec::new();
+        for (layer, header) in image.layer_data.into_iter().zip(&meta_data.headers) {
+            let mut layer = layer_from_exr(layer)?;
+            layer.level_mode = header_level_mode(header);
+            if let Some(order) = channel_order {
+                layer.reorder_channels(order.clone());
+            }
+            if flip_y {
+                layer.flip_y();
+            }
+            layers.push(layer);
+        }
+        let mut warnings = suffix_duplicate_layer_names(&mut layers);
+        warnings.extend(attribute_warnings(&layers, &image.attributes));
+
+        Ok(ExrImage {
+            layers,
+            attributes: image.attributes,
+            warnings,
+            partial: false,
+            requirements: Some(meta_data.requirements),
+        })
+    }
+
+    /// Salvages what it can of a file that failed to decode, e.g. one cut short by a
+    /// transfer. Every part with a readable header becomes a layer, and the full resolution
+    /// chunks are decoded one at a time so that those which cannot be read or decompressed are
+    /// left as zeros instead of failing the load. Files whose headers are unreadable still
+    /// raise.
+    fn decode_partial(
+        bytes: &[u8],
+        channel_order: Option<&Vec<String>>,
+        flip_y: bool,
+    ) -> PyResult<ExrImage> {
+        let reader = match exr::block::read(Cursor::new(bytes), false) {
+            Ok(reader) => reader,
+            Err(e) => return Err(exr_error_at(e, locate_read_error(bytes))),
+        };
+        let meta_data = reader.meta_data().clone();
+        let mut layers: Vec<ExrLayer> = meta_data
+            .headers
+            .iter()
+            .map(|header| empty_layer(header, header.layer_size))
+            .collect();
+
+        let mut warnings = Vec::new();
+        match reader.filter_chunks(false, |_, _, block| block.level == Vec2(0, 0)) {
+            Ok(chunks) => {
+                let chunk_count = chunks.len();
+                let mut failed = 0;
+                for chunk in chunks {
+                    let block = chunk.and_then(|chunk| {
+                        UncompressedBlock::decompress_chunk(chunk, &meta_data, false)
+                    });
+                    match block {
+                        Ok(block) => {
+                            let header = &meta_data.headers[block.index.layer];
+                            layers[block.index.layer].copy_block(&block, &header.channels);
+                        }
+                        Err(_) => failed += 1,
+                    }
+                }
+                if failed > 0 {
+                    warnings.push(format!(
+                        "{} of {} chunk(s) could not be decoded and were left as zeros",
+                        failed, chunk_count
+                    ));
+                }
+            }
+            Err(e) => warnings.push(format!(
+                "The chunk offset tables could not be read ({}), all pixels were left as zeros",
+                e
+            )),
+        }
+
+        for layer in layers.iter_mut() {
+            if let Some(order) = channel_order {
+                layer.reorder_channels(order.clone());
+            }
+            if flip_y {
+                layer.flip_y();
+            }
+        }
+        warnings.extend(suffix_duplicate_layer_names(&mut layers));
+
+        let attributes = match meta_data.headers.first() {
+            Some(header) => header.shared_attributes.clone(),
+            None => ImageAttributes::new(IntegerBounds::from_dimensions((0, 0))),
+        };
+        warnings.extend(attribute_warnings(&layers, &attributes));
+        Ok(ExrImage {
+            layers,
+            attributes,
+            warnings,
+            partial: true,
+            requirements: Some(meta_data.requirements),
+        })
+    }
+
+    /// Encodes the file in memory. Also returns whether any sample was clamped.
+    fn encode(&self, options: &SaveOptions) -> PyResult<(Vec<u8>, bool)> {
+        for layer in &self.layers {
+            layer.assert_valid()?;
+        }
+
+        let (image, clamped) = self.exr_image(options);
+        let mut writer = get_inmemory_writer();
+        match image.write().to_buffered(&mut writer) {
+            Ok(_) => (),
+            Err(e) => return Err(exr_error(e)),
+        }
+
+        let mut buffer = match writer.into_inner() {
+            Ok(buffer) => buffer.into_inner(),
+            Err(e) => return Err(PyIOError::new_err(e.to_string())),
+        };
+
+        // exr only sets the long names flag when a name exceeds 31 bytes, so force it here.
+        // It lives in bit 10 of the version field that follows the 4 byte magic number.
+        if options.long_names {
+            buffer[5] |= 0x04;
+        }
+
+        Ok((buffer, clamped))
+    }
+
+    fn exr_image(&self, options: &SaveOptions) -> (Image<Layers<AnyChannels<FlatSamples>>>, bool) {
+        let first_layer = self.layers.first().unwrap();
+        let (rust_layers, clamped): (Vec<Layer<AnyChannels<FlatSamples>>>, Vec<bool>) = self
+            .layers
+            .iter()
+            .flat_map(|layer| to_rust_layer(layer, options))
+            .unzip();
+
+        let mut attributes = self.attributes.clone();
+        match options.display_window {
+            // An explicit display window is authoritative, e.g. for overscan deliveries.
+            Some((x, y, width, height)) => {
+                attributes.display_window = IntegerBounds::new(Vec2(x, y), Vec2(width, height));
+            }
+            // Loaded images keep their display window, which may differ from the data window.
+            None if attributes.display_window.size != Vec2(0, 0) => {}
+            None => {
+                attributes.display_window.size.0 = first_layer.width.unwrap();
+                attributes.display_window.size.1 = first_layer.height.unwrap();
+            }
+        }
+        if let Some(pixel_aspect_ratio) = options.pixel_aspect_ratio {
+            attributes.pixel_aspect = pixel_aspect_ratio;
+        }
+
+        let clamped = clamped.contains(&true);
+        (Image::from_layers(attributes, rust_layers), clamped)
+    }
+
+    /// Writes the file at `path` one layer at a time. The headers are inferred from the
+    /// layers' layouts, and `layer_at(index, header)` supplies each layer's samples just before
+    /// its blocks are written, so at most one converted layer is held in memory.
+    fn write_layer_by_layer(
+        &self,
+        path: PathBuf,
+        options: &SaveOptions,
+        layer_at: impl FnMut(usize, &Header) -> PyResult<Layer<AnyChannels<FlatSamples>>>,
+    ) -> PyResult<()> {
+        write_file_atomically(&path, |writer| {
+            self.write_layers_to(writer, options, layer_at)
+        })
+    }
+
+    /// Validates the layers for `write_layers_with_options`, checking `memory_limit` against
+    /// the largest layer, which is all that writing one layer at a time copies.
+    fn check_layer_by_layer(&self, options: &SaveOptions) -> PyResult<()> {
+        for layer in &self.layers {
+            layer.assert_valid()?;
+        }
+
+        let largest_layer = self.layers.iter().map(|layer| {
+            let pixels = layer.pixels.iter().flatten();
+            pixels.map(|channel| channel.byte_size()).sum::<usize>()
+        });
+        check_save_memory(
+            largest_layer.max().unwrap_or(0),
+            options.memory_limit,
+            "use save_streaming_to_path to provide one layer at a time",
+        )
+    }
+
+    /// Writes the layers, checked by `check_layer_by_layer`, to `writer` one at a time, as
+    /// `save_to_path` does. Returns whether any sample was clamped.
+    fn write_layers_with_options(
+        &self,
+        writer: impl Write + Seek,
+        options: &SaveOptions,
+    ) -> PyResult<bool> {
+        let mut clamped = false;
+        self.write_layers_to(writer, options, |index, _| {
+            match to_rust_layer(&self.layers[index], options) {
+                Some((layer, layer_clamped)) => {
+                    clamped |= layer_clamped;
+                    Ok(layer)
+                }
+                None => Err(PyValueError::new_err("Layer is missing its size or pixels")),
+            }
+        })?;
+        Ok(clamped)
+    }
+
+    /// Same as `write_layer_by_layer`, writing to any seekable `writer`.
+    fn write_layers_to(
+        &self,
+        writer: impl Write + Seek,
+        options: &SaveOptions,
+        mut layer_at: impl FnMut(usize, &Header) -> PyResult<Layer<AnyChannels<FlatSamples>>>,
+    ) -> PyResult<()> {
+        let has_sizes = |layer: &ExrLayer| layer.width.is_some() && layer.height.is_some();
+        if self.layers.is_empty() || !self.layers.iter().all(has_sizes) {
+            return Err(PyValueError::new_err(
+                "Every layer needs a width, height, and channels to write",
+            ));
+        }
+
+        let layouts = ExrImage {
+            layers: self.layers.iter().map(ExrLayer::layout).collect(),
+            attributes: self.attributes.clone(),
+            warnings: Vec::new(),
+            partial: false,
+            requirements: None,
+        };
+        let (image, _) = layouts.exr_image(options);
+        if image.layer_data.len() != self.layers.len() {
+            return Err(PyValueError::new_err(
+                "Every layer needs a width, height, and channels to write",
+            ));
+        }
+
+        let headers = image.layer_data.infer_headers(&image.attributes);
+        let mut writer = FlagPatchingWriter::new(writer);
+
+        let mut callback_error: Option<PyErr> = None;
+        let result = exr::block::write(&mut writer, headers, true, |meta, chunks| {
+            let mut current: Option<(usize, Layer<AnyChannels<FlatSamples>>)> = None;
+            let mut for_each_block =
+                |emit: &mut dyn FnMut(usize, UncompressedBlock) -> exr::error::UnitResult| {
+                    for (index_in_header, block_index) in
+                        meta.enumerate_ordered_header_block_indices()
+                    {
+                        let header = &meta.headers[block_index.layer];
+                        if current.as_ref().map(|(index, _)| *index) != Some(block_index.layer) {
+                            // Release the previous layer before asking for the next one.
+                            drop(current.take());
+                            match layer_at(block_index.layer, header) {
+                                Ok(layer) => current = Some((block_index.layer, layer)),
+                                Err(e) => {
+                                    callback_error = Some(e);
+                                    return Err(exr::error::Error::Aborted);
+                                }
+                            }
+                        }
+
+                        let (_, layer) = current.as_ref().unwrap();
+                        let data = layer
+                            .channel_data
+                            .create_writer(header)
+                            .extract_uncompressed_block(header, block_index);
+                        emit(
+                            index_in_header,
+                            UncompressedBlock {
+                                index: block_index,
+                                data,
+                            },
+                        )?;
+                    }
+                    Ok(())
+                };
+
+            // The layers are still converted one at a time on this thread; only compressing
+            // the extracted blocks is handed to the pool. If the pool can't be started, the
+            // blocks are compressed here as without `threads`.
+            let pool = match options.threads {
+                Some(threads) if threads > 1 => ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .thread_name(|index| format!("exrio compressor {}", index))
+                    .build()
+                    .ok(),
+                _ => None,
+            };
+            if let Some(pool) = pool {
+                let compressor =
+                    ParallelBlocksCompressor::new_with_thread_pool(&meta, chunks, || Ok(pool));
+                // `None` when nothing is compressed, in which case blocks are written below.
+                if let Some(mut compressor) = compressor {
+                    return for_each_block(&mut |index, block| {
+                        compressor.add_block_to_compression_queue(index, block)
+                    });
+                }
+            }
+
+            for_each_block(&mut |index, block| {
+                chunks.write_chunk(index, block.compress_to_chunk(&meta.headers)?)
+            })
+        });
+
+        if let Some(e) = callback_error {
+            return Err(e);
+        }
+        result.map_err(exr_error)?;
+
+        // Same long names flag as in `encode`, patched into the written header.
+        if options.long_names {
+            writer
+                .set_long_names_flag()
+                .map_err(|e| PyIOError::new_err(e.to_string()))?;
+        }
+        writer
+            .flush()
+            .map_err(|e| PyIOError::new_err(e.to_string()))
+    }
+}
+
+/// Calls `fill_layer(index)` and converts the returned layer, checking it matches the header.
+fn streamed_layer(
+    fill_layer: &Bound<'_, PyAny>,
+    index: usize,
+    header: &Header,
+    options: &SaveOptions,
+) -> PyResult<Layer<AnyChannels<FlatSamples>>> {
+    let layer = fill_layer.call1((index,))?.downcast_into::<ExrLayer>()?;
+    let layer = layer.borrow();
+    layer.assert_valid()?;
+
+    let rust_layer = match to_rust_layer(&layer, options) {
+        Some((rust_layer, _)) => rust_layer,
+        None => return Err(PyValueError::new_err("Layer is missing its size or pixels")),
+    };
+    if rust_layer.size != header.layer_size
+        || rust_layer.channel_data.infer_channel_list() != header.channels
+    {
+        return Err(PyValueError::new_err(format!(
+            "Layer {} does not match the size and channels it was declared with",
+            index
+        )));
+    }
+
+    Ok(rust_layer)
+}
+
+#[pymethods]
+impl ExrImage {
+    #[new]
+    fn new() -> Self {
+        Self {
+            layers: Vec::new(),
+            attributes: ImageAttributes::new(IntegerBounds::from_dimensions((0, 0))),
+            warnings: Vec::new(),
+            partial: false,
+            requirements: None,
+        }
+    }
+
+    /// The image attributes as a dict. Values without a Python representation are left out
+    /// and listed in `warnings()` when the image is loaded.
+    fn attributes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        pyattributes::pydict_from_attributes(py, &attributes_from_image(&self.attributes))
+    }
+
+    /// Non-fatal issues found while loading the image, e.g. attributes with no Python
+    /// representation that `attributes()` skips, or duplicate layer names that were suffixed.
+    /// Empty for images that were not loaded from a file.
+    fn warnings(&self) -> Vec<String> {
+        self.warnings.clone()
+    }
+
+    /// Whether the image came from a tolerant load of a damaged file, in which case its
+    /// layers may have zeroed regions and `warnings()` says how many chunks were lost.
+    fn partial(&self) -> bool {
+        self.partial
+    }
+
+    /// The feature flags from the version field of the file the image was loaded from, as a
+    /// dict with the format `version` and booleans `tiled` (a single tiled part),
+    /// `long_names` (names longer than 31 bytes), `deep`, and `multipart`, e.g. to check
+    /// that legacy readers can open it. `None` for images that were not loaded from a file.
+    fn version_flags<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyDict>>> {
+        let requirements = match self.requirements {
+            Some(requirements) => requirements,
+            None => return Ok(None),
+        };
+
+        let flags = PyDict::new(py);
+        flags.set_item("version", requirements.file_format_version)?;
+        flags.set_item("tiled", requirements.is_single_layer_and_tiled)?;
+        flags.set_item("long_names", requirements.has_long_names)?;
+        flags.set_item("deep", requirements.has_deep_data)?;
+        flags.set_item("multipart", requirements.has_multiple_layers)?;
+        Ok(Some(flags))
+    }
+
+    /// Sorted attribute names, including those `attributes` skips because their values have
+    /// no Python representation.
+    fn attribute_keys(&self) -> Vec<String> {
+        sorted_keys(&attributes_from_image(&self.attributes))
+    }
+
+    fn with_attributes<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        dict: &Bound<'py, PyDict>,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        let attributes = pyattributes::attributes_from_pydict(dict.py(), dict)?;
+        image_attributes_from_attributes(&mut slf.attributes, &attributes, false)
+            .map_err(exr_error)?;
+        Ok(slf)
+    }
+
+    /// Sets the attributes in `dict`, routing standard names to their fields and keeping the
+    /// image's other attributes, e.g. chromaticities or an `imageState` set earlier. Unlike
+    /// `with_attributes`, which keeps standard fields but replaces all custom attributes,
+    /// `merge=False` replaces everything: attributes missing from `dict` are cleared.
+    #[pyo3(signature = (dict, merge = true))]
+    fn set_attributes(&mut self, dict: &Bound<'_, PyDict>, merge: bool) -> PyResult<()> {
+        let attributes = pyattributes::attributes_from_pydict(dict.py(), dict)?;
+        let mut image_attributes = if merge {
+            self.attributes.clone()
+        } else {
+            ImageAttributes::new(IntegerBounds::from_dimensions((0, 0)))
+        };
+        image_attributes_from_attributes(&mut image_attributes, &attributes, true)
+            .map_err(exr_error)?;
+        self.attributes = image_attributes;
+        Ok(())
+    }
+
+    fn layers(&self) -> Vec<ExrLayer> {
+        self.layers.clone()
+    }
+
+    /// The first layer named `name`. Files may repeat a part name, so this also matches the
+    /// `file_name` of duplicates suffixed on load; `layers_named` returns every match.
+    fn layer(&self, name: &str) -> PyResult<ExrLayer> {
+        match self.layers.iter().find(|layer| layer.is_named(name)) {
+            Some(layer) => Ok(layer.clone()),
+            None => Err(PyKeyError::new_err(format!("Layer {} not found", name))),
+        }
+    }
+
+    /// Every layer named `name`, in file order, including duplicates suffixed on load.
+    fn layers_named(&self, name: &str) -> Vec<ExrLayer> {
+        self.layers
+            .iter()
+            .filter(|layer| layer.is_named(name))
+            .cloned()
+            .collect()
+    }
+
+    /// Every channel of every layer as `(layer_name, channel_name, dtype)`, without copying
+    /// any pixels. The dtype is `None` for a channel that has no pixel buffer.
+    fn channels(&self) -> Vec<(Option<String>, String, Option<&'static str>)> {
+        let mut channels = Vec::new();
+        for layer in &self.layers {
+            let pixels = layer.pixels.as_deref().unwrap_or_default();
+            for (index, channel) in layer.channels.iter().enumerate() {
+                let dtype = pixels.get(index).map(|pixels| pixels.dtype_name());
+                channels.push((layer.name.clone(), channel.clone(), dtype));
+            }
+        }
+        channels
+    }
+
+    /// The bounding box of all layers' data windows, or `None` for an image without layers.
+    fn data_window(&self) -> Option<Box2i> {
+        let windows = self.layers.iter().map(|layer| layer.data_window());
+        windows.reduce(|window, other| {
+            let min_x = window.x.min(other.x);
+            let min_y = window.y.min(other.y);
+            let max_x = (window.x + window.width as i32).max(other.x + other.width as i32);
+            let max_y = (window.y + window.height as i32).max(other.y + other.height as i32);
+            Box2i {
+                x: min_x,
+                y: min_y,
+                width: (max_x - min_x) as usize,
+                height: (max_y - min_y) as usize,
+            }
+        })
+    }
+
+    /// Composites the layers, e.g. render buckets that each have their own data window, into
+    /// one layer covering `display_window` `(x, y, width, height)`. Each layer's pixels are
+    /// placed at its data-window position and cropped to the window. The result has every
+    /// channel of any layer, in the sample type of the first layer that has it, and takes its
+    /// name and attributes from the first layer. With `policy="last_wins"` later layers
+    /// overwrite earlier ones where they overlap and uncovered pixels are left at the
+    /// channel's `default_value`, or zero if unset;
+    /// `policy="error"` raises a ValueError on any overlap or gap instead. Subsampled
+    /// channels are not supported.
+    #[pyo3(signature = (display_window, policy = "last_wins"))]
+    fn assemble_tiles(&self, display_window: Bounds, policy: &str) -> PyResult<ExrLayer> {
+        let strict = match policy {
+            "last_wins" => false,
+            "error" => true,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "Unsupported tile policy {}, expected last_wins or error",
+                    policy
+                )))
+            }
+        };
+        let first = match self.layers.first() {
+            Some(first) => first,
+            None => return Err(PyValueError::new_err("Image has no layers to assemble")),
+        };
+
+        let Bounds((x, y, width, height)) = display_window;
+        let mut mosaic = ExrLayer::new(first.name.clone());
+        mosaic.attributes = first.attributes.clone();
+        mosaic.line_order = first.line_order;
+        mosaic.compression = first.compression;
+        mosaic.blocks = first.blocks;
+        let mut pixels: Vec<PixelData> = Vec::new();
+        for layer in &self.layers {
+            layer.assert_valid()?;
+            for (channel, data) in layer.channels.iter().zip(layer.pixels.iter().flatten()) {
+                if layer
+                    .sampling
+                    .get(channel)
+                    .is_some_and(|sampling| *sampling != (1, 1))
+                {
+                    return Err(PyValueError::new_err(format!(
+                        "Channel {} is subsampled, which assemble_tiles does not support",
+                        channel
+                    )));
+                }
+                if !mosaic.channels.contains(channel) {
+                    let default_value = layer.default_values.get(channel).copied();
+                    let len = width * height;
+                    mosaic.channels.push(channel.clone());
+                    pixels.push(PixelData::filled(
+                        data.sample_type(),
+                        len,
+                        default_value.unwrap_or(0.0),
+                    ));
+                    if let Some(value) = default_value {
+                        mosaic.default_values.insert(channel.clone(), value);
+                    }
+                    if layer.perceptually_linear.contains(channel) {
+                        mosaic.perceptually_linear.insert(channel.clone());
+                    }
+                }
+            }
+        }
+
+        let mut owners: Vec<Option<usize>> = vec![None; width * height];
+        for (index, layer) in self.layers.iter().enumerate() {
+            // The part of the layer's data window inside the display window.
+            let window = layer.data_window();
+            let left = window.x.max(x);
+            let top = window.y.max(y);
+            let right = (window.x + window.width as i32).min(x + width as i32);
+            let bottom = (window.y + window.height as i32).min(y + height as i32);
+            if left >= right || top >= bottom {
+                continue;
+            }
+
+            let span = (right - left) as usize;
+            let target_start = |row: i32| (row - y) as usize * width + (left - x) as usize;
+            let source_start =
+                |row: i32| (row - window.y) as usize * window.width + (left - window.x) as usize;
+            for row in top..bottom {
+                let start = target_start(row);
+                for (offset, owner) in owners[start..start + span].iter_mut().enumerate() {
+                    if let (true, Some(previous)) = (strict, *owner) {
+                        return Err(PyValueError::new_err(format!(
+                            "Layers {} and {} overlap at pixel ({}, {})",
+                            previous,
+                            index,
+                            left + offset as i32,
+                            row
+                        )));
+                    }
+                    *owner = Some(index);
+                }
+            }
+
+            for (channel, data) in layer.channels.iter().zip(layer.pixels.iter().flatten()) {
+                let target = match mosaic.channels.iter().position(|name| name == channel) {
+                    Some(position) => &mut pixels[position],
+                    None => continue,
+                };
+                let converted;
+                let source = if data.sample_type() == target.sample_type() {
+                    data
+                } else {
+                    converted = data.converted(target.sample_type());
+                    &converted
+                };
+                for row in top..bottom {
+                    target.copy_samples(target_start(row), source, source_start(row), span);
+                }
+            }
+        }
+
+        if strict {
+            if let Some(gap) = owners.iter().position(Option::is_none) {
+                return Err(PyValueError::new_err(format!(
+                    "No layer covers pixel ({}, {}) of the display window",
+                    x + (gap % width) as i32,
+                    y + (gap / width) as i32
+                )));
+            }
+        }
+
+        mosaic.width = Some(width);
+        mosaic.height = Some(height);
+        mosaic.pixels = Some(pixels);
+        mosaic.set_position(x, y);
+        Ok(mosaic)
+    }
+
+    /// Completeness of the render as recorded in the `imageState` text attribute,
+    /// e.g. "complete" or "in_progress".
+    fn image_state(&self) -> Option<String> {
+        match self.attributes.other.get(&Text::from("imageState")) {
+            Some(AttributeValue::Text(state)) => Some(state.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Stores the `imageState` text attribute; text with characters outside Latin-1 raises a
+    /// ValueError.
+    fn with_image_state<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        state: &str,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        let state = match Text::new_or_none(state) {
+            Some(state) => state,
+            None => return Err(PyValueError::new_err("Image state must be Latin-1 text")),
+        };
+
+        slf.attributes
+            .other
+            .insert(Text::from("imageState"), AttributeValue::Text(state));
+        Ok(slf)
+    }
+
+    /// Frames per second of the first layer that has a framesPerSecond attribute.
+    fn frame_rate(&self) -> Option<f64> {
+        self.layers.iter().find_map(|layer| {
+            match layer.attributes.get(&Text::from("frames_per_second")) {
+                Some(AttributeValue::Rational((numerator, denominator))) if *denominator > 0 => {
+                    Some(*numerator as f64 / *denominator as f64)
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Stores the frame rate on every layer as a rational, e.g. 23.976 becomes 24000/1001.
+    /// Only the layers already added get it, so an image without layers raises a ValueError,
+    /// as does a rate that is not a positive number of fps that fits the rational.
+    fn with_frame_rate(mut slf: PyRefMut<'_, Self>, fps: f64) -> PyResult<PyRefMut<'_, Self>> {
+        let rational = rational_from_frame_rate(fps)?;
+        if slf.layers.is_empty() {
+            return Err(PyValueError::new_err(
+                "Frame rate is stored on layers, add them before setting it",
+            ));
+        }
+
+        for layer in slf.layers.iter_mut() {
+            layer.attributes.insert(
+                Text::from("frames_per_second"),
+                AttributeValue::Rational(rational),
+            );
+        }
+        Ok(slf)
+    }
+
+    /// The `comments` text of the first layer that has one, with its line breaks intact,
+    /// e.g. a multi-line slate.
+    fn comments(&self) -> Option<String> {
+        self.layers.iter().find_map(
+            |layer| match layer.attributes.get(&Text::from("comments")) {
+                Some(AttributeValue::Text(comments)) => Some(comments.to_string()),
+                _ => None,
+            },
+        )
+    }
+
+    /// Stores `comments` on every layer. Newlines are kept as is; text with characters
+    /// outside Latin-1 raises a ValueError.
+    fn with_comments<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        comments: &str,
+    ) -> PyResult<PyRefMut<'py, Self>> {
+        let comments = match Text::new_or_none(comments) {
+            Some(comments) => comments,
+            None => return Err(PyValueError::new_err("Comments must be Latin-1 text")),
+        };
+
+        for layer in slf.layers.iter_mut() {
+            layer.attributes.insert(
+                Text::from("comments"),
+                AttributeValue::Text(comments.clone()),
+            );
+        }
+        Ok(slf)
+    }
+
+    /// Returns the red, green, blue, and white points as `(x, y)` pairs, if the file has them.
+    fn chromaticities(&self) -> Option<ChromaticityPoints> {
+        self.attributes
+            .chromaticities
+            .as_ref()
+            .map(chromaticity_points)
+    }
+
+    /// Best-effort name of the color space, based only on the chromaticities attribute:
+    /// "ACES" (AP0), "ACEScg" (AP1), "Rec.709", or "unknown".
+    fn color_space(&self) -> &'static str {
+        self.attributes
+            .chromaticities
+            .as_ref()
+            .and_then(primaries_name)
+            .unwrap_or("unknown")
+    }
+
+    /// Spins lat-long environment maps horizontally by `degrees`, moving columns to the right
+    /// and wrapping them around the seam. The shift is rounded to the nearest whole column.
+    /// Only layers whose `environment_map` attribute is "envmap:latlong" are rotated; others
+    /// are left as they are. Cube maps and subsampled channels raise a `ValueError`, checked
+    /// before any layer is changed.
+    fn reorient_envmap(&mut self, degrees: f64) -> PyResult<()> {
+        let mut latlong = Vec::new();
+        for (index, layer) in self.layers.iter().enumerate() {
+            match layer.attributes.get(&Text::from("environment_map")) {
+                Some(AttributeValue::EnvironmentMap(EnvironmentMap::LatitudeLongitude)) => {}
+                Some(AttributeValue::EnvironmentMap(EnvironmentMap::Cube)) => {
+                    return Err(PyValueError::new_err(
+                        "Only lat-long environment maps can be reoriented",
+                    ))
+                }
+                _ => continue,
+            }
+            if let Some(channel) = layer
+                .channels
+                .iter()
+                .find(|channel| layer.sampling_factors(channel) != (1, 1))
+            {
+                return Err(PyValueError::new_err(format!(
+                    "Channel {} is subsampled and can't be reoriented",
+                    channel
+                )));
+            }
+            latlong.push(index);
+        }
+
+        for index in latlong {
+            let layer = &mut self.layers[index];
+            let width = match layer.width {
+                Some(width) if width > 0 => width,
+                _ => continue,
+            };
+
+            let columns = (degrees / 360.0 * width as f64).round() as i64;
+            let shift = columns.rem_euclid(width as i64) as usize;
+            if let Some(pixels) = layer.pixels.as_mut() {
+                for channel in pixels.iter_mut() {
+                    channel.rotate_rows_right(width, shift);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn with_layer(mut slf: PyRefMut<'_, Self>, layer: ExrLayer) -> PyRefMut<'_, Self> {
+        slf.layers.push(layer);
+        slf
+    }
+
+    /// Appends several layers at once, in order.
+    fn with_layers(mut slf: PyRefMut<'_, Self>, layers: Vec<ExrLayer>) -> PyRefMut<'_, Self> {
+        slf.layers.extend(layers);
+        slf
+    }
+
+    /// Approximate size in bytes of the file `save_to_buffer` would produce with the given
+    /// compression, computed from the stored buffer sizes without encoding anything.
+    /// Lossless ratios are typical averages, so real files can differ noticeably.
+    #[pyo3(signature = (compression = "piz"))]
+    fn estimate_size(&self, compression: &str) -> PyResult<usize> {
+        let compression = parse_compression(compression)?;
+        let ratio = estimated_compression_ratio(compression);
+
+        // Magic number, version, and the shared header attributes.
+        let mut size = 1024;
+        for layer in &self.layers {
+            let pixel_bytes: usize = layer
+                .pixels
+                .iter()
+                .flatten()
+                .map(|channel| channel.byte_size())
+                .sum();
+            let chunks = layer
+                .height
+                .unwrap_or(0)
+                .div_ceil(compression.scan_lines_per_block());
+
+            // Per-layer header plus one offset table entry and chunk header per block.
+            size += 512 + chunks * 16 + (pixel_bytes as f64 * ratio) as usize;
+        }
+
+        Ok(size)
+    }
+
+    /// Bytes held by the decoded pixel buffers of all layers, accounting for each channel's dtype.
+    fn memory_bytes(&self) -> usize {
+        self.layers
+            .iter()
+            .flat_map(|layer| layer.pixels.iter().flatten())
+            .map(|channel| channel.byte_size())
+            .sum()
+    }
+
+    /// Number of pixels across all layers, i.e. the sum of each layer's width times height.
+    fn pixel_count(&self) -> usize {
+        self.layers
+            .iter()
+            .map(|layer| layer.width.unwrap_or(0) * layer.height.unwrap_or(0))
+            .sum()
+    }
+
+    /// Removes layers whose channels are all (near-)constant (see `ExrLayer.is_constant`), e.g.
+    /// empty AOVs, and returns their names in order (`None` for unnamed layers).
+    #[pyo3(signature = (tolerance = 0.0))]
+    fn prune_constant_layers(&mut self, tolerance: f64) -> Vec<Option<String>> {
+        let (pruned, kept) = std::mem::take(&mut self.layers)
+            .into_iter()
+            .partition::<Vec<_>, _>(|layer| layer.is_constant(tolerance));
+        self.layers = kept;
+        pruned.into_iter().map(|layer| layer.name).collect()
+    }
+
+    /// Removes every layer but keeps the image attributes, e.g. to reuse them as a template.
+    fn clear_layers(&mut self) {
+        self.layers.clear();
+    }
+
+    /// Saves the image as an EXR file in memory. The save options are keyword arguments, all
+    /// optional, and the same for `save_to_path` and `save_to_file`; unknown ones raise a
+    /// `TypeError`. Each layer keeps the line order it was loaded with (increasing for new
+    /// layers) unless `line_order` overrides it.
+    /// `channel_dtypes` maps channel names to the dtype they are stored as, taking precedence
+    /// over `force_dtype`; unlisted channels keep their current dtype.
+    /// `pixel_aspect_ratio` overrides the image's pixel aspect for this write only.
+    /// With `flip_y`, the pixels are taken to be bottom-up and are written top-down.
+    /// `compression` names the codec (PIZ by default). With `smart_compression`, layers whose
+    /// channels are all data (u32, or named in `data_channels`, by default depth, alpha, mask,
+    /// and ID names) are written with lossless ZIP instead, so e.g. DWA never touches IDs.
+    /// `encoding` picks an exr preset (see `parse_encoding`) instead of PIZ scanlines; an
+    /// explicit `compression` or `line_order` still takes precedence over the preset's.
+    /// Encoding copies every layer and then the whole file, so a `MemoryError` is raised up
+    /// front when that would exceed `memory_limit` bytes, which defaults to the available
+    /// memory (see `available_memory`).
+    /// `software` is recorded as the `software` attribute of every layer, replacing the one a
+    /// layer was loaded with. By default layers keep their own `software` attribute and those
+    /// without one get "exrio" and its version.
+    /// `clamp=(low, high)` clamps the float channels into that range as they are written,
+    /// e.g. to remove negative values and fireflies before lossy compression, without
+    /// changing the image.
+    /// `threads` compresses blocks on that many worker threads when layers are written one at
+    /// a time, which speeds up costly codecs such as PIZ on large images; by default they are
+    /// compressed on the calling thread. Batch servers saving several files at once can use
+    /// it to share their cores between saves. Encoding in memory always compresses on exr's
+    /// own worker threads.
+    /// Each layer's channels are written alphabetically, as the EXR format requires, whatever
+    /// order they were added in. Pass `require_sorted_channels=True` to have a layer that is
+    /// not already sorted raise a ValueError instead, e.g. to catch code that depends on
+    /// channel positions that will not survive a round trip.
+    #[pyo3(signature = (**options))]
+    fn save_to_buffer<'py>(
+        &self,
+        py: Python<'py>,
+        options: Option<&Bound<'py, PyDict>>,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let options = SaveOptions::from_kwargs(options)?;
+        self.check_save_options(py, &options)?;
+        check_save_memory(
+            2 * self.memory_bytes(),
+            options.memory_limit,
+            "use save_to_path to write one layer at a time",
+        )?;
+
+        let (buffer, _) = self.encode(&options)?;
+        Ok(PyBytes::new(py, buffer.as_slice()))
+    }
+
+    /// Saves the image to `path` with the same options as `save_to_buffer`, converting and
+    /// writing one layer at a time instead of building the whole file in memory. Only the
+    /// largest layer is copied at once, which `memory_limit` is checked against.
+    /// Returns whether any sample was clamped to `clamp`.
+    #[pyo3(signature = (path, **options))]
+    fn save_to_path(
+        &self,
+        py: Python<'_>,
+        path: PathBuf,
+        options: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<bool> {
+        let options = SaveOptions::from_kwargs(options)?;
+        self.check_save_options(py, &options)?;
+        self.check_layer_by_layer(&options)?;
+        write_file_atomically(&path, |writer| {
+            self.write_layers_with_options(writer, &options)
+        })
+    }
+
+    /// Writes the image into `file`, a Python object with a `write` method such as
+    /// `io.BytesIO`, a tarfile member, or a socket file, with the same options as
+    /// `save_to_path`. Seekable objects are written one layer at a time starting at their
+    /// current position; others receive the whole file, encoded in memory, in a single
+    /// `write`. Exceptions raised by `write` or `seek` become `IOError`s.
+    #[pyo3(signature = (file, **options))]
+    fn save_to_file<'py>(
+        &self,
+        file: Bound<'py, PyAny>,
+        options: Option<&Bound<'py, PyDict>>,
+    ) -> PyResult<bool> {
+        let options = SaveOptions::from_kwargs(options)?;
+        self.check_save_options(file.py(), &options)?;
+
+        let seekable = file.hasattr("seek")?
+            && file
+                .call_method0("seekable")
+                .and_then(|seekable| seekable.extract::<bool>())
+                .unwrap_or(true);
+        if seekable {
+            self.check_layer_by_layer(&options)?;
+            let writer = PyFileWriter::new(file)?;
+            return self.write_layers_with_options(BufWriter::new(writer), &options);
+        }
+
+        check_save_memory(
+            2 * self.memory_bytes(),
+            options.memory_limit,
+            "pass a seekable file to write one layer at a time",
+        )?;
+        let (buffer, clamped) = self.encode(&options)?;
+        let mut writer = PyFileWriter { file, start: 0 };
+        writer
+            .write_all(&buffer)
+            .and_then(|_| writer.flush())
+            .map_err(|e| PyIOError::new_err(e.to_string()))?;
+        Ok(clamped)
+    }
+
+    /// Writes the image to `path` one layer at a time, so only one layer's pixels are held in
+    /// memory. The image's layers only declare each layer's name, size, attributes, and channel
+    /// dtypes; their pixels may be empty, as declared by `from_schema` or left by `clear_pixels`.
+    /// `fill_layer(index)` is called once per layer, in order, and must return a layer with the
+    /// same size and channels.
+    fn save_streaming_to_path(&self, path: PathBuf, fill_layer: &Bound<'_, PyAny>) -> PyResult<()> {
+        let options = SaveOptions::default();
+        self.write_layer_by_layer(path, &options, |index, header| {
+            streamed_layer(fill_layer, index, header, &options)
+        })
+    }
+
+    /// Reads only the first layer of the file at `path`, skipping the pixels of all others.
+    #[staticmethod]
+    fn load_first_layer(path: PathBuf) -> PyResult<ExrLayer> {
+        let image = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .from_file(&path);
+
+        let mut layer = match image {
+            Ok(image) => layer_from_exr(image.layer_data)?,
+            Err(e) => return Err(exr_error(e)),
+        };
+        if let Some(header) = read_meta_data(path)?
+            .headers
+            .iter()
+            .find(|header| !header.deep)
+        {
+            layer.level_mode = header_level_mode(header);
         }
+        Ok(layer)
     }
 
+    /// Returns a `(height, width, 3)` uint8 RGB thumbnail of the first layer of `path` whose
+    /// longest side is at most `max_size`. Mipmapped files are read from the smallest level
+    /// that is still at least `max_size`, so the full resolution is never decoded; other files
+    /// are decoded in full and box filtered. Layers without R, G, and B use their first three
+    /// channels (repeating the last one if there are fewer). Values are clamped to [0, 1] and
+    /// scaled to [0, 255] without any tone mapping.
     #[staticmethod]
-    fn load_from_buffer<'py>(py: Python<'py>, buffer: &Bound<'py, PyBytes>) -> PyResult<ExrImage> {
-        let bytes: &[u8] = buffer.extract::<&[u8]>()?;
-        let cursor = Cursor::new(bytes);
-        let image = match get_image_reader().from_buffered(cursor) {
-            Ok(image) => image,
-            Err(e) => return Err(PyIOError::new_err(e.to_string())),
+    fn thumbnail<'py>(
+        py: Python<'py>,
+        path: PathBuf,
+        max_size: usize,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        if max_size == 0 {
+            return Err(PyValueError::new_err("max_size must be positive"));
+        }
+
+        let meta_data = read_meta_data(path.clone())?;
+        let header = match meta_data.headers.first() {
+            Some(header) => header,
+            None => return Err(PyIOError::new_err("File has no layers")),
         };
 
-        let mut layers: Vec<ExrLayer> = Vec::new();
-        for layer in image.layer_data {
-            layers.push(layer_from_exr(layer));
+        let channel_list = &header.channels.list;
+        let rgb_indices = ["R", "G", "B"].map(|name| {
+            channel_list
+                .iter()
+                .position(|channel| channel.name.eq(name))
+        });
+        let rgb_indices = match rgb_indices {
+            [Some(r), Some(g), Some(b)] => [r, g, b],
+            _ if channel_list.is_empty() => {
+                return Err(PyIOError::new_err("Layer has no channels"))
+            }
+            _ => [0, 1, 2].map(|index| index.min(channel_list.len() - 1)),
+        };
+
+        let (level, size) = match header.blocks {
+            BlockDescription::Tiles(tiles) if tiles.level_mode != LevelMode::Singular => {
+                mip_map_levels(tiles.rounding_mode, header.layer_size)
+                    .take_while(|(index, size)| *index == 0 || size.0.max(size.1) >= max_size)
+                    .last()
+                    .unwrap_or((0, header.layer_size))
+            }
+            _ => (0, header.layer_size),
+        };
+
+        let file = File::open(&path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        let reader = match exr::block::read(io::BufReader::new(file), false) {
+            Ok(reader) => reader,
+            Err(e) => return Err(exr_error(e)),
+        };
+        let chunks = match reader.filter_chunks(false, |_, _, block| {
+            block.layer == 0 && block.level == Vec2(level, level)
+        }) {
+            Ok(chunks) => chunks,
+            Err(e) => return Err(exr_error(e)),
+        };
+
+        let mut planes = vec![vec![0.0f32; size.area()]; 3];
+        let decoded = chunks.decompress_sequential(false, |_, block| {
+            for line in block.lines(&header.channels) {
+                let channel = line.location.channel;
+                let start = line.location.position.1 * size.0 + line.location.position.0;
+                let samples = line_samples_f32(channel_list[channel].sample_type, line.value);
+                for (plane, index) in planes.iter_mut().zip(rgb_indices) {
+                    if index == channel {
+                        plane[start..start + samples.len()].copy_from_slice(&samples);
+                    }
+                }
+            }
+            Ok(())
+        });
+        if let Err(e) = decoded {
+            return Err(exr_error(e));
+        }
+
+        let (width, height) = (size.0, size.1);
+        let scale = (width.max(height) as f64 / max_size as f64).max(1.0);
+        let out_width = ((width as f64 / scale).round() as usize).max(1);
+        let out_height = ((height as f64 / scale).round() as usize).max(1);
+
+        let mut thumbnail = Vec::with_capacity(out_width * out_height * 3);
+        for out_y in 0..out_height {
+            let rows = box_filter_range(out_y, out_height, height);
+            for out_x in 0..out_width {
+                let columns = box_filter_range(out_x, out_width, width);
+                for plane in &planes {
+                    let mut sum = 0.0;
+                    for y in rows.clone() {
+                        for x in columns.clone() {
+                            let value = plane[y * width + x];
+                            // NaN (and infinities after clamping) should not poison the average.
+                            sum += if value.is_nan() {
+                                0.0
+                            } else {
+                                value.clamp(0.0, 1.0)
+                            };
+                        }
+                    }
+                    let mean = sum / (rows.len() * columns.len()) as f32;
+                    thumbnail.push((mean * 255.0).round() as u8);
+                }
+            }
+        }
+
+        PyArray1::from_vec(py, thumbnail)
+            .into_any()
+            .call_method1("reshape", ((out_height, out_width, 3),))
+    }
+
+    /// Streams the full-resolution pixels of the file at `path` one block (a group of scanlines,
+    /// or a tile) at a time, so that only a single block is held in memory. For every block,
+    /// `callback(layer_index, (x, y, width, height), channels)` is called with the block's
+    /// bounds within the layer's data window and a dict of `(height, width)` arrays in each
+    /// channel's stored dtype. Blocks arrive in file order; the y range of a block is
+    /// `y..y + height`. Exceptions raised by the callback stop the read and are re-raised.
+    #[staticmethod]
+    fn read_blocks<'py>(
+        py: Python<'py>,
+        path: PathBuf,
+        callback: &Bound<'py, PyAny>,
+    ) -> PyResult<()> {
+        let file = File::open(&path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        let reader = match exr::block::read(io::BufReader::new(file), false) {
+            Ok(reader) => reader,
+            Err(e) => return Err(exr_error(e)),
+        };
+        let chunks = match reader.filter_chunks(false, |_, _, block| block.level == Vec2(0, 0)) {
+            Ok(chunks) => chunks,
+            Err(e) => return Err(exr_error(e)),
+        };
+
+        let mut callback_error: Option<PyErr> = None;
+        let decoded = chunks.decompress_sequential(false, |meta_data, block| {
+            let channels = &meta_data.headers[block.index.layer].channels;
+            match call_block_callback(py, callback, &block, channels) {
+                Ok(()) => Ok(()),
+                Err(e) => {
+                    callback_error = Some(e);
+                    Err(exr::error::Error::Aborted)
+                }
+            }
+        });
+
+        match (callback_error, decoded) {
+            (Some(e), _) => Err(e),
+            (None, Err(e)) => Err(exr_error(e)),
+            (None, Ok(())) => Ok(()),
+        }
+    }
+
+    /// Iterates over the full-resolution tiles of `channel` in part `layer` of the tiled file at
+    /// `path`, reading and decoding one tile per step so the whole frame is never in memory.
+    /// Each step yields `(tile_x, tile_y, pixels)`, where the tile covers pixels from
+    /// `(tile_x * tile_width, tile_y * tile_height)` of the data window and `pixels` is a
+    /// `(height, width)` array in the channel's stored dtype. Tiles on the right and bottom
+    /// edges are cropped to the image, so they can be smaller than the tile size. Tiles arrive
+    /// in file order. Scanline parts raise a `ValueError`; use `read_blocks` for those.
+    #[staticmethod]
+    fn iter_tiles(path: PathBuf, layer: usize, channel: &str) -> PyResult<TileIterator> {
+        let file = File::open(&path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        let reader = match exr::block::read(io::BufReader::new(file), false) {
+            Ok(reader) => reader,
+            Err(e) => return Err(exr_error(e)),
+        };
+
+        let header = match reader.headers().get(layer) {
+            Some(header) => header,
+            None => {
+                return Err(PyIndexError::new_err(format!(
+                    "Part {} is out of range, the file has {} part(s)",
+                    layer,
+                    reader.headers().len()
+                )))
+            }
+        };
+        let tile_size = match header.blocks {
+            BlockDescription::Tiles(tiles) => tiles.tile_size,
+            BlockDescription::ScanLines => {
+                return Err(PyValueError::new_err(format!(
+                    "Layer {} is stored as scanlines, not tiles; use read_blocks instead",
+                    layer
+                )))
+            }
+        };
+        let channel = match header
+            .channels
+            .list
+            .iter()
+            .position(|description| description.name.eq(channel))
+        {
+            Some(index) => index,
+            None => {
+                return Err(PyKeyError::new_err(format!(
+                    "Channel {} not found",
+                    channel
+                )))
+            }
+        };
+
+        let chunks = match reader.filter_chunks(false, |_, _, block| {
+            block.layer == layer && block.level == Vec2(0, 0)
+        }) {
+            Ok(chunks) => chunks,
+            Err(e) => return Err(exr_error(e)),
+        };
+        Ok(TileIterator {
+            chunks,
+            channel,
+            tile_size,
+        })
+    }
+
+    /// Number of chunks (scanline blocks or tiles, across all parts) that reading the file at
+    /// `path` will decode. Loading only decodes the full resolution, so the smaller levels of
+    /// mipmapped parts are not counted. Only the header is read.
+    #[staticmethod]
+    fn chunk_count(path: PathBuf) -> PyResult<usize> {
+        Ok(read_meta_data(path)?
+            .headers
+            .iter()
+            .map(full_resolution_chunk_count)
+            .sum())
+    }
+
+    /// Number of parts (layers) in the file at `path`. Only the header is read.
+    #[staticmethod]
+    fn part_count(path: PathBuf) -> PyResult<usize> {
+        Ok(read_meta_data(path)?.headers.len())
+    }
+
+    /// Reads an EXR file from memory. Rows are returned top-down, in the file's increasing-y
+    /// order, unless `flip_y` returns them bottom-up (e.g. for OpenGL textures).
+    /// With `tolerant`, a truncated or corrupt file returns whatever chunks could be decoded
+    /// instead of raising, with the rest left as zeros, `partial()` set, and the loss listed in
+    /// `warnings()`. Files whose headers cannot be read still raise.
+    #[staticmethod]
+    #[pyo3(signature = (buffer, channel_order = None, flip_y = false, tolerant = false))]
+    fn load_from_buffer<'py>(
+        py: Python<'py>,
+        buffer: &Bound<'py, PyBytes>,
+        channel_order: Option<Vec<String>>,
+        flip_y: bool,
+        tolerant: bool,
+    ) -> PyResult<ExrImage> {
+        let bytes: &[u8] = buffer.extract::<&[u8]>()?;
+        Self::decode(bytes, channel_order.as_ref(), flip_y, tolerant)
+    }
+
+    /// Reads resolution level `level` of every part of the file at `path`, decoding only that
+    /// level's tiles, e.g. to load a small mip of a large texture first. Level 0 is the full
+    /// resolution and each level halves it, so layers report the reduced width and height.
+    /// Parts without mip maps only have level 0; a `ValueError` names the part that lacks
+    /// `level`.
+    #[staticmethod]
+    fn load_mip(path: PathBuf, level: usize) -> PyResult<ExrImage> {
+        let meta_data = read_meta_data(path.clone())?;
+        let mut layers = Vec::with_capacity(meta_data.headers.len());
+        for (index, header) in meta_data.headers.iter().enumerate() {
+            let sizes = level_sizes(header);
+            let size = match sizes.get(level) {
+                Some(size) => *size,
+                None => {
+                    return Err(PyValueError::new_err(format!(
+                        "Level {} is out of range, part {} has {} resolution level(s)",
+                        level,
+                        index,
+                        sizes.len()
+                    )))
+                }
+            };
+
+            layers.push(empty_layer(header, size));
         }
 
+        decode_level(&path, level, 0..layers.len(), &mut layers)?;
+        let mut warnings = suffix_duplicate_layer_names(&mut layers);
+
+        let attributes = match meta_data.headers.first() {
+            Some(header) => header.shared_attributes.clone(),
+            None => ImageAttributes::new(IntegerBounds::from_dimensions((0, 0))),
+        };
+        warnings.extend(attribute_warnings(&layers, &attributes));
         Ok(ExrImage {
             layers,
-            attributes: image.attributes,
+            attributes,
+            warnings,
+            partial: false,
+            requirements: Some(meta_data.requirements),
         })
     }
+
+    /// Reads part `layer` (the first by default) of the file at `path` straight into one
+    /// contiguous float32 `(height, width, channels)` array, e.g. for an ML dataloader, and
+    /// returns it with the channel names along the last axis. `channels` picks and orders the
+    /// channels, e.g. `["R", "G", "B"]`; by default all are loaded in file (alphabetical)
+    /// order. Chunks are decompressed in parallel without holding the GIL and each sample is
+    /// converted into its slot of the array as it is decoded, without per-channel buffers.
+    /// f16 and u32 samples are converted to float32. Subsampled channels raise a `ValueError`.
+    #[staticmethod]
+    #[pyo3(signature = (path, layer = None, channels = None))]
+    fn load_tensor(
+        py: Python<'_>,
+        path: PathBuf,
+        layer: Option<usize>,
+        channels: Option<Vec<String>>,
+    ) -> PyResult<(Bound<'_, PyAny>, Vec<String>)> {
+        let index = layer.unwrap_or(0);
+        let file = File::open(&path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        let reader = match exr::block::read(io::BufReader::new(file), false) {
+            Ok(reader) => reader,
+            Err(e) => return Err(exr_error(e)),
+        };
+
+        let header = match reader.headers().get(index) {
+            Some(header) => header,
+            None => {
+                return Err(PyIndexError::new_err(format!(
+                    "Part {} is out of range, the file has {} part(s)",
+                    index,
+                    reader.headers().len()
+                )))
+            }
+        };
+        let channel_list = &header.channels.list;
+        let names = channels.unwrap_or_else(|| {
+            channel_list
+                .iter()
+                .map(|channel| channel.name.to_string())
+                .collect()
+        });
+        if names.is_empty() {
+            return Err(PyValueError::new_err("No channels to load"));
+        }
+
+        // The positions along the last axis that each channel of the file is written to.
+        let mut slots: Vec<Vec<usize>> = vec![Vec::new(); channel_list.len()];
+        for (slot, name) in names.iter().enumerate() {
+            let position = match channel_list
+                .iter()
+                .position(|channel| channel.name.eq(name.as_str()))
+            {
+                Some(position) => position,
+                None => return Err(PyKeyError::new_err(format!("Channel {} not found", name))),
+            };
+            if channel_list[position].sampling != Vec2(1, 1) {
+                return Err(PyValueError::new_err(format!(
+                    "Channel {} is subsampled and cannot be packed into a tensor",
+                    name
+                )));
+            }
+            slots[position].push(slot);
+        }
+        let sample_types: Vec<SampleType> = channel_list
+            .iter()
+            .map(|channel| channel.sample_type)
+            .collect();
+        let (size, depth) = (header.layer_size, names.len());
+
+        let chunks = match reader.filter_chunks(false, |_, _, block| {
+            block.layer == index && block.level == Vec2(0, 0)
+        }) {
+            Ok(chunks) => chunks,
+            Err(e) => return Err(exr_error(e)),
+        };
+        let tensor = py.allow_threads(|| {
+            let mut tensor = vec![0.0f32; size.area() * depth];
+            let decoded = chunks.decompress_parallel(false, |meta_data, block| {
+                let channels = &meta_data.headers[block.index.layer].channels;
+                for line in block.lines(channels) {
+                    let location = line.location;
+                    let pixel = location.position.1 * size.0 + location.position.0;
+                    for slot in &slots[location.channel] {
+                        let out = &mut tensor[pixel * depth + slot..];
+                        write_strided_f32(sample_types[location.channel], line.value, out, depth);
+                    }
+                }
+                Ok(())
+            });
+            decoded.map(|()| tensor)
+        });
+
+        let tensor = tensor.map_err(exr_error)?;
+        let array = PyArray1::from_vec(py, tensor)
+            .into_any()
+            .call_method1("reshape", ((size.1, size.0, depth),))?;
+        Ok((array, names))
+    }
+
+    /// Reads part `index` of the file at `path` as a layer, decoding only that part's chunks,
+    /// e.g. to pull one AOV out of a large multipart file. Tiled parts are read at full
+    /// resolution. Raises an `IndexError` if the file has no part `index`.
+    #[staticmethod]
+    fn load_part(path: PathBuf, index: usize) -> PyResult<ExrLayer> {
+        let meta_data = read_meta_data(path.clone())?;
+        let header = match meta_data.headers.get(index) {
+            Some(header) => header,
+            None => {
+                return Err(PyIndexError::new_err(format!(
+                    "Part {} is out of range, the file has {} part(s)",
+                    index,
+                    meta_data.headers.len()
+                )))
+            }
+        };
+
+        let mut layers = [empty_layer(header, header.layer_size)];
+        decode_level(&path, 0, index..index + 1, &mut layers)?;
+        let [layer] = layers;
+        Ok(layer)
+    }
+
+    /// Reads the file at `path` through a memory map instead of copying it into a buffer.
+    /// Fails if the file's size or modification time changes during the read; truncating
+    /// the file while it is mapped can still crash the process, so only map files that are
+    /// not being written to.
+    #[staticmethod]
+    #[pyo3(signature = (path, channel_order = None, flip_y = false))]
+    fn load_mmap(
+        path: PathBuf,
+        channel_order: Option<Vec<String>>,
+        flip_y: bool,
+    ) -> PyResult<ExrImage> {
+        let file = File::open(&path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        let stamp = |file: &File| -> PyResult<(u64, Option<SystemTime>)> {
+            let metadata = file
+                .metadata()
+                .map_err(|e| PyIOError::new_err(e.to_string()))?;
+            Ok((metadata.len(), metadata.modified().ok()))
+        };
+
+        let before = stamp(&file)?;
+        // SAFETY: the map is only read, and changes to the file during the read are
+        // detected below by comparing its size and modification time.
+        let mmap = unsafe { Mmap::map(&file) }.map_err(|e| PyIOError::new_err(e.to_string()))?;
+        let image = Self::decode(&mmap, channel_order.as_ref(), flip_y, false);
+
+        if stamp(&file)? != before {
+            return Err(PyIOError::new_err(format!(
+                "{} was modified while being read",
+                path.display()
+            )));
+        }
+
+        image
+    }
+}
+
+/// Registers text attribute `name` as an enum: on load, stored text found in `mapping` reads
+/// as the mapped Python value, and on save, those values are written back as their text.
+/// Text outside the mapping stays a `str`. Registering `name` again replaces its mapping, and
+/// an empty mapping unregisters it.
+/// The numpy dtype name `array` is stored as, or the one `dtype` ("f16", "f32", or "u32")
+/// names, so Python casts arrays by the same rules as `ExrLayer.with_channel`.
+#[pyfunction]
+#[pyo3(signature = (array, dtype = None))]
+fn exr_dtype(array: &Bound<'_, PyAny>, dtype: Option<&str>) -> PyResult<&'static str> {
+    let sample_type = match dtype {
+        Some(dtype) => parse_sample_type(dtype)?,
+        None => numpy_sample_type(array)?,
+    };
+    Ok(numpy_dtype_name(sample_type))
+}
+
+#[pyfunction]
+fn register_text_enum(name: String, mapping: &Bound<'_, PyDict>) -> PyResult<()> {
+    let mapping = mapping
+        .iter()
+        .map(|(text, value)| Ok((text.extract::<String>()?, value.unbind())))
+        .collect::<PyResult<Vec<_>>>()?;
+    pyattributes::register_text_enum(name, mapping)
+}
+
+/// Sets the chromaticities that `default_chromaticities` returns for the rest of the session,
+/// by name: "ACES" (AP0), "ACEScg" (AP1), or "Rec.709" (sRGB, the built-in default). Lets
+/// wide-gamut pipelines stop defaulting to Rec.709 primaries when building Python
+/// `Chromaticities`. Loading and saving never fill in chromaticities a file doesn't have.
+#[pyfunction]
+fn set_default_chromaticities(primaries: &str) -> PyResult<()> {
+    pyattributes::set_default_chromaticities(parse_primaries(primaries)?);
+    Ok(())
+}
+
+/// The red, green, blue, and white points that Python `Chromaticities` default to, as `(x, y)`
+/// pairs. Rec.709 unless changed with `set_default_chromaticities`.
+#[pyfunction]
+fn default_chromaticities() -> ChromaticityPoints {
+    chromaticity_points(&pyattributes::default_chromaticities())
+}
+
+/// The red, green, blue, and white points of known primaries, named as for
+/// `set_default_chromaticities`, e.g. "AP1". The Python `PRIMARY_CHROMATICITIES` table is
+/// built from these.
+#[pyfunction]
+fn known_primaries(name: &str) -> PyResult<ChromaticityPoints> {
+    Ok(chromaticity_points(&parse_primaries(name)?))
+}
+
+/// Names the known primaries within 1e-3 of `points`: "ACES" (AP0), "ACEScg" (AP1), or
+/// "Rec.709" (sRGB), or `None` if they match none of them.
+#[pyfunction]
+fn match_primaries(points: ChromaticityPoints) -> Option<&'static str> {
+    let (red, green, blue, white) = points;
+    primaries_name(&Chromaticities {
+        red: Vec2(red.0, red.1),
+        green: Vec2(green.0, green.1),
+        blue: Vec2(blue.0, blue.1),
+        white: Vec2(white.0, white.1),
+    })
+}
+
+/// The largest and mean absolute difference between two channels with the same number of
+/// samples. Samples that are NaN in both count as equal; NaN against a number is an infinite
+/// difference.
+fn channel_difference(a: &PixelData, b: &PixelData) -> (f64, f64) {
+    let mut max: f64 = 0.0;
+    let mut sum = 0.0;
+    for index in 0..a.len() {
+        let (a, b) = (a.sample_f32(index) as f64, b.sample_f32(index) as f64);
+        let difference = if a.is_nan() && b.is_nan() {
+            0.0
+        } else if a.is_nan() || b.is_nan() {
+            f64::INFINITY
+        } else if a == b {
+            // Also covers matching infinities, whose difference would be NaN.
+            0.0
+        } else {
+            (a - b).abs()
+        };
+        max = max.max(difference);
+        sum += difference;
+    }
+    (max, sum / a.len().max(1) as f64)
+}
+
+/// Decodes the files at `path_a` and `path_b` and compares their pixels, pairing layers by
+/// position and channels by name. Returns a dict with `differences`, describing mismatched
+/// layer counts, names, sizes, and channel sets; `channels`, mapping "layer.channel" (or just
+/// the channel for unnamed layers) to the `(max, mean)` absolute difference of each channel
+/// found in both; and `matches`, true when there are no differences and no channel differs
+/// by more than `tolerance`.
+#[pyfunction]
+#[pyo3(signature = (path_a, path_b, tolerance = 0.0))]
+fn compare<'py>(
+    py: Python<'py>,
+    path_a: PathBuf,
+    path_b: PathBuf,
+    tolerance: f64,
+) -> PyResult<Bound<'py, PyDict>> {
+    let load = |path: &PathBuf| {
+        let bytes = std::fs::read(path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        ExrImage::decode(&bytes, None, false, false)
+    };
+    let (image_a, image_b) = (load(&path_a)?, load(&path_b)?);
+
+    let mut differences = Vec::new();
+    let channels = PyDict::new(py);
+    let mut matches = true;
+    if image_a.layers.len() != image_b.layers.len() {
+        differences.push(format!(
+            "{} layer(s) vs {} layer(s)",
+            image_a.layers.len(),
+            image_b.layers.len()
+        ));
+    }
+
+    for (index, (a, b)) in image_a.layers.iter().zip(&image_b.layers).enumerate() {
+        let label = a.name.clone().unwrap_or_else(|| format!("layer {}", index));
+        if a.name != b.name {
+            differences.push(format!(
+                "Layer {} is named {:?} vs {:?}",
+                index, a.name, b.name
+            ));
+        }
+        if (a.width, a.height) != (b.width, b.height) {
+            differences.push(format!(
+                "{} is {}x{} vs {}x{}",
+                label,
+                a.width.unwrap_or(0),
+                a.height.unwrap_or(0),
+                b.width.unwrap_or(0),
+                b.height.unwrap_or(0)
+            ));
+            continue;
+        }
+
+        for name in b.channels.iter().filter(|name| !a.channels.contains(name)) {
+            differences.push(format!(
+                "{} channel {} is only in the second file",
+                label, name
+            ));
+        }
+        let (pixels_a, pixels_b) = match (a.pixels.as_ref(), b.pixels.as_ref()) {
+            (Some(pixels_a), Some(pixels_b)) => (pixels_a, pixels_b),
+            _ => continue,
+        };
+        for (channel_index, name) in a.channels.iter().enumerate() {
+            let Some(other_index) = b.channels.iter().position(|other| other == name) else {
+                differences.push(format!(
+                    "{} channel {} is only in the first file",
+                    label, name
+                ));
+                continue;
+            };
+            let (channel_a, channel_b) = (&pixels_a[channel_index], &pixels_b[other_index]);
+            if channel_a.len() != channel_b.len() {
+                differences.push(format!(
+                    "{} channel {} has {} vs {} samples",
+                    label,
+                    name,
+                    channel_a.len(),
+                    channel_b.len()
+                ));
+                continue;
+            }
+
+            let (max, mean) = channel_difference(channel_a, channel_b);
+            matches &= max <= tolerance;
+            let key = match &a.name {
+                Some(layer) => format!("{}.{}", layer, name),
+                None => name.clone(),
+            };
+            channels.set_item(key, (max, mean))?;
+        }
+    }
+
+    let result = PyDict::new(py);
+    result.set_item("matches", matches && differences.is_empty())?;
+    result.set_item("differences", differences)?;
+    result.set_item("channels", channels)?;
+    Ok(result)
 }
 
 #[pymodule]
@@ -388,5 +4622,15 @@ impl ExrImage {
 fn exrio<'py>(m: &Bound<'py, PyModule>) -> PyResult<()> {
     m.add_class::<ExrImage>()?;
     m.add_class::<ExrLayer>()?;
+    m.add_class::<Box2i>()?;
+    m.add_class::<TileIterator>()?;
+    m.add("DEFAULT_SOFTWARE", DEFAULT_SOFTWARE)?;
+    m.add_function(wrap_pyfunction!(exr_dtype, m)?)?;
+    m.add_function(wrap_pyfunction!(register_text_enum, m)?)?;
+    m.add_function(wrap_pyfunction!(set_default_chromaticities, m)?)?;
+    m.add_function(wrap_pyfunction!(default_chromaticities, m)?)?;
+    m.add_function(wrap_pyfunction!(known_primaries, m)?)?;
+    m.add_function(wrap_pyfunction!(match_primaries, m)?)?;
+    m.add_function(wrap_pyfunction!(compare, m)?)?;
     Ok(())
 }
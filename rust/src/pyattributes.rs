@@ -1,11 +1,11 @@
-use std::{collections::HashMap, fmt::format};
+use std::{collections::HashMap, fmt::format, sync::Mutex};
 
 use attribute::Chromaticities;
-use exr::meta::attribute::TimeCode;
+use exr::meta::attribute::{EnvironmentMap, TimeCode};
 use exr::prelude::*;
 use pyo3::{
     conversion::{IntoPyObject, IntoPyObjectExt},
-    exceptions::PyIOError,
+    exceptions::{PyIOError, PyIndexError, PyValueError},
     pyclass, pymethods, pymodule,
     types::{PyAnyMethods, PyBytes, PyDict, PyDictMethods, PyModule, PyModuleMethods},
     Bound, FromPyObject, Py, PyAny, PyErr, PyObject, PyResult, Python,
@@ -42,21 +42,53 @@ fn extract_int(dict: &Bound<PyDict>, key: &str) -> PyResult<i32> {
     }
 }
 
-fn get_chromaticities_or_default(attrs: &mut ImageAttributes) -> Chromaticities {
-    let chromaticities = match attrs.chromaticities {
-        Some(chromaticities) => chromaticities,
-        None => {
-            attrs.chromaticities = Some(Chromaticities {
-                red: Vec2(0.64, 0.33),
-                green: Vec2(0.3, 0.6),
-                blue: Vec2(0.15, 0.06),
-                white: Vec2(0.3127, 0.329),
-            });
-            return attrs.chromaticities.unwrap();
-        }
+/// Reads `{"position": [x, y], "size": [width, height]}` integer bounds. Both keys are
+/// required, no others are allowed, and the size must not be negative.
+fn extract_integer_bounds(dict: &Bound<PyDict>) -> PyResult<IntegerBounds> {
+    let pair = |key: &str| match dict.get_item(key)? {
+        Some(value) => value
+            .extract::<[i32; 2]>()
+            .map_err(|_| PyIOError::new_err(format!("{} must be a pair of integers", key))),
+        None => Err(PyIOError::new_err(format!("{} not found", key))),
     };
-    attrs.chromaticities = Some(chromaticities);
-    chromaticities
+
+    if dict.len() != 2 {
+        return Err(PyIOError::new_err(
+            "Integer bounds only take \"position\" and \"size\"",
+        ));
+    }
+    let [x, y] = pair("position")?;
+    let [width, height] = pair("size")?;
+    if width < 0 || height < 0 {
+        return Err(PyIOError::new_err(
+            "Integer bounds size must not be negative",
+        ));
+    }
+
+    Ok(IntegerBounds::new(
+        Vec2(x, y),
+        Vec2(width as usize, height as usize),
+    ))
+}
+
+/// Rec.709 primaries with a D65 white point, which sRGB shares. The built-in default
+/// chromaticities, until `set_default_chromaticities` picks others for the session. Only new
+/// Python `Chromaticities` take the default; files without chromaticities are left without.
+pub const REC_709_CHROMATICITIES: Chromaticities = Chromaticities {
+    red: Vec2(0.64, 0.33),
+    green: Vec2(0.3, 0.6),
+    blue: Vec2(0.15, 0.06),
+    white: Vec2(0.3127, 0.329),
+};
+
+static DEFAULT_CHROMATICITIES: Mutex<Chromaticities> = Mutex::new(REC_709_CHROMATICITIES);
+
+pub fn default_chromaticities() -> Chromaticities {
+    *DEFAULT_CHROMATICITIES.lock().unwrap()
+}
+
+pub fn set_default_chromaticities(chromaticities: Chromaticities) {
+    *DEFAULT_CHROMATICITIES.lock().unwrap() = chromaticities;
 }
 
 fn get_timecode_or_default(attrs: &mut ImageAttributes) -> TimeCode {
@@ -79,6 +111,106 @@ fn get_timecode_or_default(attrs: &mut ImageAttributes) -> TimeCode {
     timecode
 }
 
+/// An integer rectangle such as a display or data window, with its position and size. It
+/// also behaves as the `(x, y, width, height)` tuple bounds used to be returned as: it can be
+/// unpacked, indexed, and compared equal to such a tuple.
+#[pyclass(frozen)]
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Box2i {
+    #[pyo3(get)]
+    pub x: i32,
+    #[pyo3(get)]
+    pub y: i32,
+    #[pyo3(get)]
+    pub width: usize,
+    #[pyo3(get)]
+    pub height: usize,
+}
+
+impl Box2i {
+    pub fn from_bounds(bounds: &IntegerBounds) -> Self {
+        Self {
+            x: bounds.position.0,
+            y: bounds.position.1,
+            width: bounds.size.0,
+            height: bounds.size.1,
+        }
+    }
+
+    pub fn to_bounds(&self) -> IntegerBounds {
+        IntegerBounds::new(Vec2(self.x, self.y), Vec2(self.width, self.height))
+    }
+}
+
+#[pymethods]
+impl Box2i {
+    #[new]
+    fn new(x: i32, y: i32, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The bounds as an `(x, y, width, height)` tuple.
+    pub fn to_tuple(&self) -> (i32, i32, usize, usize) {
+        (self.x, self.y, self.width, self.height)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Box2i(x={}, y={}, width={}, height={})",
+            self.x, self.y, self.width, self.height
+        )
+    }
+
+    fn __len__(&self) -> usize {
+        4
+    }
+
+    fn __getitem__<'py>(&self, py: Python<'py>, index: isize) -> PyResult<Bound<'py, PyAny>> {
+        match if index < 0 { index + 4 } else { index } {
+            0 => self.x.into_bound_py_any(py),
+            1 => self.y.into_bound_py_any(py),
+            2 => self.width.into_bound_py_any(py),
+            3 => self.height.into_bound_py_any(py),
+            _ => Err(PyIndexError::new_err("Box2i index out of range")),
+        }
+    }
+
+    fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        Ok(self.to_tuple().into_pyobject(py)?.try_iter()?.into_any())
+    }
+
+    /// Equal to another `Box2i` or an `(x, y, width, height)` tuple with the same values.
+    fn __eq__(&self, other: &Bound<'_, PyAny>) -> bool {
+        match Bounds::extract_bound(other) {
+            Ok(Bounds(bounds)) => bounds == self.to_tuple(),
+            Err(_) => false,
+        }
+    }
+
+    /// Hashes like the equal tuple, so a `Box2i` and its tuple find the same dict entry.
+    fn __hash__(&self, py: Python<'_>) -> PyResult<isize> {
+        self.to_tuple().into_pyobject(py)?.hash()
+    }
+}
+
+/// Bounds given by Python as a `Box2i` or an `(x, y, width, height)` tuple, e.g. a display
+/// window read from one file and passed on to a save.
+pub struct Bounds(pub (i32, i32, usize, usize));
+
+impl<'py> FromPyObject<'py> for Bounds {
+    fn extract_bound(value: &Bound<'py, PyAny>) -> PyResult<Self> {
+        if let Ok(bounds) = value.downcast::<Box2i>() {
+            return Ok(Bounds(bounds.get().to_tuple()));
+        }
+        Ok(Bounds(value.extract()?))
+    }
+}
+
 pub const ATTRIBUTE_HANDLERS: &[AttributeValueHandler] = &[
     AttributeValueHandler {
         name: "timecode",
@@ -195,45 +327,140 @@ pub const ATTRIBUTE_HANDLERS: &[AttributeValueHandler] = &[
         },
     },
     AttributeValueHandler {
-        name: "integer_bounds",
+        name: "environment_map",
+        to_python: |value, py| match value {
+            AttributeValue::EnvironmentMap(EnvironmentMap::LatitudeLongitude) => {
+                Some("envmap:latlong".into_py_any(py))
+            }
+            AttributeValue::EnvironmentMap(EnvironmentMap::Cube) => {
+                Some("envmap:cube".into_py_any(py))
+            }
+            _ => None,
+        },
+        from_python: |value| match value.extract::<String>() {
+            Ok(value) => match value.as_str() {
+                "envmap:latlong" => Ok(AttributeValue::EnvironmentMap(
+                    EnvironmentMap::LatitudeLongitude,
+                )),
+                "envmap:cube" => Ok(AttributeValue::EnvironmentMap(EnvironmentMap::Cube)),
+                _ => Err(PyIOError::new_err("Invalid environment map")),
+            },
+            Err(e) => Err(PyIOError::new_err(format!("{} invalid", e))),
+        },
+    },
+    AttributeValueHandler {
+        name: "rational",
         to_python: |value, py| match value {
-            AttributeValue::IntegerBounds(bounds) => Some(
-                format!(
-                    "{:?}-{:?}-{:?}-{:?}",
-                    bounds.position.0, bounds.position.1, bounds.size.0, bounds.size.1
-                )
-                .into_py_any(py),
-            ),
+            AttributeValue::Rational((numerator, denominator)) => {
+                Some(format!("rational:{}/{}", numerator, denominator).into_py_any(py))
+            }
             _ => None,
         },
         from_python: |value| match value.extract::<String>() {
             Ok(value) => {
-                let values = value
-                    .split('-')
-                    .flat_map(|s| s.parse::<i32>())
-                    .collect::<Vec<i32>>();
+                let parsed = value
+                    .strip_prefix("rational:")
+                    .and_then(|value| value.split_once('/'))
+                    .and_then(|(numerator, denominator)| {
+                        Some((
+                            numerator.parse::<i32>().ok()?,
+                            denominator.parse::<u32>().ok()?,
+                        ))
+                    });
 
-                if values.len() != 4 {
-                    return Err(PyIOError::new_err("Invalid integer bounds"));
+                match parsed {
+                    Some(rational) => Ok(AttributeValue::Rational(rational)),
+                    None => Err(PyIOError::new_err("Invalid rational")),
                 }
-
-                Ok(AttributeValue::IntegerBounds(IntegerBounds {
-                    position: Vec2(values[0], values[1]),
-                    size: Vec2(values[2] as usize, values[3] as usize),
-                }))
             }
             Err(e) => Err(PyIOError::new_err(format!("{} invalid", e))),
         },
     },
+    AttributeValueHandler {
+        name: "integer_bounds",
+        to_python: |value, py| match value {
+            AttributeValue::IntegerBounds(bounds) => {
+                Some(Box2i::from_bounds(bounds).into_py_any(py))
+            }
+            _ => None,
+        },
+        from_python: |value| {
+            if let Ok(bounds) = value.downcast::<Box2i>() {
+                return Ok(AttributeValue::IntegerBounds(bounds.get().to_bounds()));
+            }
+
+            // Bounds are a structured `(x, y, width, height)` tuple so that negative positions survive.
+            if let Ok((x, y, width, height)) = value.extract::<(i32, i32, usize, usize)>() {
+                return Ok(AttributeValue::IntegerBounds(IntegerBounds::new(
+                    Vec2(x, y),
+                    Vec2(width, height),
+                )));
+            }
+
+            // Lists such as `[x, y, width, height]`, e.g. from JSON sidecar metadata.
+            if let Ok([x, y, width, height]) = value.extract::<[i32; 4]>() {
+                if width >= 0 && height >= 0 {
+                    return Ok(AttributeValue::IntegerBounds(IntegerBounds::new(
+                        Vec2(x, y),
+                        Vec2(width as usize, height as usize),
+                    )));
+                }
+            }
+
+            // Dicts such as `{"position": [x, y], "size": [width, height]}`.
+            if let Ok(dict) = value.downcast::<PyDict>() {
+                return extract_integer_bounds(dict).map(AttributeValue::IntegerBounds);
+            }
+
+            // Legacy `x-y-width-height` strings, where an empty segment marks a negative number.
+            match value.extract::<String>() {
+                Ok(value) => {
+                    let mut values = Vec::<i32>::new();
+                    let mut negative = false;
+                    for segment in value.split('-') {
+                        if segment.is_empty() {
+                            negative = true;
+                            continue;
+                        }
+
+                        match segment.parse::<i32>() {
+                            Ok(number) if negative => values.push(-number),
+                            Ok(number) => values.push(number),
+                            Err(_) => return Err(PyIOError::new_err("Invalid integer bounds")),
+                        }
+                        negative = false;
+                    }
+
+                    if values.len() != 4 || values[2] < 0 || values[3] < 0 {
+                        return Err(PyIOError::new_err("Invalid integer bounds"));
+                    }
+
+                    Ok(AttributeValue::IntegerBounds(IntegerBounds {
+                        position: Vec2(values[0], values[1]),
+                        size: Vec2(values[2] as usize, values[3] as usize),
+                    }))
+                }
+                Err(e) => Err(PyIOError::new_err(format!("{} invalid", e))),
+            }
+        },
+    },
     AttributeValueHandler {
         name: "f32",
         to_python: |value, py| match value {
             AttributeValue::F32(f32) => Some(f32.into_py_any(py)),
             _ => None,
         },
-        from_python: |value| match value.extract::<f32>() {
-            Ok(value) => Ok(AttributeValue::F32(value)),
-            Err(e) => Err(PyIOError::new_err(format!("{} invalid", e))),
+        from_python: |value| {
+            // Integers (including bools and numpy integers) stay integers so values round-trip
+            // with the Python type they were written with.
+            if value.hasattr("__index__")? {
+                return Err(PyIOError::new_err("Integer is not an f32"));
+            }
+
+            match value.extract::<f32>() {
+                Ok(value) => Ok(AttributeValue::F32(value)),
+                Err(e) => Err(PyIOError::new_err(format!("{} invalid", e))),
+            }
         },
     },
     AttributeValueHandler {
@@ -243,7 +470,14 @@ pub const ATTRIBUTE_HANDLERS: &[AttributeValueHandler] = &[
             _ => None,
         },
         from_python: |value| match value.extract::<String>() {
-            Ok(value) => Ok(AttributeValue::Text(Text::from(value.as_str()))),
+            // Stored byte for byte, so newlines survive; characters beyond Latin-1 cannot.
+            Ok(value) => match Text::new_or_none(&value) {
+                Some(text) => Ok(AttributeValue::Text(text)),
+                None => Err(PyIOError::new_err(format!(
+                    "Text {:?} has characters outside Latin-1, which EXR text cannot store",
+                    value
+                ))),
+            },
             Err(e) => Err(PyIOError::new_err(format!("{} invalid", e))),
         },
     },
@@ -264,8 +498,8 @@ pub const ATTRIBUTE_HANDLERS: &[AttributeValueHandler] = &[
             AttributeValue::IntVec2(vec) => Some([vec.0, vec.1].into_py_any(py)),
             _ => None,
         },
-        from_python: |value| match value.extract::<Vec<i32>>() {
-            Ok(value) => Ok(AttributeValue::IntVec2(Vec2(value[0], value[1]))),
+        from_python: |value| match value.extract::<[i32; 2]>() {
+            Ok([x, y]) => Ok(AttributeValue::IntVec2(Vec2(x, y))),
             Err(e) => Err(PyIOError::new_err(format!("{} invalid", e))),
         },
     },
@@ -275,14 +509,65 @@ pub const ATTRIBUTE_HANDLERS: &[AttributeValueHandler] = &[
             AttributeValue::FloatVec2(vec) => Some([vec.0, vec.1].into_py_any(py)),
             _ => None,
         },
-        from_python: |value| match value.extract::<Vec<f32>>() {
-            Ok(value) => Ok(AttributeValue::FloatVec2(Vec2(value[0], value[1]))),
+        from_python: |value| match value.extract::<[f32; 2]>() {
+            Ok([x, y]) => Ok(AttributeValue::FloatVec2(Vec2(x, y))),
             Err(e) => Err(PyIOError::new_err(format!("{} invalid", e))),
         },
     },
 ];
 
+struct TextEnum {
+    attribute: String,
+    values: Vec<(String, Py<PyAny>)>,
+}
+
+// Text attributes read as Python values, registered at runtime with `register_text_enum`.
+static TEXT_ENUMS: Mutex<Vec<TextEnum>> = Mutex::new(Vec::new());
+
+/// Replaces the enum mapping for `attribute`; an empty mapping removes it.
+pub fn register_text_enum(attribute: String, mapping: Vec<(String, Py<PyAny>)>) -> PyResult<()> {
+    if let Some((text, _)) = mapping
+        .iter()
+        .find(|(text, _)| Text::new_or_none(text).is_none())
+    {
+        return Err(PyValueError::new_err(format!(
+            "Enum text {} for {} must be ASCII",
+            text, attribute
+        )));
+    }
+
+    let mut text_enums = TEXT_ENUMS.lock().unwrap();
+    text_enums.retain(|text_enum| text_enum.attribute != attribute);
+    if !mapping.is_empty() {
+        text_enums.push(TextEnum {
+            attribute,
+            values: mapping,
+        });
+    }
+    Ok(())
+}
+
+fn text_enum_values(key: &str, py: Python) -> Option<Vec<(String, Py<PyAny>)>> {
+    let text_enums = TEXT_ENUMS.lock().unwrap();
+    let text_enum = text_enums
+        .iter()
+        .find(|text_enum| text_enum.attribute == key)?;
+    let values = text_enum.values.iter();
+    Some(
+        values
+            .map(|(text, value)| (text.clone(), value.clone_ref(py)))
+            .collect(),
+    )
+}
+
 pub fn to_python(key: &str, value: &AttributeValue, py: Python) -> PyResult<Py<PyAny>> {
+    if let (AttributeValue::Text(text), Some(values)) = (value, text_enum_values(key, py)) {
+        let text = text.to_string();
+        if let Some((_, value)) = values.into_iter().find(|(candidate, _)| *candidate == text) {
+            return Ok(value);
+        }
+    }
+
     let mut last_error: Option<PyErr> = None;
     for handler in ATTRIBUTE_HANDLERS {
         match (handler.to_python)(value, py) {
@@ -315,6 +600,14 @@ pub fn from_python<'py>(
     value: &Bound<'py, PyAny>,
     py: Python<'py>,
 ) -> PyResult<AttributeValue> {
+    if let Some(values) = text_enum_values(key, py) {
+        for (text, candidate) in values {
+            if value.eq(candidate.bind(py))? {
+                return Ok(AttributeValue::Text(Text::from(text.as_str())));
+            }
+        }
+    }
+
     let mut last_error: Option<PyErr> = None;
     for handler in ATTRIBUTE_HANDLERS {
         match (handler.from_python)(value) {
@@ -345,8 +638,11 @@ pub fn pydict_from_attributes<'py>(
 ) -> PyResult<Bound<'py, PyDict>> {
     let dict = PyDict::new(py);
     for (key, value) in attributes.iter() {
-        let py_value = to_python(key.to_string().as_str(), value, py)?;
-        dict.set_item(key.to_string(), py_value)?;
+        // Loaded files can hold attribute types without a Python representation. They are
+        // skipped here and reported in the loaded image's `warnings()`.
+        if let Ok(py_value) = to_python(key.to_string().as_str(), value, py) {
+            dict.set_item(key.to_string(), py_value)?;
+        }
     }
     Ok(dict)
 }
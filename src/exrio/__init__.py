@@ -1,11 +1,19 @@
 from .image import (
+    CHANNEL_NAME_ALIASES,
     PRIMARY_CHROMATICITIES,
+    Box2i,
     Chromaticities,
     Colorspace,
+    Comparison,
     ExrChannel,
     ExrImage,
     ExrLayer,
+    compare,
+    from_imageio,
     load,
+    register_text_enum,
+    set_default_chromaticities,
+    to_imageio,
 )
 
 __all__ = [
@@ -14,6 +22,14 @@ __all__ = [
     "ExrLayer",
     "ExrChannel",
     "Chromaticities",
+    "Box2i",
     "Colorspace",
     "PRIMARY_CHROMATICITIES",
+    "CHANNEL_NAME_ALIASES",
+    "register_text_enum",
+    "set_default_chromaticities",
+    "compare",
+    "Comparison",
+    "to_imageio",
+    "from_imageio",
 ]
@@ -1,26 +1,177 @@
+import gzip
 import json
-from dataclasses import dataclass, field
+import os
+import shutil
+import tempfile
+import warnings
+from contextlib import contextmanager
+from dataclasses import dataclass, field, replace
 from enum import Enum
 from io import BytesIO
 from pathlib import Path
-from typing import Any, Optional, Union
+from typing import Any, BinaryIO, Callable, Iterator, Optional, Union
 
 import numpy as np
 from numpy.typing import NDArray
 
+from exrio._rust import Box2i
 from exrio._rust import ExrImage as RustImage
 from exrio._rust import ExrLayer as RustLayer
+from exrio._rust import compare as _compare
+from exrio._rust import default_chromaticities, exr_dtype, known_primaries, match_primaries
+from exrio._rust import register_text_enum
+from exrio._rust import set_default_chromaticities
 
 ACES_IMAGE_CONTAINER_FLAG = "acesImageContainerFlag"
 EXRIO_COLORSPACE_KEY = "py/exrio/Colorspace"
 
+# Canonical channel names used by `normalize_names`, keyed by the lowercase name found in the file.
+# Only the last dot-separated component is normalized, so "diffuse.r" becomes "diffuse.R".
+CHANNEL_NAME_ALIASES = {
+    "r": "R",
+    "red": "R",
+    "g": "G",
+    "green": "G",
+    "b": "B",
+    "blue": "B",
+    "a": "A",
+    "alpha": "A",
+    "z": "Z",
+    "depth": "Z",
+}
+
+
+# Names `ExrLayer.alpha()` looks for, in order.
+ALPHA_CHANNEL_NAMES = ["A", "a", "alpha"]
+
+
+def _unique_layer_name(name: str, taken: set[str]) -> str:
+    suffix = 1
+    while f"{name}.{suffix}" in taken:
+        suffix += 1
+    return f"{name}.{suffix}"
+
+
+GZIP_MAGIC = b"\x1f\x8b"
+ZSTD_MAGIC = b"\x28\xb5\x2f\xfd"
+
+
+def _decompressing_reader(file: BinaryIO) -> BinaryIO:
+    """
+    Wraps a seekable binary file in a streaming gzip or zstd decompressor, detected by magic
+    bytes, so the compressed data is never held in memory as a whole. Others pass through.
+    """
+    magic = file.read(len(ZSTD_MAGIC))
+    file.seek(-len(magic), 1)
+    if magic.startswith(GZIP_MAGIC):
+        return gzip.GzipFile(fileobj=file, mode="rb")  # type: ignore
+    if not magic.startswith(ZSTD_MAGIC):
+        return file
+
+    try:
+        from compression import zstd  # type: ignore
+    except ImportError:
+        try:
+            import zstandard  # type: ignore
+        except ImportError:
+            raise ValueError(
+                "reading zstd-compressed EXR files requires Python 3.14 or the "
+                "zstandard package, e.g. `pip install exrio[zstd]`"
+            ) from None
+        return zstandard.ZstdDecompressor().stream_reader(file)
+    return zstd.ZstdFile(file)
+
+
+def _unwrap_compressed(buffer: bytes) -> bytes:
+    """Decompresses a gzip or zstd wrapped EXR held in memory; others pass through."""
+    if not buffer.startswith((GZIP_MAGIC, ZSTD_MAGIC)):
+        return buffer
+    return _decompressing_reader(BytesIO(buffer)).read()
+
+
+@contextmanager
+def _uncompressed_path(path: Union[str, Path]) -> Iterator[Union[str, Path]]:
+    """
+    Yields a path the Rust loaders can read directly: `path` itself, or for a gzip or zstd
+    wrapped file a temporary copy decompressed in a stream, deleted on exit.
+    """
+    with open(path, "rb") as file:
+        reader = _decompressing_reader(file)
+        if reader is file:
+            temporary = None
+        else:
+            with tempfile.NamedTemporaryFile(suffix=".exr", delete=False) as temporary:
+                shutil.copyfileobj(reader, temporary)
+
+    if temporary is None:
+        yield path
+        return
+    try:
+        yield temporary.name
+    finally:
+        os.remove(temporary.name)
+
+
+def _iter_tiles_decompressed(
+    path: Union[str, Path], layer: int, channel: str
+) -> Iterator[tuple[int, int, NDArray[Any]]]:
+    # The decompressed copy must outlive the Rust iterator, which reads it tile by tile.
+    with _uncompressed_path(path) as uncompressed:
+        yield from RustImage.iter_tiles(uncompressed, layer, channel)
+
+
+def _resolve_channel_aliases(
+    normalize_names: bool, channel_aliases: Optional[dict[str, str]]
+) -> Optional[dict[str, str]]:
+    if not normalize_names:
+        return None
+    return CHANNEL_NAME_ALIASES if channel_aliases is None else channel_aliases
+
+
+def _normalize_channel_name(name: str, aliases: dict[str, str]) -> str:
+    prefix, dot, suffix = name.rpartition(".")
+    return prefix + dot + aliases.get(suffix.lower(), suffix)
+
 
 def _pixels_from_layer(layer: RustLayer) -> list[NDArray[Any]]:
     pixels = layer.pixels()
     assert pixels is not None
-    return [
-        pixels[i].reshape(layer.height(), layer.width()) for i in range(len(pixels))
-    ]
+    # Channels declared by `from_schema` stay empty until they are filled.
+    return [p.reshape(layer.height(), layer.width()) if p.size else p for p in pixels]
+
+
+EXR_DTYPES = {
+    "f16": np.float16,
+    "float16": np.float16,
+    "f32": np.float32,
+    "float32": np.float32,
+    "u32": np.uint32,
+    "uint32": np.uint32,
+}
+
+
+def _as_exr_dtype(array: NDArray[Any], dtype: Optional[str] = None) -> NDArray[Any]:
+    """
+    Casts `array` to the sample type `dtype` names, or else the one Rust stores its dtype as
+    (see `exr_dtype`): float16 stays float16, other floats become float32, and integers become
+    uint32.
+    """
+    target = np.dtype(exr_dtype(array, dtype))
+    if array.dtype == target:
+        return array
+    return _convert_pixels(array, target)
+
+
+def _convert_pixels(pixels: NDArray[Any], target: Any) -> NDArray[Any]:
+    """
+    Converts `pixels` to `target` the way the Rust side converts samples: floats cast to
+    uint32 are truncated toward zero and saturate, with NaN becoming 0.
+    """
+    if target == np.uint32 and pixels.dtype.kind == "f":
+        limit = np.iinfo(np.uint32).max
+        clipped = np.clip(np.nan_to_num(pixels.astype(np.float64), nan=0.0), 0, limit)
+        return clipped.astype(np.uint32)
+    return pixels.astype(target)
 
 
 class Colorspace(str, Enum):
@@ -44,10 +195,18 @@ class Colorspace(str, Enum):
 
 @dataclass
 class Chromaticities:
-    red: tuple[float, float] = field(default=(0.64, 0.33))
-    green: tuple[float, float] = field(default=(0.3, 0.6))
-    blue: tuple[float, float] = field(default=(0.15, 0.06))
-    white: tuple[float, float] = field(default=(0.3127, 0.329))
+    """
+    Red, green, blue, and white points as CIE `(x, y)` pairs. Points left out default to the
+    session default, Rec.709 (sRGB) with a D65 white point unless changed with
+    `set_default_chromaticities("AP0")` or `set_default_chromaticities("AP1")`. The default
+    only applies here: images loaded without chromaticities keep `chromaticities=None`, and
+    nothing is added to them on save.
+    """
+
+    red: tuple[float, float] = field(default_factory=lambda: default_chromaticities()[0])
+    green: tuple[float, float] = field(default_factory=lambda: default_chromaticities()[1])
+    blue: tuple[float, float] = field(default_factory=lambda: default_chromaticities()[2])
+    white: tuple[float, float] = field(default_factory=lambda: default_chromaticities()[3])
 
     def to_list(self) -> list[float]:
         return [*self.red, *self.green, *self.blue, *self.white]
@@ -75,29 +234,9 @@ class Chromaticities:
         return f"chroma:{json.dumps(values_dict)}"
 
 
+# The primaries themselves live in Rust (`known_primaries`), shared with `color_space`.
 PRIMARY_CHROMATICITIES = {
-    # https://pub.smpte.org/pub/st2065-1/st2065-1-2021.pdf
-    "AP0": Chromaticities(
-        red=(0.7347, 0.2653),
-        green=(0.0000, 1.0000),
-        blue=(0.0001, -0.0770),
-        white=(0.32168, 0.33767),
-    ),
-    # https://docs.acescentral.com/specifications/acescg/
-    # https://docs.acescentral.com/specifications/acescct/
-    "AP1": Chromaticities(
-        red=(0.713, 0.293),
-        green=(0.165, 0.830),
-        blue=(0.128, 0.044),
-        white=(0.32168, 0.33767),
-    ),
-    # https://www.color.org/chardata/rgb/srgb.xalter
-    "sRGB": Chromaticities(
-        red=(0.64, 0.33),
-        green=(0.3, 0.6),
-        blue=(0.15, 0.06),
-        white=(0.3127, 0.329),
-    ),
+    name: Chromaticities(*known_primaries(name)) for name in ["AP0", "AP1", "sRGB"]
 }
 
 
@@ -106,19 +245,54 @@ class ExrChannel:
     name: str
     width: int
     height: int
+    # float16, float32, or uint32 in native byte order; byte-swapped arrays are rejected.
     pixels: NDArray[Any]
+    raw_name: Optional[str] = None
+    # The `pLinear` flag: values are perceptually linear (e.g. chroma), so B44 skips its log curve.
+    perceptually_linear: bool = False
+    # The value outside the data window; `None` leaves it unset, which readers treat as zero.
+    default_value: Optional[float] = None
 
     @staticmethod
     def _from_rust(
-        name: str, width: int, height: int, pixels: NDArray[Any]
+        name: str,
+        width: int,
+        height: int,
+        pixels: NDArray[Any],
+        channel_aliases: Optional[dict[str, str]] = None,
+        perceptually_linear: bool = False,
+        default_value: Optional[float] = None,
     ) -> "ExrChannel":
         return ExrChannel(
-            name=name,
+            name=(
+                name
+                if channel_aliases is None
+                else _normalize_channel_name(name, channel_aliases)
+            ),
             width=width,
             height=height,
             pixels=pixels,
+            raw_name=name,
+            perceptually_linear=perceptually_linear,
+            default_value=default_value,
         )
 
+    def __buffer__(self, flags: int) -> memoryview:
+        """
+        Exposes the pixels through the buffer protocol (Python 3.12+) as a `(height, width)`
+        view in their own sample type ("e" for float16, "f" for float32, "I" for uint32), so
+        e.g. `memoryview(channel)` or `torch.frombuffer(channel, dtype=torch.float16)` reads
+        them without a copy. The view shares memory with `pixels` and keeps it alive while
+        held, even if `pixels` is reassigned; writing through it changes the channel.
+        Non-contiguous pixels, such as channels sliced out of an (H, W, C) array, are exposed
+        with their strides, which consumers that require contiguous data like torch reject.
+        Python 3.9 to 3.11 can use `memoryview(channel.pixels)` instead.
+        """
+        pixels = self.pixels
+        if pixels.ndim == 1:
+            pixels = pixels.reshape(self.height, self.width)
+        return memoryview(pixels)
+
 
 @dataclass
 class ExrLayer:
@@ -127,6 +301,46 @@ class ExrLayer:
     channels: list[ExrChannel]
     name: Optional[str] = None
     attributes: dict[str, Any] = field(default_factory=dict)
+    line_order: str = "increasing"
+    compression: str = "piz"
+    tile_size: Optional[tuple[int, int]] = None
+    # "one_level", "mipmap", or "ripmap" as loaded; only the full resolution is ever written.
+    level_mode: str = "one_level"
+    # The name in the file when loading suffixed a repeated part name, e.g. "beauty" for
+    # "beauty.1". Not written back; the layer is saved under `name`.
+    file_name: Optional[str] = None
+
+    @staticmethod
+    def from_schema(
+        name: Optional[str], width: int, height: int, channels: list[tuple[str, str]]
+    ) -> "ExrLayer":
+        """
+        Declares a layer's channels as `(name, dtype)` pairs, e.g. `[("R", "f16"), ("Z",
+        "f32")]`, with empty pixels to be filled frame by frame with `set_channel`. Saving
+        raises until every channel has been filled.
+        """
+        return ExrLayer._from_rust(RustLayer.from_schema(name, width, height, channels))
+
+    def set_channel(self, name: str, pixels: NDArray[Any]) -> None:
+        """
+        Fills a declared channel with `pixels`, which must have its dtype and one sample per
+        pixel, e.g. a `(height, width)` array. The checks are those of the Rust `set_channel`,
+        so other floats are stored as float32 and integers as uint32 first.
+        """
+        channel = next((c for c in self.channels if c.name == name), None)
+        if channel is None:
+            raise KeyError(f"Channel {name} not found")
+        declared = [(name, channel.pixels.dtype.name)]
+        RustLayer.from_schema(None, channel.width, channel.height, declared).set_channel(
+            name, pixels
+        )
+        channel.pixels = _as_exr_dtype(pixels)
+
+    def tile_description(self) -> Optional[tuple[int, int, str]]:
+        """`(tile_width, tile_height, level_mode)` for tiled layers, `None` for scanlines."""
+        if self.tile_size is None:
+            return None
+        return (*self.tile_size, self.level_mode)
 
     @property
     def is_rgb_like(self) -> bool:
@@ -138,15 +352,99 @@ class ExrLayer:
         channel_names = set([c.name for c in self.channels])
         return channel_names == {"L"} or channel_names == {"A"}
 
+    @property
+    def position(self) -> tuple[int, int]:
+        """Position of the layer's data window, which may be negative for overscan."""
+        x, y = self.attributes.get("layer_position", (0, 0))
+        return (x, y)
+
+    @position.setter
+    def position(self, position: tuple[int, int]) -> None:
+        self.attributes["layer_position"] = list(position)
+
+    @property
+    def screen_window(self) -> dict[str, Any]:
+        """
+        The camera's screen window as `{"center": (x, y), "width": w}`, from the
+        `screen_window_center` and `screen_window_width` attributes. Missing values take the
+        EXR defaults, a center of (0, 0) and a width of 1.
+        """
+        x, y = self.attributes.get("screen_window_center", (0.0, 0.0))
+        return {"center": (x, y), "width": self.attributes.get("screen_window_width", 1.0)}
+
+    @screen_window.setter
+    def screen_window(self, window: dict[str, Any]) -> None:
+        if not window["width"] > 0:
+            raise ValueError(f"Screen window width must be positive, got {window['width']}")
+        self.attributes["screen_window_center"] = [float(c) for c in window["center"]]
+        self.attributes["screen_window_width"] = float(window["width"])
+
+    def horizontal_density(self, unit: str = "inch") -> Optional[float]:
+        """
+        The horizontal output density from the `xDensity` attribute, which EXR stores in pixels
+        per inch. `unit` is "inch" or "cm"; the vertical density is this times the image's
+        pixel aspect ratio. The unit is converted by the Rust layer.
+        """
+        rust_layer = RustLayer(self.name)
+        density = self.attributes.get("horizontal_density")
+        if density is not None:
+            rust_layer.with_attributes({"horizontal_density": density})
+        return rust_layer.horizontal_density(unit)
+
+    def set_horizontal_density(self, density: float, unit: str = "inch") -> None:
+        """Sets the `xDensity` attribute from a density in pixels per `unit` ("inch" or "cm")."""
+        rust_layer = RustLayer(self.name).with_horizontal_density(density, unit)
+        self.attributes["horizontal_density"] = rust_layer.attributes()["horizontal_density"]
+
+    @property
+    def data_window(self) -> Box2i:
+        """The layer's data window, i.e. its position and size."""
+        x, y = self.position
+        return Box2i(x, y, self.width, self.height)
+
+    def raw_channel_names(self) -> list[str]:
+        """Returns the channel names as stored in the file, before any normalization."""
+        return [channel.raw_name or channel.name for channel in self.channels]
+
+    def astype(self, dtype: str) -> "ExrLayer":
+        """
+        Returns a copy with every channel converted to `dtype` ("f16", "f32", or "u32"), which
+        is also the sample type written on the next save. Narrowing to float16 rounds to the
+        nearest half (values beyond ±65504 become infinite).
+
+        Floats converted to u32 are truncated toward zero, as with numpy's `astype`, and
+        saturate at 0 and 2**32 - 1, with NaN becoming 0. Round first (e.g. `np.rint`) when
+        the values are meant as the nearest integer id.
+        """
+        np_dtype = EXR_DTYPES.get(dtype)
+        if np_dtype is None:
+            raise ValueError(f"Unsupported dtype {dtype}, expected f16, f32, or u32")
+
+        channels = [
+            replace(channel, pixels=_convert_pixels(channel.pixels, np_dtype))
+            for channel in self.channels
+        ]
+        return replace(self, channels=channels, attributes=dict(self.attributes))
+
     def _to_rust(self) -> RustLayer:
-        layer = RustLayer(name=self.name)
-        layer.with_width(self.width)
-        layer.with_height(self.height)
+        # Channels are declared first, so that those with empty pixels, as left by
+        # `from_schema` or for `to_path_streaming`, keep their dtype without being filled.
+        layer = RustLayer.from_schema(
+            self.name,
+            self.width,
+            self.height,
+            [(channel.name, channel.pixels.dtype.name) for channel in self.channels],
+        )
+        layer.with_line_order(self.line_order)
+        layer.with_compression(self.compression)
+        layer.with_tile_size(self.tile_size)
         layer.with_attributes(self.attributes)
         for channel in self.channels:
             assert channel.pixels.dtype in [np.float16, np.float32, np.uint32]
-            pixels = channel.pixels.flatten()
-            layer.with_channel(channel=channel.name, pixels=pixels.copy(order="C"))
+            if channel.pixels.size > 0:
+                layer.set_channel(channel.name, channel.pixels)
+            layer.with_perceptually_linear(channel.name, channel.perceptually_linear)
+            layer.with_default_value(channel.name, channel.default_value)
         return layer
 
     def to_pixels(self) -> NDArray[Any]:
@@ -173,8 +471,109 @@ class ExrLayer:
 
         return np.stack(rgb_pixels, axis=-1)
 
+    def alpha(self) -> Optional[NDArray[Any]]:
+        """
+        The alpha channel as a `(height, width)` array, or `None` if the layer has none.
+        Looks for "A", then "a", then "alpha", and returns the first that exists.
+        """
+        channels = {channel.name: channel for channel in self.channels}
+        for name in ALPHA_CHANNEL_NAMES:
+            if name in channels:
+                channel = channels[name]
+                return channel.pixels.reshape(channel.height, channel.width)
+        return None
+
+    def to_rgba(self, fill_alpha: float = 1.0, allow_missing: bool = True) -> NDArray[np.float32]:
+        """
+        Returns the layer as a float32 `(height, width, 4)` RGBA array. Alpha is found like
+        `alpha()`. By default missing channels are synthesized: alpha is filled with
+        `fill_alpha` (1.0, fully opaque) and R, G, or B with 0, so an RGB-only file still
+        gives an RGBA array. With `allow_missing=False` a missing channel raises a `KeyError`.
+        """
+        channel_pixels = {channel.name: channel.pixels for channel in self.channels}
+        shape = (self.height, self.width)
+        alpha = self.alpha()
+
+        rgba = []
+        for name in ["R", "G", "B", "A"]:
+            pixels = alpha if name == "A" else channel_pixels.get(name)
+            if pixels is None:
+                if not allow_missing:
+                    raise KeyError(f"Channel {name} not found")
+                pixels = np.full(shape, fill_alpha if name == "A" else 0.0)
+            rgba.append(pixels.reshape(shape).astype(np.float32))
+        return np.stack(rgba, axis=-1)
+
+    def auto_crop(self, channel: str = "A") -> tuple["ExrLayer", tuple[int, int]]:
+        """
+        Trims the borders where `channel` is zero, e.g. around a sprite or FX element, and
+        returns the cropped layer with the `(x, y)` offset of the kept region. The cropped
+        layer's `position` is moved by the offset so it still lines up with the original.
+        Raises a `ValueError` if `channel` is zero everywhere.
+        """
+        cropped, offset = self._to_rust().auto_crop(channel)
+        return ExrLayer._from_rust(cropped), offset
+
+    def interleaved(self, channel_order: list[str]) -> NDArray[np.float32]:
+        """
+        Returns the channels named in `channel_order` as one float32 `(height, width, channels)`
+        array, e.g. `["R", "G", "B", "A"]` for an RGBA texture upload. The samples are
+        interleaved in Rust; subsampled channels raise a `ValueError`.
+        """
+        return self._to_rust().interleaved(channel_order)
+
+    def as_structured(self, default_key: str = "default") -> dict[str, NDArray[Any]]:
+        """
+        Groups channels by the prefix before their last "." into `(height, width, channels)`
+        arrays, so "diffuse.R", "diffuse.G", and "diffuse.B" become one `diffuse` array.
+        Within a group, R, G, B, and A come first in that order and other channels keep their
+        layer order. Channels without a prefix are grouped under `default_key`.
+        """
+        groups: dict[str, list[tuple[str, NDArray[Any]]]] = {}
+        for channel in self.channels:
+            prefix, dot, suffix = channel.name.rpartition(".")
+            pixels = channel.pixels.reshape(self.height, self.width)
+            groups.setdefault(prefix if dot else default_key, []).append((suffix, pixels))
+
+        def rank(suffix: str) -> int:
+            return "RGBA".index(suffix) if suffix in ["R", "G", "B", "A"] else 4
+
+        return {
+            key: np.stack(
+                [pixels for _, pixels in sorted(channels, key=lambda c: rank(c[0]))],
+                axis=-1,
+            )
+            for key, channels in groups.items()
+        }
+
+    def channels_nested(self, default_key: str = "default") -> dict[str, Any]:
+        """
+        Returns the channels as a nested dict split on ".", so "diffuse.R" and "diffuse.G"
+        become `{"diffuse": {"R": ..., "G": ...}}`. The arrays are the channels' own pixels,
+        not copies. Channels without a "." are placed under `default_key`. Raises a
+        `ValueError` when one channel's name is a prefix of another's, such as "light.key"
+        and "light.key.R".
+        """
+        nested: dict[str, Any] = {}
+        for channel in self.channels:
+            path = channel.name.split(".")
+            if len(path) == 1:
+                path = [default_key, *path]
+
+            node = nested
+            for part in path[:-1]:
+                node = node.setdefault(part, {})
+                if not isinstance(node, dict):
+                    raise ValueError(f"Channel {channel.name} is nested under another channel")
+            if path[-1] in node:
+                raise ValueError(f"Channel {channel.name} conflicts with another channel")
+            node[path[-1]] = channel.pixels
+        return nested
+
     @staticmethod
-    def _from_rust(rust_layer: RustLayer) -> "ExrLayer":
+    def _from_rust(
+        rust_layer: RustLayer, channel_aliases: Optional[dict[str, str]] = None
+    ) -> "ExrLayer":
         width = rust_layer.width()
         assert width is not None
 
@@ -188,7 +587,15 @@ class ExrLayer:
         ), f"expected {len(channel_names)} channels, got {len(channel_pixels)}"
 
         channels = [
-            ExrChannel._from_rust(channel, width, height, pixels)
+            ExrChannel._from_rust(
+                channel,
+                width,
+                height,
+                pixels,
+                channel_aliases,
+                perceptually_linear=rust_layer.perceptually_linear(channel),
+                default_value=rust_layer.default_value(channel),
+            )
             for channel, pixels in zip(channel_names, channel_pixels)
         ]
 
@@ -198,6 +605,11 @@ class ExrLayer:
             height=height,
             channels=channels,
             attributes=rust_layer.attributes(),
+            line_order=rust_layer.line_order(),
+            compression=rust_layer.compression(),
+            tile_size=rust_layer.tile_size(),
+            level_mode=(rust_layer.tile_description() or (0, 0, "one_level"))[2],
+            file_name=rust_layer.file_name(),
         )
 
 
@@ -206,6 +618,12 @@ class ExrImage:
     layers: list[ExrLayer]
     attributes: dict[str, Any] = field(default_factory=dict)
     chromaticities: Optional[Chromaticities] = None
+    # Non-fatal issues found while loading, e.g. skipped attributes or renamed layers.
+    warnings: list[str] = field(default_factory=list)
+    # True when a tolerant load salvaged a damaged file; lost chunks are left as zeros.
+    partial: bool = False
+    # Feature flags from the loaded file's version field, e.g. whether it needs long names.
+    version_flags: Optional[dict[str, Any]] = None
 
     @property
     def inferred_colorspace(self) -> Optional[Colorspace]:
@@ -230,14 +648,16 @@ class ExrImage:
         if self.chromaticities is None:
             return None
 
-        if self.chromaticities.is_close_to(PRIMARY_CHROMATICITIES["sRGB"]):
+        c = self.chromaticities
+        primaries = match_primaries((c.red, c.green, c.blue, c.white))
+        if primaries == "Rec.709":
             # We can't easily determine if the image is sRGB or LinearRGB,
             # so we'll default to the more common sRGB.
             return Colorspace.sRGB
-        elif self.chromaticities.is_close_to(PRIMARY_CHROMATICITIES["AP0"]):
+        elif primaries == "ACES":
             # Thankfully, ACES2065-1 is the only supported colorspace that uses the AP0 primaries.
             return Colorspace.ACES
-        elif self.chromaticities.is_close_to(PRIMARY_CHROMATICITIES["AP1"]):
+        elif primaries == "ACEScg":
             if not self.first_layer:
                 return None
 
@@ -261,12 +681,215 @@ class ExrImage:
             return None
         return self.layers[0]
 
-    def to_buffer(self) -> bytes:
-        return self._to_rust().save_to_buffer()
+    def memory_bytes(self) -> int:
+        """Bytes held by the pixel arrays of all layers, accounting for each channel's dtype."""
+        return sum(
+            channel.pixels.nbytes for layer in self.layers for channel in layer.channels
+        )
+
+    def channels(self) -> list[tuple[Optional[str], str, str]]:
+        """Every channel of every layer as `(layer_name, channel_name, dtype)`."""
+        return [
+            (layer.name, channel.name, channel.pixels.dtype.name)
+            for layer in self.layers
+            for channel in layer.channels
+        ]
+
+    def pixel_count(self) -> int:
+        """Number of pixels across all layers, i.e. the sum of each layer's width times height."""
+        return sum(layer.width * layer.height for layer in self.layers)
+
+    def extend(
+        self,
+        other: "ExrImage",
+        allow_mismatched_dimensions: bool = False,
+        namespace_collisions: bool = False,
+    ) -> None:
+        """
+        Appends the layers of another image to this one, e.g. to combine separate render passes.
+
+        Layers must match the dimensions of this image's first layer unless
+        `allow_mismatched_dimensions` is set. A layer whose name is already taken raises
+        an error, or is renamed with a numeric suffix ("beauty" -> "beauty.1") when
+        `namespace_collisions` is set. The attributes of `other` are not copied.
+        """
+        reference = self.first_layer or other.first_layer
+        taken = set(layer.name for layer in self.layers if layer.name is not None)
+
+        layers: list[ExrLayer] = []
+        for layer in other.layers:
+            if reference is not None and not allow_mismatched_dimensions:
+                if (layer.width, layer.height) != (reference.width, reference.height):
+                    raise ValueError(
+                        f"layer {layer.name} is {layer.width}x{layer.height}, "
+                        f"expected {reference.width}x{reference.height}"
+                    )
+
+            name = layer.name
+            if name is not None and name in taken:
+                if not namespace_collisions:
+                    raise ValueError(f"layer {name} already exists")
+                name = _unique_layer_name(name, taken)
+                layer = replace(layer, name=name)
 
-    def to_path(self, path: Union[str, Path]) -> None:
-        with open(path, "wb") as file:
-            file.write(self.to_buffer())
+            if name is not None:
+                taken.add(name)
+            layers.append(layer)
+
+        self.layers.extend(layers)
+
+    def set_attributes(self, attributes: dict[str, Any], merge: bool = True) -> None:
+        """
+        Merges `attributes` into the image's attributes, keeping any set earlier, including
+        `chromaticities`. With `merge=False` they replace the attributes and chromaticities
+        entirely instead.
+        """
+        if merge:
+            self.attributes.update(attributes)
+            return
+
+        self.attributes = dict(attributes)
+        self.chromaticities = None
+
+    def assemble_tiles(
+        self,
+        display_window: Union[tuple[int, int, int, int], Box2i],
+        policy: str = "last_wins",
+    ) -> ExrLayer:
+        """
+        Composites layers with their own data windows, e.g. render buckets, into one layer
+        covering `display_window` `(x, y, width, height)`. Each layer is placed at its
+        `position` and cropped to the window; the result has every channel of any layer and
+        the first layer's name and attributes.
+
+        With `policy="last_wins"` later layers overwrite earlier ones where they overlap and
+        uncovered pixels are zero. `policy="error"` raises a ValueError on overlaps or gaps.
+        """
+        return ExrLayer._from_rust(self._to_rust().assemble_tiles(display_window, policy))
+
+    def to_buffer(self, **options: Any) -> bytes:
+        """
+        Encodes the image as an EXR file in memory. The save options below are keyword
+        arguments shared with `to_path` and `to_file`; unknown ones raise a `TypeError`.
+
+        The display window defaults to the image's `display_window` attribute, as loaded or
+        set in `attributes`, or to the dimensions of the first layer at the origin when unset.
+        Pass `display_window` as `(x, y, width, height)` or a `Box2i` to set it explicitly,
+        e.g. when the data window intentionally extends beyond the display window for overscan.
+
+        `force_dtype` ("f16", "f32", or "u32") stores every channel with that sample type.
+        Floats are rounded to the nearest half when narrowed to f16 and truncated toward
+        zero when cast to u32 (negative values and NaN become 0).
+
+        `channel_dtypes` overrides the sample type per channel name, e.g.
+        `{"depth": "f32", "id": "u32"}`, and takes precedence over `force_dtype`.
+
+        Narrowing to f16 turns values beyond ±65504, e.g. bright HDR highlights, into
+        infinity. `half_overflow` decides what happens when that would occur: "warn" (the
+        default) emits a `UserWarning` with the number of samples affected, "error" raises a
+        `ValueError` before anything is written, and "ignore" writes them silently.
+
+        `long_names` sets the header flag that allows attribute and channel names longer than
+        31 characters. It is set automatically when such a name is present.
+
+        Each layer is written with its own `line_order` (preserved from the file it was loaded
+        from) unless `line_order` ("increasing", "decreasing", "unspecified") overrides it.
+
+        `pixel_aspect_ratio` overrides the written pixel aspect, e.g. for anamorphic deliveries,
+        without changing `attributes`.
+
+        Channel pixels are top-down: row 0 is the top of the data window, matching the EXR
+        increasing-y order. Pass `flip_y` if the pixels are bottom-up (e.g. read back from an
+        OpenGL framebuffer) to have the rows reversed while writing.
+
+        `compression` names the codec ("none", "rle", "zips", "zip", "piz", "pxr24", "b44",
+        "b44a") for every layer. By default each layer keeps its own `compression` and
+        `tile_size`, preserved from the file it was loaded from and "piz" scanlines for new
+        layers. EXR compresses whole layers, so `smart_compression`
+        classifies layers rather than channels: a layer is written with lossless "zip"
+        whatever `compression` says when every channel in it is data, meaning it is stored as
+        u32 or its name after the last "." is one of `data_channels` (case-insensitive). Those
+        default to depth ("Z", "ZBack", "depth"), alpha and mask ("A", "alpha", "mask",
+        "matte"), and ID ("id", "objectId", "materialId") names. Color layers keep `compression`.
+
+        `encoding` picks one of exr's presets for the speed/size tradeoff instead of each
+        layer's own compression and tiling. An explicit `compression` or `line_order`
+        overrides the preset's.
+        - "fast_lossless": RLE in 64x64 tiles, fastest, good for mattes and masks
+        - "small_lossless": ZIP in 16-line scanline blocks, slow but small
+        - "small_fast_lossless": PIZ in 256x256 tiles, small and not too slow
+        - "uncompressed": no compression, largest files
+
+        Encoding in memory needs about twice the size of the pixels. A `MemoryError` is raised
+        before encoding when that exceeds `memory_limit` bytes, which defaults to the memory
+        available (capped by the container's cgroup limit on Linux, the physical memory on
+        macOS). `to_path` writes one layer at a time and needs far less.
+
+        `software` is written as every layer's `software` attribute to record which tool wrote
+        the file, e.g. your app's name. By default layers keep the `software` attribute they
+        were loaded with, and those without one get "exrio" and its version.
+
+        `clamp=(low, high)` clamps float channels into that range in the written file only,
+        e.g. to drop negative values and fireflies before lossy compression.
+
+        `threads` compresses blocks on that many worker threads when `to_path` or `to_file`
+        write one layer at a time, e.g. for large PIZ images. By default blocks are compressed
+        on the calling thread, which lets servers saving several files at once keep each save
+        to a bounded share of their cores. Encoding in memory always uses exr's own threads.
+
+        EXR files store each layer's channels alphabetically, so they are sorted on save and
+        load back as B, G, R whatever order they were added in. With
+        `require_sorted_channels=True` a layer that is not already sorted raises a ValueError
+        instead of being silently reordered.
+        """
+        return self._to_rust().save_to_buffer(**options)
+
+    def to_path(self, path: Union[str, Path], **options: Any) -> bool:
+        """
+        Writes the image to `path` with the same options as `to_buffer`, one layer at a time
+        rather than encoding the whole file in memory. `memory_limit` is checked against the
+        size of the largest layer. Returns whether any sample was clamped to `clamp`.
+        """
+        return self._to_rust().save_to_path(path, **options)
+
+    def to_file(self, file: Any, **options: Any) -> bool:
+        """
+        Writes the image into a binary file-like object with the same options as `to_path`,
+        e.g. an `io.BytesIO` or an open socket file, without a temporary file. Seekable objects
+        are written one layer at a time from their current position; others get the whole
+        file in a single `write`. Errors raised by the object surface as `OSError`.
+        """
+        return self._to_rust().save_to_file(file, **options)
+
+    def to_path_streaming(
+        self, path: Union[str, Path], fill_layer: Callable[[int], ExrLayer]
+    ) -> None:
+        """
+        Writes the image one layer at a time, for multipart files too large to hold in memory.
+        `self.layers` only declare each layer's name, size, attributes, and channel dtypes, so
+        their channel pixels can be empty arrays, e.g. `np.empty(0, dtype=np.float16)`.
+        `fill_layer(index)` is called once per layer, in order, and must return a layer with
+        the same size and channels. Exceptions raised by it stop the write and propagate.
+        """
+        self._to_rust().save_streaming_to_path(
+            path, lambda index: fill_layer(index)._to_rust()
+        )
+
+    def layer(self, name: str) -> ExrLayer:
+        """
+        Returns the first layer named `name`. EXR files may repeat a part name; loading keeps
+        every part in `layers`, suffixing repeats ("beauty.1"), and lookups by the file's name
+        match those too, in file order. Use `layers_named` for every match. Raises a `KeyError`
+        if no layer matches.
+        """
+        for layer in self.layers:
+            if name in (layer.name, layer.file_name):
+                return layer
+        raise KeyError(f"Layer {name} not found")
+
+    def layers_named(self, name: str) -> list[ExrLayer]:
+        """Returns every layer named `name` in file order, including suffixed repeats."""
+        return [layer for layer in self.layers if name in (layer.name, layer.file_name)]
 
     def to_pixels(self) -> NDArray[Any]:
         """
@@ -312,28 +935,298 @@ class ExrImage:
         return image
 
     @staticmethod
-    def _from_rust(rust_image: RustImage) -> "ExrImage":
+    def _from_rust(
+        rust_image: RustImage, channel_aliases: Optional[dict[str, str]] = None
+    ) -> "ExrImage":
         attributes = rust_image.attributes()
         chromaticities = attributes.get("chromaticities")
         if chromaticities is not None:
             chromaticities = Chromaticities._from_rust(chromaticities)
         return ExrImage(
-            layers=[ExrLayer._from_rust(layer) for layer in rust_image.layers()],
+            layers=[
+                ExrLayer._from_rust(layer, channel_aliases)
+                for layer in rust_image.layers()
+            ],
             attributes=attributes,
             chromaticities=chromaticities,
+            warnings=rust_image.warnings(),
+            partial=rust_image.partial(),
+            version_flags=rust_image.version_flags(),
         )
 
     @staticmethod
-    def from_buffer(buffer: Union[BytesIO, bytes]) -> "ExrImage":
+    def from_buffer(
+        buffer: Union[BytesIO, bytes],
+        normalize_names: bool = False,
+        channel_aliases: Optional[dict[str, str]] = None,
+        channel_order: Optional[list[str]] = None,
+        flip_y: bool = False,
+        tolerant: bool = False,
+    ) -> "ExrImage":
+        """
+        Reads an EXR file from memory.
+
+        With `normalize_names`, common channel names are canonicalized using `channel_aliases`
+        (defaults to `CHANNEL_NAME_ALIASES`) so that e.g. "alpha" and "a" both become "A".
+        The names as stored in the file remain available via `ExrLayer.raw_channel_names()`.
+
+        EXR files always store channels alphabetically (B, G, R). Pass `channel_order`, e.g.
+        `["R", "G", "B", "A"]`, to move those channels to the front of each layer in that order.
+
+        Pixels are top-down (row 0 is the top of the data window). With `flip_y` the rows are
+        reversed while reading to give bottom-up pixels, e.g. for OpenGL textures.
+
+        Attributes whose types have no Python representation (e.g. matrices or custom types)
+        are skipped rather than failing the load, and listed in `warnings` along with other
+        non-fatal issues such as renamed duplicate layers. Skipped attributes are not written
+        when the image is saved again.
+
+        Files wrapped in gzip or zstd (e.g. `.exr.gz`) are decompressed transparently, detected
+        by their magic bytes. The path-based readers such as `load_mmap`, `read_blocks`, and
+        `chunk_count` stream them into a temporary file first. zstd needs Python 3.14 or the
+        `zstandard` package, installed by the `exrio[zstd]` extra.
+
+        Corrupt or truncated files raise an `OSError` whose `part_index`, `chunk_index`, and
+        `offset` attributes locate the first chunk that could not be read (`None` if unknown).
+        With `tolerant`, they instead load whatever chunks could be decoded, e.g. to salvage a
+        partial transfer: lost chunks are left as zeros, `partial` is set, and `warnings` says
+        how many chunks were lost. Files whose headers cannot be read still raise.
+        """
         if isinstance(buffer, BytesIO):
             buffer = buffer.getvalue()
-        return ExrImage._from_rust(RustImage.load_from_buffer(buffer))
+        return ExrImage._from_rust(
+            RustImage.load_from_buffer(
+                _unwrap_compressed(buffer),
+                channel_order=channel_order,
+                flip_y=flip_y,
+                tolerant=tolerant,
+            ),
+            _resolve_channel_aliases(normalize_names, channel_aliases),
+        )
+
+    @staticmethod
+    def load_mmap(
+        path: Union[str, Path],
+        normalize_names: bool = False,
+        channel_aliases: Optional[dict[str, str]] = None,
+        channel_order: Optional[list[str]] = None,
+        flip_y: bool = False,
+    ) -> "ExrImage":
+        """
+        Reads an EXR file through a memory map instead of copying it into memory first, which
+        lowers peak memory for large local files. Raises an `IOError` if the file changes while
+        it is read. Takes the same options as `from_buffer`.
+        """
+        with _uncompressed_path(path) as path:
+            rust_image = RustImage.load_mmap(path, channel_order=channel_order, flip_y=flip_y)
+        return ExrImage._from_rust(
+            rust_image, _resolve_channel_aliases(normalize_names, channel_aliases)
+        )
+
+    def prune_constant_layers(self, tolerance: float = 0.0) -> list[Optional[str]]:
+        """
+        Removes layers whose channels are all constant, e.g. empty AOVs, and returns their
+        names. Each channel may hold a different constant; with a `tolerance`, samples may
+        differ by up to that much. The check runs in Rust.
+        """
+        pruned = [layer._to_rust().is_constant(tolerance) for layer in self.layers]
+        names = [layer.name for layer, drop in zip(self.layers, pruned) if drop]
+        self.layers = [layer for layer, drop in zip(self.layers, pruned) if not drop]
+        return names
+
+    @staticmethod
+    def load_mip(
+        path: Union[str, Path],
+        level: int,
+        normalize_names: bool = False,
+        channel_aliases: Optional[dict[str, str]] = None,
+    ) -> "ExrImage":
+        """
+        Reads one resolution level of a mip-mapped file, decoding only that level. Level 0 is
+        the full resolution and each level halves it; the layers report the level's width and
+        height. Raises a `ValueError` if a part has fewer levels, e.g. any level but 0 of a file
+        without mip maps.
+        """
+        with _uncompressed_path(path) as path:
+            rust_image = RustImage.load_mip(path, level)
+        return ExrImage._from_rust(
+            rust_image, _resolve_channel_aliases(normalize_names, channel_aliases)
+        )
+
+    @staticmethod
+    def load_first_layer(path: Union[str, Path]) -> ExrLayer:
+        """
+        Reads only the first layer of a file, which is faster than `from_path` for files
+        with several layers when only the main image is needed.
+        """
+        with _uncompressed_path(path) as path:
+            return ExrLayer._from_rust(RustImage.load_first_layer(path))
+
+    @staticmethod
+    def load_part(
+        path: Union[str, Path], index: int, channel_aliases: Optional[dict[str, str]] = None
+    ) -> ExrLayer:
+        """
+        Reads only part `index` of a multipart file, decoding none of the other parts' chunks.
+        Use `part_count` to find how many parts there are; an out-of-range index raises an
+        `IndexError`.
+        """
+        with _uncompressed_path(path) as path:
+            return ExrLayer._from_rust(RustImage.load_part(path, index), channel_aliases)
+
+    @staticmethod
+    def load_tensor(
+        path: Union[str, Path], layer: Optional[int] = None, channels: Optional[list[str]] = None
+    ) -> tuple[NDArray[np.float32], list[str]]:
+        """
+        Decodes part `layer` (the first by default) straight into one contiguous float32
+        `(height, width, channels)` array and returns it with the channel names along the last
+        axis. `channels` picks and orders the channels, e.g. `["R", "G", "B"]`; by default all
+        are loaded in file order. Unlike `load(...).layers[i].interleaved(...)`, no per-channel
+        arrays are built, which keeps memory and copies down when feeding a dataloader.
+        """
+        with _uncompressed_path(path) as path:
+            array, names = RustImage.load_tensor(path, layer, channels)
+        return array, list(names)
+
+    @staticmethod
+    def thumbnail(path: Union[str, Path], max_size: int = 256) -> NDArray[np.uint8]:
+        """
+        Returns an HWC RGB uint8 thumbnail of the first layer that fits in `max_size` pixels.
+
+        Mipmapped files are read from the smallest suitable level without decoding the full
+        image; other files are decoded and box filtered. Layers without R, G, and B use their
+        first three channels. Values are clamped to [0, 1] without any tone mapping.
+        """
+        with _uncompressed_path(path) as path:
+            return RustImage.thumbnail(path, max_size)
+
+    @staticmethod
+    def read_blocks(
+        path: Union[str, Path],
+        callback: Callable[[int, tuple[int, int, int, int], dict[str, NDArray[Any]]], None],
+    ) -> None:
+        """
+        Streams the pixels of a file one block (a group of scanlines, or a tile) at a time,
+        for processing files that don't fit in memory. `callback(layer_index, bounds, channels)`
+        receives the block's `(x, y, width, height)` within the layer's data window and a dict
+        of `(height, width)` arrays keyed by channel name. Exceptions raised by the callback
+        stop the read and propagate.
+        """
+        with _uncompressed_path(path) as path:
+            RustImage.read_blocks(path, callback)
+
+    @staticmethod
+    def iter_tiles(
+        path: Union[str, Path], layer: int, channel: str
+    ) -> Iterator[tuple[int, int, NDArray[Any]]]:
+        """
+        Yields `(tile_x, tile_y, pixels)` for every full-resolution tile of `channel` in part
+        `layer` of a tiled file, decoding one tile at a time for out-of-core processing.
+        A tile starts at pixel `(tile_x * tile_width, tile_y * tile_height)` of the data
+        window, with the tile size given by `ExrLayer.tile_size`. `pixels` is a
+        `(height, width)` array in the stored dtype; tiles on the right and bottom edges are
+        cropped to the image and can be smaller. Scanline files raise a `ValueError`, on the
+        first tile for gzip or zstd wrapped files.
+        """
+        with open(path, "rb") as file:
+            compressed = file.read(len(ZSTD_MAGIC)).startswith((GZIP_MAGIC, ZSTD_MAGIC))
+        if not compressed:
+            return RustImage.iter_tiles(path, layer, channel)
+        return _iter_tiles_decompressed(path, layer, channel)
 
     @staticmethod
-    def from_path(path: Union[str, Path]) -> "ExrImage":
+    def chunk_count(path: Union[str, Path]) -> int:
+        """
+        Returns the number of chunks (scanline blocks or tiles) that loading the file decodes,
+        reading only the header. Useful for sizing a progress bar before decoding. Only the full
+        resolution counts: the smaller levels of mipmapped files are not loaded.
+        """
+        with _uncompressed_path(path) as path:
+            return RustImage.chunk_count(path)
+
+    @staticmethod
+    def block_count(path: Union[str, Path]) -> int:
+        """Deprecated alias of `chunk_count`."""
+        warnings.warn(
+            "ExrImage.block_count is deprecated, use chunk_count", DeprecationWarning, stacklevel=2
+        )
+        return ExrImage.chunk_count(path)
+
+    @staticmethod
+    def part_count(path: Union[str, Path]) -> int:
+        """Returns the number of parts (layers) in the file, reading only the header."""
+        with _uncompressed_path(path) as path:
+            return RustImage.part_count(path)
+
+    @staticmethod
+    def from_path(
+        path: Union[str, Path],
+        normalize_names: bool = False,
+        channel_aliases: Optional[dict[str, str]] = None,
+        channel_order: Optional[list[str]] = None,
+        flip_y: bool = False,
+        tolerant: bool = False,
+    ) -> "ExrImage":
         with open(path, "rb") as file:
-            buffer = BytesIO(file.read())
-            return ExrImage.from_buffer(buffer)
+            buffer = _decompressing_reader(file).read()
+            return ExrImage.from_buffer(
+                buffer, normalize_names, channel_aliases, channel_order, flip_y, tolerant
+            )
+
+    @staticmethod
+    def from_array(
+        array: NDArray[Any],
+        channel_names: list[str],
+        layer_name: str = "default",
+        dtype: Optional[str] = None,
+    ) -> "ExrImage":
+        """
+        Creates a single-layer image from an (H, W, C) array, naming the channels along the
+        last axis with `channel_names`, e.g. `["R", "G", "B", "Z"]`.
+
+        The sample type follows the array's dtype: float16 is stored as f16, other floats
+        (e.g. float64) as f32, and integers as u32. Pass `dtype` ("f16", "f32", or "u32") to
+        store the channels as another type.
+        """
+        if array.ndim != 3:
+            raise ValueError(f"Expected an (H, W, C) array, got shape {array.shape}")
+        array = _as_exr_dtype(array, dtype)
+
+        height, width, channel_count = array.shape
+        if len(channel_names) != channel_count:
+            raise ValueError(
+                f"Got {len(channel_names)} channel names for {channel_count} channels"
+            )
+
+        channels = [
+            ExrChannel(name=name, width=width, height=height, pixels=array[..., idx])
+            for idx, name in enumerate(channel_names)
+        ]
+        layer = ExrLayer(name=layer_name, width=width, height=height, channels=channels)
+        return ExrImage(layers=[layer])
+
+    @staticmethod
+    def from_rgba(
+        pixels: NDArray[Any],
+        fill_alpha: float = 1.0,
+        allow_missing: bool = True,
+        layer_name: str = "default",
+        dtype: Optional[str] = None,
+    ) -> "ExrImage":
+        """
+        Creates a single-layer RGBA image from an `(H, W, 4)` array. By default an
+        `(H, W, 3)` RGB array is also accepted and given an alpha channel filled with
+        `fill_alpha` (1.0, fully opaque) in the array's dtype. With `allow_missing=False` only
+        `(H, W, 4)` arrays are accepted and anything else raises a `ValueError`. `dtype` is
+        passed on to `from_array`.
+        """
+        if pixels.ndim == 3 and pixels.shape[-1] == 3 and allow_missing:
+            alpha = np.full((*pixels.shape[:2], 1), fill_alpha, dtype=pixels.dtype)
+            pixels = np.concatenate([pixels, alpha], axis=-1)
+        if pixels.ndim != 3 or pixels.shape[-1] != 4:
+            raise ValueError(f"Expected an (H, W, 4) RGBA array, got shape {pixels.shape}")
+        return ExrImage.from_array(pixels, ["R", "G", "B", "A"], layer_name, dtype)
 
     @staticmethod
     def _from_pixels(
@@ -518,12 +1411,118 @@ class ExrImage:
             raise ValueError(f"Unsupported colorspace: {colorspace}")
 
 
-def load(path_or_buffer: Union[BytesIO, bytes, str, Path, NDArray[Any]]) -> ExrImage:
+def load(
+    path_or_buffer: Union[BytesIO, bytes, str, Path, NDArray[Any]],
+    normalize_names: bool = False,
+    channel_aliases: Optional[dict[str, str]] = None,
+    channel_order: Optional[list[str]] = None,
+    flip_y: bool = False,
+    tolerant: bool = False,
+) -> ExrImage:
     if isinstance(path_or_buffer, np.ndarray):
         return ExrImage.from_pixels(path_or_buffer)
     elif isinstance(path_or_buffer, str) or isinstance(path_or_buffer, Path):
-        return ExrImage.from_path(path_or_buffer)
+        return ExrImage.from_path(
+            path_or_buffer, normalize_names, channel_aliases, channel_order, flip_y, tolerant
+        )
     elif isinstance(path_or_buffer, bytes) or isinstance(path_or_buffer, BytesIO):
-        return ExrImage.from_buffer(path_or_buffer)
+        return ExrImage.from_buffer(
+            path_or_buffer, normalize_names, channel_aliases, channel_order, flip_y, tolerant
+        )
     else:
         raise ValueError(f"Unsupported type: {type(path_or_buffer)}")
+
+
+def to_imageio(
+    image: ExrImage, dtype: str = "float32"
+) -> tuple[NDArray[Any], dict[str, Any]]:
+    """
+    Returns the first layer as an (H, W, C) array and a metadata dict, the shape of what
+    imageio's EXR plugin returns, for code that used to read EXRs through imageio or PIL.
+    Layers with R, G, and B give RGB or RGBA; other layers give every channel in layer order.
+    The metadata holds the image attributes plus "channel_names" and "layer_name", so
+    `from_imageio` can rebuild the image.
+
+    `dtype` is "float32", which keeps float16 and float32 samples exactly, or "uint8" for
+    8-bit consumers. The uint8 path is lossy: values are clipped to [0, 1] and quantized to
+    256 levels with no tone mapping or sRGB encoding, so highlights above 1 and fine shading
+    are lost, and NaNs become 0.
+    """
+    if dtype not in ["float32", "uint8"]:
+        raise ValueError(f"Unsupported dtype {dtype}, expected float32 or uint8")
+    layer = image.first_layer
+    if layer is None:
+        raise ValueError("Image has no layers")
+
+    channel_names = [channel.name for channel in layer.channels]
+    if {"R", "G", "B"} <= set(channel_names):
+        channel_names = [name for name in "RGBA" if name in channel_names]
+    array = layer.interleaved(channel_names)
+    if dtype == "uint8":
+        array = np.clip(np.nan_to_num(array), 0, 1)
+        array = np.round(array * 255).astype(np.uint8)
+
+    meta = {**image.attributes, "channel_names": channel_names, "layer_name": layer.name}
+    return array, meta
+
+
+def from_imageio(array: NDArray[Any], meta: Optional[dict[str, Any]] = None) -> ExrImage:
+    """
+    Builds a single-layer image from an (H, W) or (H, W, C) array and optional metadata dict,
+    as returned by imageio or `to_imageio`. Channels are named from the metadata's
+    "channel_names", or else L, RGB, or RGBA by count; its "layer_name" names the layer and
+    any other entries become image attributes.
+
+    uint8 arrays are scaled to [0, 1] float32 without undoing any sRGB encoding, so they
+    only have 256 levels; other dtypes are stored as `ExrImage.from_array` stores them.
+    """
+    meta = dict(meta or {})
+    channel_names = meta.pop("channel_names", None)
+    layer_name = meta.pop("layer_name", None)
+
+    if array.ndim == 2:
+        array = array[..., np.newaxis]
+    if array.dtype == np.uint8:
+        array = array.astype(np.float32) / 255
+    if channel_names is None:
+        names = {1: ["L"], 3: ["R", "G", "B"], 4: ["R", "G", "B", "A"]}
+        if array.shape[-1] not in names:
+            raise ValueError(
+                f"Cannot name {array.shape[-1]} channels, pass channel_names in the metadata"
+            )
+        channel_names = names[array.shape[-1]]
+
+    image = ExrImage.from_array(array, channel_names, layer_name or "default")
+    image.attributes = meta
+    return image
+
+
+@dataclass
+class Comparison:
+    # True when the files have the same layers, sizes, and channels, and no channel differs
+    # by more than the tolerance.
+    matches: bool
+    # Mismatched layer counts, names, sizes, or channel sets.
+    differences: list[str]
+    # `(max, mean)` absolute difference per "layer.channel" found in both files.
+    channels: dict[str, tuple[float, float]]
+
+    def __bool__(self) -> bool:
+        return self.matches
+
+
+def compare(
+    path_a: Union[str, Path], path_b: Union[str, Path], tolerance: float = 0.0
+) -> Comparison:
+    """
+    Compares the pixels of two EXR files, e.g. a render against its reference in CI. Both
+    files are decoded and compared in Rust, so neither is loaded into Python. Layers are
+    paired by position and channels by name; NaNs match NaNs. Mismatched structure is
+    reported in `differences` rather than raised.
+    """
+    result = _compare(path_a, path_b, tolerance)
+    return Comparison(
+        matches=result["matches"],
+        differences=result["differences"],
+        channels=result["channels"],
+    )
@@ -1,6 +1,21 @@
+import io
+import struct
+from enum import Enum
+
 import numpy as np
+import pytest
 
-from exrio._rust import ExrImage, ExrLayer
+from exrio._rust import (
+    Box2i,
+    ExrImage,
+    ExrLayer,
+    default_chromaticities,
+    exr_dtype,
+    known_primaries,
+    match_primaries,
+    register_text_enum,
+    set_default_chromaticities,
+)
 
 
 def _create_test_channels():
@@ -58,3 +73,1391 @@ def test_basic_exr_roundtrip_buffer():
 
     read_red_channel = read_layer.pixels()[2]  # Saved as BGR, not RGB
     np.testing.assert_array_almost_equal(read_red_channel, r_channel.reshape(-1))
+
+
+def test_explicit_display_window():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    image = _create_test_image([layer], {})
+
+    buffer = image.save_to_buffer(display_window=(0, 0, 4, 3))
+    read_image = ExrImage.load_from_buffer(buffer)
+
+    assert read_image.attributes()["display_window"].to_tuple() == (0, 0, 4, 3)
+
+
+def test_display_window_from_attributes():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    image = _create_test_image([layer], {"display_window": (-2, -1, 6, 4)})
+
+    read_image = ExrImage.load_from_buffer(image.save_to_buffer())
+    assert read_image.attributes()["display_window"].to_tuple() == (-2, -1, 6, 4)
+
+    image.with_attributes({"displayWindow": [0, 0, 8, 8]})
+    read_image = ExrImage.load_from_buffer(image.save_to_buffer())
+    assert read_image.attributes()["display_window"].to_tuple() == (0, 0, 8, 8)
+
+    with pytest.raises(IOError):
+        image.with_attributes({"display_window": 1.5})
+
+
+def test_half_pixels_stay_half():
+    with open("tests/fixtures/AllHalfValues.exr", "rb") as f:
+        image = ExrImage.load_from_buffer(f.read())
+    layer = image.layers()[0]
+
+    half_pixels = layer.pixels()[0]
+    assert half_pixels.dtype == np.float16
+
+    float_pixels = layer.pixels_f32()[0]
+    assert float_pixels.dtype == np.float32
+    np.testing.assert_array_equal(float_pixels, half_pixels.astype(np.float32))
+
+
+def test_integer_bounds_negative_origin():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    layer.with_attributes({"overscanWindow": (-20, -20, 42, 42)})
+    image = _create_test_image([layer], {})
+
+    buffer = image.save_to_buffer(display_window=(-20, -20, 42, 42))
+    read_image = ExrImage.load_from_buffer(buffer)
+
+    assert read_image.attributes()["display_window"].to_tuple() == (-20, -20, 42, 42)
+    read_layer = read_image.layers()[0]
+    assert read_layer.attributes()["overscanWindow"].to_tuple() == (-20, -20, 42, 42)
+
+
+def test_integer_bounds_from_dict():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    layer.with_attributes({"overscanWindow": {"position": [-20, -10], "size": [42, 32]}})
+    layer.with_attributes({"dataWindow": {"position": (-1, 3), "size": (2, 2)}})
+    assert layer.attributes()["overscanWindow"].to_tuple() == (-20, -10, 42, 32)
+    assert layer.data_window().to_tuple() == (-1, 3, 2, 2)
+
+    for invalid in [
+        {"position": [0, 0]},
+        {"position": [0, 0], "size": [-1, 2]},
+        {"position": [0, 0], "size": [1.5, 2]},
+        {"position": [0, 0], "size": [1, 2], "extra": 1},
+    ]:
+        with pytest.raises(IOError):
+            layer.with_attributes({"overscanWindow": invalid})
+
+
+def test_exr_error_kind():
+    with pytest.raises(IOError) as error:
+        ExrImage.load_from_buffer(b"not an exr file")
+    assert error.value.kind == "Invalid"
+    assert error.value.chunk_index is None
+
+
+def test_load_mip():
+    # 16x8 half RGB with ZIP 8x8 tiles and 5 mip levels. Level L holds L + 0.5 (B), L + 0.25 (G),
+    # and L (R), plus index / 1024 for each pixel in row-major order.
+    path = "tests/fixtures/Mipmap.exr"
+    sizes = [(16, 8), (8, 4), (4, 2), (2, 1), (1, 1)]
+    for level, (width, height) in enumerate(sizes):
+        layer = ExrImage.load_mip(path, level).layers()[0]
+        assert (layer.width(), layer.height()) == (width, height)
+        assert layer.channels() == ["B", "G", "R"]
+        red = layer.pixels()[2]
+        assert red.dtype == np.float16
+        expected = level + np.arange(width * height) / 1024
+        np.testing.assert_array_equal(red, expected.astype(np.float16))
+
+    with open(path, "rb") as f:
+        full = ExrImage.load_from_buffer(f.read()).layers()[0]
+    level_0 = ExrImage.load_mip(path, 0).layers()[0]
+    np.testing.assert_array_equal(level_0.pixels()[0], full.pixels()[0])
+
+    assert level_0.tile_description() == (8, 8, "mipmap")
+    assert full.tile_description() == (8, 8, "mipmap")
+    assert ExrImage.load_first_layer(path).tile_description() == (8, 8, "mipmap")
+    assert full.with_tile_size((8, 8)).tile_description() == (8, 8, "one_level")
+
+    with pytest.raises(ValueError, match="part 0 has 5"):
+        ExrImage.load_mip(path, 5)
+    with pytest.raises(ValueError):
+        ExrImage.load_mip("tests/fixtures/sRGB.exr", 1)
+
+
+def test_mixed_tiled_and_scanline_parts(tmp_path):
+    # 16x8 F32 "beauty" in PIZ 8x8 tiles and "depth" in ZIP16 scanlines. Channel values are
+    # 0, 1000, 2000 and 3000 plus the pixel index.
+    with open("tests/fixtures/Mixed.exr", "rb") as f:
+        original = ExrImage.load_from_buffer(f.read())
+    beauty, depth = original.layers()
+    assert (beauty.name(), beauty.tile_size(), beauty.compression()) == ("beauty", (8, 8), "piz")
+    assert (depth.name(), depth.tile_size(), depth.compression()) == ("depth", None, "zip")
+    np.testing.assert_array_equal(depth.pixels()[0], 3000 + np.arange(128, dtype=np.float32))
+
+    path = tmp_path / "mixed.exr"
+    original.save_to_path(path)
+    read_image = ExrImage.load_from_buffer(path.read_bytes())
+    for read_layer, layer in zip(read_image.layers(), original.layers()):
+        assert read_layer.tile_size() == layer.tile_size()
+        assert read_layer.compression() == layer.compression()
+        for read_pixels, pixels in zip(read_layer.pixels(), layer.pixels()):
+            np.testing.assert_array_equal(read_pixels, pixels)
+    assert ExrImage.load_mip(path, 0).layers()[1].tile_size() is None
+    assert beauty.tile_description() == (8, 8, "one_level")
+    assert depth.tile_description() is None
+
+    part = ExrImage.load_part("tests/fixtures/Mixed.exr", 1)
+    assert (part.name(), part.tile_size(), part.channels()) == ("depth", None, ["Z"])
+    np.testing.assert_array_equal(part.pixels()[0], depth.pixels()[0])
+    part = ExrImage.load_part("tests/fixtures/Mixed.exr", 0)
+    np.testing.assert_array_equal(part.pixels()[2], beauty.pixels()[2])
+
+
+def test_unsupported_attributes_are_reported():
+    # One layer with a m44f world_to_camera, a custom "vendorBlob" attribute, and a text note.
+    with open("tests/fixtures/UnsupportedAttributes.exr", "rb") as f:
+        image = ExrImage.load_from_buffer(f.read())
+    layer = image.layers()[0]
+    assert layer.attributes()["note"] == "kept"
+    assert "world_to_camera" not in layer.attributes()
+    assert "world_to_camera" in layer.attribute_keys()
+    assert len(image.warnings()) == 2
+    assert "vendorData of layer 0, vendorBlob" in image.warnings()[0]
+    assert "world_to_camera of layer 0, m44f" in image.warnings()[1]
+
+    with open("tests/fixtures/ACES-2065-1.exr", "rb") as f:
+        assert ExrImage.load_from_buffer(f.read()).warnings() == []
+    assert ExrImage().warnings() == []
+
+
+def test_truncated_file():
+    with open("tests/fixtures/ACES-2065-1.exr", "rb") as f:
+        data = f.read()
+
+    for length in [len(data) - 10, len(data) // 2]:
+        with pytest.raises(IOError) as error:
+            ExrImage.load_from_buffer(data[:length])
+        assert error.value.kind == "Invalid"
+        # The fixture is a single part of 50 one-scanline chunks.
+        assert error.value.part_index is None
+        assert 0 < error.value.chunk_index < 50
+        assert 0 < error.value.offset < length
+        assert f"chunk {error.value.chunk_index}" in str(error.value)
+
+
+def test_duplicate_channel_names():
+    # The exr reader accepts repeated channel names, which the layer can't keep apart.
+    with open("tests/fixtures/DuplicateChannels.exr", "rb") as f:
+        data = f.read()
+
+    with pytest.raises(IOError) as error:
+        ExrImage.load_from_buffer(data)
+    assert error.value.kind == "Invalid"
+    assert "Channel R appears more than once" in str(error.value)
+
+
+def test_tolerant_load_of_truncated_file():
+    with open("tests/fixtures/ACES-2065-1.exr", "rb") as f:
+        data = f.read()
+
+    full = ExrImage.load_from_buffer(data, tolerant=True)
+    assert not full.partial()
+
+    image = ExrImage.load_from_buffer(data[: len(data) // 2], tolerant=True)
+    assert image.partial()
+    assert any("of 50 chunk(s) could not be decoded" in w for w in image.warnings())
+    width = image.layers()[0].width()
+    full_pixels, pixels = full.layers()[0].pixels()[0], image.layers()[0].pixels()[0]
+    np.testing.assert_array_equal(pixels[:width], full_pixels[:width])
+    assert not pixels[-width:].any()
+
+    with pytest.raises(IOError):
+        ExrImage.load_from_buffer(data[:100], tolerant=True)
+
+
+def test_layer_assert_valid():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    layer.assert_valid()
+
+    layer.with_channel("R", np.zeros(4, dtype=np.float32))
+    with pytest.raises(IOError, match="more than once"):
+        layer.assert_valid()
+
+    with pytest.raises(IOError, match="width and height"):
+        ExrLayer("empty").assert_valid()
+
+
+def test_channel_index():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+
+    assert layer.channel_count() == 3
+    assert layer.channel_index("G") == 1
+    with pytest.raises(KeyError):
+        layer.channel_index("A")
+
+
+def test_reorient_envmap():
+    r_channel, g_channel, b_channel = _create_test_channels()
+    layer = _create_test_layer("test_layer", (r_channel, g_channel, b_channel))
+    layer.with_attributes({"environment_map": "envmap:latlong"})
+    image = _create_test_image([layer], {})
+
+    image.reorient_envmap(180.0)
+    read_image = ExrImage.load_from_buffer(image.save_to_buffer())
+    read_layer = read_image.layers()[0]
+
+    assert read_layer.attributes()["environment_map"] == "envmap:latlong"
+    read_red_channel = read_layer.pixels()[2]  # Saved as BGR, not RGB
+    expected = np.roll(r_channel, 1, axis=1).reshape(-1)
+    np.testing.assert_array_almost_equal(read_red_channel, expected)
+
+    plain = _create_test_layer("plain", (r_channel, g_channel, b_channel))
+    image = _create_test_image([plain], {})
+    image.reorient_envmap(180.0)
+    np.testing.assert_array_equal(image.layers()[0].pixels()[0], r_channel.reshape(-1))
+
+    cube = _create_test_layer("cube", (r_channel, g_channel, b_channel))
+    cube.with_attributes({"environment_map": "envmap:cube"})
+    with pytest.raises(ValueError):
+        _create_test_image([cube], {}).reorient_envmap(90.0)
+
+
+def test_software_attribute(tmp_path):
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    image = _create_test_image([layer], {})
+    path = tmp_path / "image.exr"
+
+    image.save_to_path(path)
+    read_layer = ExrImage.load_from_buffer(path.read_bytes()).layers()[0]
+    assert read_layer.attributes()["software_name"].startswith("exrio ")
+
+    image.save_to_path(path, software="compositor 2.1")
+    read_layer = ExrImage.load_from_buffer(path.read_bytes()).layers()[0]
+    assert read_layer.attributes()["software_name"] == "compositor 2.1"
+
+    read_image = ExrImage.load_from_buffer(path.read_bytes())
+    read_image.save_to_path(path)
+    read_layer = ExrImage.load_from_buffer(path.read_bytes()).layers()[0]
+    assert read_layer.attributes()["software_name"] == "compositor 2.1"
+
+    image.save_streaming_to_path(path, lambda index: image.layers()[index])
+    read_layer = ExrImage.load_from_buffer(path.read_bytes()).layers()[0]
+    assert read_layer.attributes()["software_name"].startswith("exrio ")
+
+
+def test_wrap_modes():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    layer.with_attributes({"environment_map": "envmap:latlong", "wrapmodes": "periodic,clamp"})
+    image = _create_test_image([layer], {})
+
+    read_attributes = ExrImage.load_from_buffer(image.save_to_buffer()).layers()[0].attributes()
+    assert read_attributes["wrap_mode_name"] == "periodic,clamp"
+    assert read_attributes["environment_map"] == "envmap:latlong"
+
+    layer.with_attributes({"wrap_mode_name": "clamp"})
+    with pytest.raises(ValueError):
+        layer.with_attributes({"wrapmodes": "periodic,clamp,black"})
+    with pytest.raises(ValueError):
+        layer.with_attributes({"wrap_mode_name": "repeat"})
+
+
+def test_rgba_channel_order():
+    r_channel, g_channel, b_channel = _create_test_channels()
+    layer = _create_test_layer("test_layer", (r_channel, g_channel, b_channel))
+    layer.with_channel("A", np.ones(4, dtype=np.float32))
+    image = _create_test_image([layer], {})
+    buffer = image.save_to_buffer()
+
+    read_layer = ExrImage.load_from_buffer(buffer).layers()[0]
+    assert read_layer.channels() == ["A", "B", "G", "R"]
+
+    read_layer.reorder_channels()
+    assert read_layer.channels() == ["R", "G", "B", "A"]
+    np.testing.assert_array_almost_equal(read_layer.pixels()[0], r_channel.reshape(-1))
+
+    read_layer = ExrImage.load_from_buffer(buffer, channel_order=["R", "G", "B"]).layers()[0]
+    assert read_layer.channels() == ["R", "G", "B", "A"]
+
+
+def test_astype():
+    r_channel, g_channel, b_channel = _create_test_channels()
+    layer = _create_test_layer("test_layer", (r_channel, g_channel, b_channel))
+
+    half_layer = layer.astype("float16")
+    assert all(pixels.dtype == np.float16 for pixels in half_layer.pixels())
+    assert all(pixels.dtype == np.float32 for pixels in layer.pixels())
+
+    buffer = _create_test_image([half_layer], {}).save_to_buffer()
+    read_layer = ExrImage.load_from_buffer(buffer).layers()[0]
+    assert all(pixels.dtype == np.float16 for pixels in read_layer.pixels())
+
+    id_layer = ExrLayer("ids").with_width(5).with_height(1)
+    id_layer.with_channel("id", np.array([1.9, -2.5, np.nan, np.inf, 7.0], dtype=np.float32))
+    ids = id_layer.astype("u32").pixels()[0]
+    assert ids.dtype == np.uint32
+    np.testing.assert_array_equal(ids, [1, 0, 0, 4294967295, 7])
+
+    with pytest.raises(ValueError):
+        layer.astype("float64")
+
+
+def test_auto_crop():
+    alpha = np.zeros((6, 8), dtype=np.float16)
+    alpha[2:4, 3:7] = 1.0
+    color = np.arange(48, dtype=np.float32).reshape(6, 8)
+    layer = ExrLayer("sprite").with_width(8).with_height(6).with_position(10, -5)
+    layer.with_channel("A", alpha.reshape(-1))
+    layer.with_channel("R", color.reshape(-1))
+
+    cropped, offset = layer.auto_crop()
+    assert offset == (3, 2)
+    assert cropped.data_window() == Box2i(13, -3, 4, 2)
+    a, r = cropped.pixels()
+    assert a.dtype == np.float16 and (a == 1.0).all()
+    np.testing.assert_array_equal(r.reshape(2, 4), color[2:4, 3:7])
+    assert layer.data_window() == Box2i(10, -5, 8, 6)
+
+    empty = ExrLayer("empty").with_width(1).with_height(1)
+    empty.with_channel("A", np.zeros(1, dtype=np.float32))
+    with pytest.raises(ValueError):
+        empty.auto_crop()
+    with pytest.raises(KeyError):
+        layer.auto_crop("alpha")
+
+
+def test_half_overflow():
+    layer = ExrLayer("test_layer").with_width(2).with_height(1)
+    layer.with_channel("R", np.array([1e5, 1.0], dtype=np.float32))
+    image = _create_test_image([layer], {})
+
+    with pytest.warns(UserWarning, match="1 sample"):
+        image.save_to_buffer(force_dtype="f16")
+    with pytest.raises(ValueError):
+        image.save_to_buffer(channel_dtypes={"R": "f16"}, half_overflow="error")
+    with pytest.raises(ValueError):
+        image.save_to_buffer(half_overflow="raise")
+
+
+def test_perceptually_linear():
+    layer = ExrLayer("test_layer").with_width(2).with_height(2)
+    layer.with_channel("R", np.ones(4, dtype=np.float32))
+    layer.with_channel("Y", np.ones(4, dtype=np.float16), perceptually_linear=True)
+    assert layer.perceptually_linear("Y") and not layer.perceptually_linear("R")
+
+    buffer = _create_test_image([layer], {}).save_to_buffer()
+    assert b"Y\0\x01\x00\x00\x00\x01" in buffer
+    read_layer = ExrImage.load_from_buffer(buffer).layers()[0]
+    assert read_layer.perceptually_linear("Y")
+    assert not read_layer.perceptually_linear("R")
+
+    read_layer.with_perceptually_linear("Y", False).with_perceptually_linear("R")
+    buffer = _create_test_image([read_layer], {}).save_to_buffer()
+    read_layer = ExrImage.load_from_buffer(buffer).layers()[0]
+    assert read_layer.perceptually_linear("R")
+    assert not read_layer.perceptually_linear("Y")
+
+    with pytest.raises(KeyError):
+        layer.perceptually_linear("G")
+
+    # A channel that fails to be added leaves no flags behind for a later one of its name.
+    with pytest.raises(IOError):
+        layer.with_channel("G", np.ones(3, dtype=np.float32), perceptually_linear=True)
+    layer.with_channel("G", np.ones(4, dtype=np.float32))
+    assert not layer.perceptually_linear("G")
+
+    layer.with_channel("id", np.arange(4, dtype=np.uint32), perceptually_linear=True)
+    with pytest.raises(ValueError, match="u32"):
+        _create_test_image([layer], {}).save_to_buffer()
+
+
+def test_channel_default_value():
+    layer = ExrLayer("test_layer").with_width(2).with_height(2)
+    layer.with_channel("R", np.ones(4, dtype=np.float32))
+    layer.with_channel("Z", np.ones(4, dtype=np.float32), default_value=1e9)
+    assert layer.default_value("Z") == 1e9 and layer.default_value("R") is None
+
+    buffer = _create_test_image([layer], {}).save_to_buffer()
+    assert b"defaultValue.Z\0float\0" in buffer
+    read_layer = ExrImage.load_from_buffer(buffer).layers()[0]
+    assert read_layer.default_value("Z") == 1e9
+    assert "defaultValue.Z" not in read_layer.attributes()
+
+    read_layer.with_default_value("Z").with_default_value("R", 0.5)
+    read_layer = ExrImage.load_from_buffer(
+        _create_test_image([read_layer], {}).save_to_buffer()
+    ).layers()[0]
+    assert read_layer.default_value("R") == 0.5
+    assert read_layer.default_value("Z") is None
+
+    image = _create_test_image([read_layer.with_position(1, 1)], {})
+    mosaic = image.assemble_tiles((0, 0, 3, 3))
+    assert mosaic.default_value("R") == 0.5
+    assert list(mosaic.pixels()[0][:3]) == [0.5, 0.5, 0.5]
+
+    layer.with_channel("id", np.arange(4, dtype=np.uint32), default_value=0.5)
+    with pytest.raises(ValueError, match="u32"):
+        _create_test_image([layer], {}).save_to_buffer()
+    with pytest.raises(KeyError):
+        layer.default_value("G")
+
+
+def test_channel_dtype_inference():
+    layer = ExrLayer("test_layer").with_width(2).with_height(2)
+    layer.with_channel("R", np.ones(4, dtype=np.float16))
+    layer.with_channel("G", np.ones(4, dtype=np.float64))
+    layer.with_channel("id", np.arange(4, dtype=np.int64))
+    layer.with_channel("B", np.ones(4, dtype=np.float32), dtype="f16")
+
+    dtypes = [pixels.dtype for pixels in layer.pixels()]
+    assert dtypes == [np.float16, np.float32, np.uint32, np.float16]
+
+    with pytest.raises(ValueError):
+        layer.with_channel("neg", np.array([-1, 0, 1, 2], dtype=np.int32))
+    with pytest.raises(ValueError):
+        layer.with_channel("big", np.full(4, 2**40, dtype=np.uint64))
+    with pytest.raises(ValueError):
+        layer.with_channel("A", np.ones(4, dtype=np.float32), dtype="f64")
+
+
+def test_channel_bytes():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    layer.with_channel("id", np.arange(4, dtype=np.uint32))
+
+    data, dtype, count = layer.channel_bytes("G")
+    assert (dtype, count, len(data)) == ("float32", 4, 16)
+    np.testing.assert_array_equal(np.frombuffer(data, dtype="<f4"), layer.pixels()[1])
+
+    data, dtype, count = layer.astype("float16").channel_bytes("R")
+    assert (dtype, count, len(data)) == ("float16", 4, 8)
+
+    data, dtype, count = layer.channel_bytes("id")
+    assert dtype == "uint32"
+    np.testing.assert_array_equal(np.frombuffer(data, dtype="<u4"), np.arange(4))
+
+    with pytest.raises(KeyError):
+        layer.channel_bytes("Z")
+
+
+def test_channel_math():
+    layer = ExrLayer("test_layer").with_width(2).with_height(2)
+    layer.with_channel("R", np.array([0.5, 1.0, 2.0, 4.0], dtype=np.float16))
+    layer.with_channel("G", np.array([1.0, 2.0, 3.0, 4.0], dtype=np.float32))
+    layer.with_channel("id", np.array([1, 2, 3, 4], dtype=np.uint32))
+
+    layer.scale_channel("R", 2.0).scale_channel("id", 1.5)
+    assert layer.pixels()[0].dtype == np.float16
+    np.testing.assert_array_equal(layer.pixels()[0], [1.0, 2.0, 4.0, 8.0])
+    np.testing.assert_array_equal(layer.pixels()[2], [1, 3, 4, 6])
+
+    layer.add_channels("R", "G", "sum")
+    assert layer.channels() == ["R", "G", "id", "sum"]
+    assert layer.pixels()[3].dtype == np.float16
+    np.testing.assert_array_equal(layer.pixels()[3], [2.0, 4.0, 7.0, 12.0])
+
+    layer.add_channels("G", "R", "G")
+    assert layer.pixels()[1].dtype == np.float32
+    np.testing.assert_array_equal(layer.pixels()[1], [2.0, 4.0, 7.0, 12.0])
+
+    with pytest.raises(KeyError):
+        layer.scale_channel("B", 2.0)
+    with pytest.raises(KeyError):
+        layer.add_channels("R", "B", "sum")
+
+
+def test_interleaved():
+    channels = _create_test_channels()
+    layer = _create_test_layer("test_layer", channels)
+    layer.with_channel("id", np.arange(4, dtype=np.uint32))
+
+    rgb = layer.interleaved(["R", "G", "B"])
+    assert rgb.shape == (2, 2, 3)
+    assert rgb.dtype == np.float32
+    np.testing.assert_array_equal(rgb, np.stack(channels, axis=-1))
+
+    bgr = layer.astype("float16").interleaved(["B", "G", "R", "id"])
+    np.testing.assert_array_equal(bgr[1, 0], [1.0, 0.0, 0.0, 2.0])
+    assert len(layer.pixels_f32()) == 4
+
+    with pytest.raises(KeyError):
+        layer.interleaved(["R", "A"])
+
+
+def test_with_channel_requires_pixels():
+    with pytest.raises(IOError, match="width and height must be set"):
+        ExrLayer("test_layer").with_channel("R", np.empty(0, dtype=np.float32))
+    layer = ExrLayer("test_layer").with_width(2).with_height(2)
+    with pytest.raises(IOError):
+        layer.with_channel("R", np.empty(0, dtype=np.float32))
+    assert layer.channels() == []
+
+
+def test_byte_swapped_pixels_are_rejected():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    swapped = np.arange(4, dtype=np.float32).astype(np.dtype(np.float32).newbyteorder())
+    with pytest.raises(ValueError, match="native byte order"):
+        layer.with_channel("A", swapped)
+    with pytest.raises(ValueError, match="native byte order"):
+        layer.set_channel("R", swapped)
+
+    layer.with_channel("A", swapped.astype(swapped.dtype.newbyteorder("=")))
+    assert all(pixels.dtype.isnative for pixels in layer.pixels())
+
+
+def test_prune_constant_layers():
+    def layer(name, pixels, dtype=np.float32):
+        return ExrLayer(name).with_width(2).with_height(2).with_channel(
+            "Y", np.array(pixels, dtype=dtype)
+        )
+
+    image = ExrImage().with_layers(
+        [
+            layer("beauty", [0.0, 0.5, 1.0, 0.25]),
+            layer("empty", [0.0, 0.0, 0.0, 0.0]).with_channel("A", np.ones(4, np.float32)),
+            layer("noisy", [1.0, 1.0, 1.001, 1.0], np.float16),
+            layer("nan", [np.nan] * 4),
+            layer("ids", [7, 7, 7, 8], np.uint32),
+        ]
+    )
+    assert image.prune_constant_layers() == ["empty", "nan"]
+    assert [layer.name() for layer in image.layers()] == ["beauty", "noisy", "ids"]
+    assert image.prune_constant_layers(tolerance=1) == ["noisy", "ids"]
+    assert image.prune_constant_layers(tolerance=1) == []
+
+
+def test_color_space():
+    with open("tests/fixtures/ACES-2065-1.exr", "rb") as f:
+        image = ExrImage.load_from_buffer(f.read())
+    assert image.color_space() == "ACES"
+    assert image.chromaticities()[0] == pytest.approx((0.7347, 0.2653))
+
+    with open("tests/fixtures/AllHalfValues.exr", "rb") as f:
+        image = ExrImage.load_from_buffer(f.read())
+    assert image.color_space() == "unknown"
+
+    assert match_primaries(known_primaries("AP1")) == "ACEScg"
+    display_p3 = ((0.68, 0.32), (0.265, 0.69), (0.15, 0.06), (0.3127, 0.329))
+    assert match_primaries(display_p3) is None
+    with pytest.raises(ValueError):
+        known_primaries("P3")
+
+
+def test_frame_rate():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    image = _create_test_image([layer], {})
+    assert image.frame_rate() is None
+
+    image.with_frame_rate(23.976)
+    read_image = ExrImage.load_from_buffer(image.save_to_buffer())
+
+    assert read_image.frame_rate() == pytest.approx(24000 / 1001)
+    read_attributes = read_image.layers()[0].attributes()
+    assert read_attributes["frames_per_second"] == "rational:24000/1001"
+
+
+@pytest.mark.parametrize(
+    "fps, rational", [(29.97, "rational:30000/1001"), (24, "rational:24/1")]
+)
+def test_frame_rate_rationals(fps, rational):
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    image = _create_test_image([layer], {})
+    image.with_frame_rate(fps)
+
+    assert image.frame_rate() == pytest.approx(fps, abs=1e-3)
+    assert image.layers()[0].attributes()["frames_per_second"] == rational
+
+
+@pytest.mark.parametrize("fps", [0.0, -24.0, float("nan"), float("inf"), 1e7])
+def test_frame_rate_rejects_invalid_rates(fps):
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    image = _create_test_image([layer], {})
+    with pytest.raises(ValueError, match="Frame rate must be"):
+        image.with_frame_rate(fps)
+
+
+def test_frame_rate_requires_layers():
+    with pytest.raises(ValueError, match="add them before"):
+        ExrImage().with_frame_rate(24)
+
+
+def test_comments():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    image = _create_test_image([layer], {})
+    assert image.comments() is None
+
+    slate = "Show: exrio\r\nShot: 010_0040\nArtist: Zoë\n"
+    image.with_comments(slate)
+    read_image = ExrImage.load_from_buffer(image.save_to_buffer())
+
+    assert read_image.comments() == slate
+    assert read_image.layers()[0].attributes()["comments"] == slate
+
+    with pytest.raises(ValueError):
+        image.with_comments("Artist: \u5c71\u7530")
+    with pytest.raises(IOError):
+        layer.with_attributes({"comments": "\u5c71\u7530"})
+
+
+def test_clear_and_refill_pixels():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+
+    layer.clear_pixels()
+    assert layer.channels() == ["R", "G", "B"]
+    assert all(len(pixels) == 0 for pixels in layer.pixels())
+    with pytest.raises(IOError):
+        layer.assert_valid()
+
+    for channel in layer.channels():
+        layer.set_channel(channel, np.full(4, 0.5, dtype=np.float32))
+    layer.assert_valid()
+
+    with pytest.raises(ValueError, match="needs 2x2 pixels"):
+        layer.set_channel("R", np.zeros(3, dtype=np.float32))
+    with pytest.raises(KeyError):
+        layer.set_channel("A", np.zeros(4, dtype=np.float32))
+
+
+def test_layer_from_schema():
+    layer = ExrLayer.from_schema("beauty", 2, 2, [("R", "f16"), ("Z", "float32")])
+    assert layer.channels() == ["R", "Z"]
+    assert [pixels.dtype for pixels in layer.pixels()] == [np.float16, np.float32]
+
+    layer.set_channel("R", np.ones((2, 2), dtype=np.float16))
+    image = _create_test_image([layer], {})
+    with pytest.raises(IOError, match="Z has not been filled"):
+        image.save_to_buffer()
+
+    with pytest.raises(ValueError, match="declared as float32"):
+        layer.set_channel("Z", np.zeros(4, dtype=np.float16))
+    with pytest.raises(ValueError, match="needs 2x2 pixels"):
+        layer.set_channel("Z", np.zeros(3, dtype=np.float32))
+    layer.set_channel("Z", np.zeros(4, dtype=np.float32))
+    read_layer = ExrImage.load_from_buffer(
+        _create_test_image([layer], {}).save_to_buffer()
+    ).layers()[0]
+    assert read_layer.pixels()[0].dtype == np.float16
+
+    with pytest.raises(ValueError):
+        ExrLayer.from_schema("beauty", 2, 2, [("R", "f16"), ("R", "f32")])
+
+
+def test_alpha():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    assert layer.alpha() is None
+
+    layer.with_channel("alpha", np.full(4, 0.25, dtype=np.float32))
+    layer.with_channel("a", np.arange(4, dtype=np.float16))
+    alpha = layer.alpha()
+    assert alpha.shape == (2, 2)
+    assert alpha.dtype == np.float16
+    np.testing.assert_array_equal(alpha, [[0, 1], [2, 3]])
+
+
+def test_clear_layers():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    image = _create_test_image([layer], {"test_attr": "test_value"})
+
+    image.clear_layers()
+    assert image.layers() == []
+    assert image.attributes()["test_attr"] == "test_value"
+
+
+def test_estimate_size():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    image = _create_test_image([layer], {})
+
+    uncompressed = image.estimate_size(compression="none")
+    assert uncompressed > 3 * 4 * 4
+    assert image.estimate_size(compression="zip") <= uncompressed
+    with pytest.raises(ValueError):
+        image.estimate_size(compression="jpeg")
+
+
+def test_memory_bytes_and_pixel_count():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    image = _create_test_image([layer], {})
+    assert image.pixel_count() == 4
+    assert image.memory_bytes() == 3 * 4 * 4
+
+    half = _create_test_image([layer.astype("float16"), layer], {})
+    assert half.pixel_count() == 8
+    assert half.memory_bytes() == 3 * 4 * 2 + 3 * 4 * 4
+
+
+def test_channels_listing():
+    layer = _create_test_layer("beauty", _create_test_channels())
+    image = _create_test_image([layer, layer.astype("float16").with_name("half")], {})
+
+    assert image.channels() == [
+        ("beauty", "R", "float32"),
+        ("beauty", "G", "float32"),
+        ("beauty", "B", "float32"),
+        ("half", "R", "float16"),
+        ("half", "G", "float16"),
+        ("half", "B", "float16"),
+    ]
+    assert ExrImage().channels() == []
+
+
+def test_non_contiguous_channel_pixels():
+    transposed = np.arange(6, dtype=np.float32).reshape(3, 2).T
+    strided = np.arange(12, dtype=np.float16)[::2]
+    assert not transposed.flags.c_contiguous and not strided.flags.c_contiguous
+
+    layer = ExrLayer("strided").with_width(3).with_height(2)
+    layer.with_channel("R", transposed).with_channel("G", strided)
+
+    read_layer = ExrImage.load_from_buffer(
+        _create_test_image([layer], {}).save_to_buffer()
+    ).layers()[0]
+    pixels = dict(zip(read_layer.channels(), read_layer.pixels()))
+    np.testing.assert_array_equal(pixels["R"], transposed.flatten())
+    np.testing.assert_array_equal(pixels["G"], strided)
+
+
+def test_save_to_path_matches_buffer(tmp_path):
+    layers = [
+        _create_test_layer("beauty", _create_test_channels()),
+        _create_test_layer("half", _create_test_channels()).astype("float16"),
+    ]
+    image = _create_test_image(layers, {})
+
+    path = tmp_path / "image.exr"
+    image.save_to_path(path, long_names=True, flip_y=True)
+    assert path.read_bytes() == image.save_to_buffer(long_names=True, flip_y=True)
+
+
+def test_save_memory_limit(tmp_path):
+    image = _create_test_image([_create_test_layer("beauty", _create_test_channels())], {})
+
+    with pytest.raises(MemoryError):
+        image.save_to_buffer(memory_limit=16)
+    with pytest.raises(MemoryError):
+        image.save_to_path(tmp_path / "image.exr", memory_limit=16)
+    image.save_to_path(tmp_path / "image.exr", memory_limit=48)
+
+
+def test_smart_compression(tmp_path):
+    column = np.zeros((64, 1), dtype=np.float32)
+    color = ExrLayer.from_grayscale("color", column, channel="Y")
+    depth = ExrLayer.from_grayscale("depth", column, channel="Z")
+    image = _create_test_image([color, depth], {})
+
+    # RLE stores one scanline per chunk and ZIP sixteen, so the counts reveal each codec.
+    path = tmp_path / "image.exr"
+    image.save_to_path(path, compression="rle")
+    assert ExrImage.chunk_count(path) == 64 + 64
+
+    image.save_to_path(path, compression="rle", smart_compression=True)
+    assert ExrImage.chunk_count(path) == 64 + 4
+
+    image.save_to_path(path, compression="rle", smart_compression=True, data_channels=["Y"])
+    assert ExrImage.chunk_count(path) == 4 + 64
+
+    with pytest.raises(ValueError):
+        image.save_to_path(path, compression="jpeg")
+
+
+def test_encoding_presets(tmp_path):
+    image = _create_test_image(
+        [ExrLayer.from_grayscale("depth", np.zeros((64, 1), dtype=np.float32))], {}
+    )
+
+    path = tmp_path / "image.exr"
+    expected_chunks = {
+        "small_lossless": 4,
+        "fast_lossless": 1,
+        "small_fast_lossless": 1,
+        "uncompressed": 64,
+    }
+    for encoding, chunks in expected_chunks.items():
+        image.save_to_path(path, encoding=encoding)
+        assert ExrImage.chunk_count(path) == chunks
+        read_layer = ExrImage.load_from_buffer(path.read_bytes()).layers()[0]
+        np.testing.assert_array_equal(read_layer.pixels()[0], np.zeros(64))
+
+    image.save_to_path(path, encoding="small_lossless", compression="rle")
+    assert ExrImage.chunk_count(path) == 64
+    with pytest.raises(ValueError):
+        image.save_to_path(path, encoding="tiny")
+
+
+def test_fidelity(tmp_path):
+    def header(image):
+        layers = [
+            (
+                layer.name(),
+                layer.compression(),
+                layer.tile_size(),
+                layer.line_order(),
+                layer.data_window(),
+            )
+            for layer in image.layers()
+        ]
+        return layers, image.channels(), image.attributes()
+
+    with open("tests/fixtures/ACES-2065-1.exr", "rb") as f:
+        original = ExrImage.load_from_buffer(f.read())
+    path = tmp_path / "image.exr"
+    original.save_to_path(path)
+    assert header(ExrImage.load_from_buffer(path.read_bytes())) == header(original)
+
+    original.save_to_path(path, encoding="small_fast_lossless")
+    tiled = ExrImage.load_from_buffer(path.read_bytes())
+    tiled.save_to_path(path)
+    read_image = ExrImage.load_from_buffer(path.read_bytes())
+    assert header(read_image) == header(tiled)
+    assert read_image.layers()[0].compression() == "piz"
+    assert read_image.layers()[0].tile_size() == (256, 256)
+
+
+def test_grayscale_layer():
+    depth = np.arange(12, dtype=np.float32).reshape(3, 4)
+    layer = ExrLayer.from_grayscale("depth", depth, channel="Z")
+
+    assert layer.channels() == ["Z"]
+    assert (layer.width(), layer.height()) == (4, 3)
+
+    image = _create_test_image([layer], {})
+    read_layer = ExrImage.load_from_buffer(image.save_to_buffer()).layers()[0]
+    np.testing.assert_array_equal(read_layer.as_grayscale(), depth)
+
+    with pytest.raises(ValueError):
+        ExrLayer.from_grayscale("depth", depth.reshape(-1))
+
+
+def test_from_chw():
+    chw = np.arange(2 * 3 * 4, dtype=np.float32).reshape(2, 3, 4)
+    layer = ExrLayer.from_chw("beauty", chw, ["R", "G"])
+    assert (layer.width(), layer.height()) == (4, 3)
+    assert layer.channels() == ["R", "G"]
+    np.testing.assert_array_equal(layer.pixels()[1], chw[1].flatten())
+
+    with pytest.raises(ValueError):
+        ExrLayer.from_chw("beauty", chw, ["R", "G", "B"])
+    with pytest.raises(ValueError):
+        ExrLayer.from_chw("beauty", chw[0], ["R"])
+
+
+def test_long_names_flag():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    image = _create_test_image([layer], {})
+
+    assert image.save_to_buffer()[5] & 0x04 == 0
+    buffer = image.save_to_buffer(long_names=True)
+    assert buffer[5] & 0x04 == 0x04
+    assert ExrImage.load_from_buffer(buffer).layers()[0].name() == "test_layer"
+
+
+def test_version_flags():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    image = _create_test_image([layer], {})
+    assert image.version_flags() is None
+
+    flags = ExrImage.load_from_buffer(image.save_to_buffer(long_names=True)).version_flags()
+    assert flags == {
+        "version": 2,
+        "tiled": False,
+        "long_names": True,
+        "deep": False,
+        "multipart": False,
+    }
+
+    with open("tests/fixtures/Mixed.exr", "rb") as f:
+        flags = ExrImage.load_from_buffer(f.read()).version_flags()
+    assert (flags["multipart"], flags["tiled"], flags["long_names"]) == (True, False, False)
+    assert ExrImage.load_mip("tests/fixtures/Mipmap.exr", 1).version_flags()["tiled"]
+
+
+def test_set_attributes():
+    with open("tests/fixtures/sRGB.exr", "rb") as f:
+        image = ExrImage.load_from_buffer(f.read())
+    chromaticities = image.chromaticities()
+    assert chromaticities is not None
+    image.with_image_state("complete")
+
+    image.set_attributes({"note": "first pass", "pixelAspectRatio": 2.0})
+    read_image = ExrImage.load_from_buffer(image.save_to_buffer())
+    assert read_image.chromaticities()[0] == pytest.approx(chromaticities[0])
+    assert read_image.image_state() == "complete"
+    assert read_image.attributes()["note"] == "first pass"
+    assert read_image.attributes()["pixel_aspect_ratio"] == 2.0
+
+    image.set_attributes({"note": "second pass"}, merge=False)
+    assert image.chromaticities() is None
+    assert image.image_state() is None
+    assert image.attributes()["note"] == "second pass"
+    assert image.attributes()["pixel_aspect_ratio"] == 1.0
+
+
+def test_assemble_tiles():
+    def bucket(x, y, width, height, value):
+        layer = ExrLayer("beauty").with_width(width).with_height(height)
+        layer.with_channel("R", np.full(width * height, value, dtype=np.float32))
+        return layer.with_position(x, y)
+
+    left = bucket(-2, 0, 6, 4, 1.0)
+    right = bucket(4, 0, 4, 4, 2.0)
+    image = _create_test_image([left, right], {})
+
+    mosaic = image.assemble_tiles((0, 0, 8, 4), policy="error")
+    assert mosaic.name() == "beauty"
+    assert mosaic.data_window() == Box2i(0, 0, 8, 4)
+    rows = mosaic.pixels()[0].reshape(4, 8)
+    np.testing.assert_array_equal(rows[:, :4], 1.0)
+    np.testing.assert_array_equal(rows[:, 4:], 2.0)
+
+    depth = bucket(2, 0, 4, 2, 3.0)
+    depth.with_channel("Z", np.full(8, 5.0, dtype=np.float16))
+    image = _create_test_image([left, right, depth], {})
+    mosaic = image.assemble_tiles((0, 0, 8, 4))
+    assert mosaic.channels() == ["R", "Z"]
+    rows, z = (pixels.reshape(4, 8) for pixels in mosaic.pixels())
+    np.testing.assert_array_equal(rows[:2, 2:6], 3.0)
+    np.testing.assert_array_equal(rows[2:, 2:4], 1.0)
+    assert z.dtype == np.float16 and z.sum() == 40
+
+    with pytest.raises(ValueError, match="overlap"):
+        image.assemble_tiles((0, 0, 8, 4), policy="error")
+    with pytest.raises(ValueError, match="covers"):
+        _create_test_image([left], {}).assemble_tiles((0, 0, 8, 4), policy="error")
+    with pytest.raises(ValueError):
+        image.assemble_tiles((0, 0, 8, 4), policy="first_wins")
+
+
+def test_image_state():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    image = _create_test_image([layer], {})
+    assert image.image_state() is None
+
+    image.with_image_state("complete")
+    read_image = ExrImage.load_from_buffer(image.save_to_buffer())
+
+    assert read_image.image_state() == "complete"
+    assert read_image.attributes()["imageState"] == "complete"
+
+    image.with_image_state("fertig à 90%")
+    assert image.image_state() == "fertig à 90%"
+    with pytest.raises(ValueError, match="Latin-1"):
+        image.with_image_state("完成")
+
+
+def test_exr_dtype():
+    assert exr_dtype(np.zeros(4, dtype=np.float16)) == "float16"
+    assert exr_dtype(np.zeros(4, dtype=np.float64)) == "float32"
+    assert exr_dtype(np.zeros(4, dtype=np.int64)) == "uint32"
+    assert exr_dtype(np.zeros(4, dtype=np.float32), "u32") == "uint32"
+
+    with pytest.raises(ValueError, match="between 0 and 4294967295"):
+        exr_dtype(-np.ones(4, dtype=np.int32))
+    with pytest.raises(ValueError, match="native byte order"):
+        exr_dtype(np.zeros(4, dtype=np.float32).astype(">f4"))
+    with pytest.raises(TypeError):
+        exr_dtype(np.zeros(4, dtype=bool))
+
+
+def test_content_hash():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    other = _create_test_layer("other_layer", _create_test_channels())
+    assert layer.content_hash() == other.content_hash()
+
+    other.set_channel("R", np.zeros(4, dtype=np.float32))
+    assert layer.content_hash() != other.content_hash()
+
+
+def test_content_hash_includes_dtype():
+    layers = []
+    for dtype in (np.float32, np.uint32):
+        layer = ExrLayer("zeros")
+        layer.with_width(2)
+        layer.with_height(2)
+        layer.with_channel("Y", np.zeros(4, dtype=dtype))
+        layers.append(layer)
+
+    assert layers[0].content_hash() != layers[1].content_hash()
+
+
+def test_channel_sampling_defaults_to_full_resolution():
+    with open("tests/fixtures/sRGB.exr", "rb") as f:
+        layer = ExrImage.load_from_buffer(f.read()).layers()[0]
+
+    for channel in layer.channels():
+        assert layer.channel_sampling(channel) == (1, 1)
+    with pytest.raises(KeyError):
+        layer.channel_sampling("Q")
+
+
+def test_attribute_types_are_stable():
+    metadata = {"frame": 5, "gain": 5.0, "exposure": 2, "note": "take 3", "offset": [1, 2]}
+
+    read_layers = []
+    for _ in range(2):
+        layer = _create_test_layer("test_layer", _create_test_channels())
+        layer.with_attributes(metadata)
+        buffer = _create_test_image([layer], {}).save_to_buffer()
+        read_layers.append(ExrImage.load_from_buffer(buffer).layers()[0])
+
+    attributes = read_layers[0].attributes()
+    assert attributes == read_layers[1].attributes()
+    assert type(attributes["frame"]) is int
+    assert type(attributes["gain"]) is float
+    assert type(attributes["exposure"]) is float  # Standard attribute stored as f32
+    assert attributes["offset"] == [1, 2]
+
+
+def test_canonical_standard_attribute_names():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    layer.with_attributes({"whiteLuminance": 100.0, "owner": "studio"})
+    image = _create_test_image([layer], {})
+    image.with_attributes({"pixelAspectRatio": 2.0})
+
+    read_image = ExrImage.load_from_buffer(image.save_to_buffer())
+    read_attributes = read_image.layers()[0].attributes()
+    assert read_image.attributes()["pixel_aspect_ratio"] == 2.0
+    assert read_attributes["white_luminance"] == 100.0
+    assert read_attributes["owner"] == "studio"
+    assert "whiteLuminance" not in read_attributes
+    assert "pixelAspectRatio" not in read_image.attributes()
+
+
+def test_pixel_aspect_ratio_roundtrip():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    image = _create_test_image([layer], {"pixel_aspect_ratio": 2.0})
+
+    buffer = image.save_to_buffer()
+    header_value = b"pixelAspectRatio\0float\0" + struct.pack("<if", 4, 2.0)
+    assert header_value in buffer
+
+    read_image = ExrImage.load_from_buffer(buffer)
+    assert read_image.attributes()["pixel_aspect_ratio"] == 2.0
+    read_image.with_attributes({"pixel_aspect_ratio": 0.5})
+    buffer = read_image.save_to_buffer()
+    assert ExrImage.load_from_buffer(buffer).attributes()["pixel_aspect_ratio"] == 0.5
+
+    with pytest.raises(IOError):
+        image.with_attributes({"pixel_aspect_ratio": "2.0"})
+
+
+def test_custom_integer_bounds_attributes():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    layer.with_attributes(
+        {
+            "originalDataWindow": (-8, -4, 80, 60),
+            "denoiseWindow": [-2, -2, 20, 20],
+            "sampleCount": 64,
+        }
+    )
+    image = _create_test_image([layer], {})
+
+    read_attributes = ExrImage.load_from_buffer(image.save_to_buffer()).layers()[0].attributes()
+    # Standard attributes are exposed under their snake_case name.
+    assert read_attributes["original_data_window"].to_tuple() == (-8, -4, 80, 60)
+    assert read_attributes["denoiseWindow"].to_tuple() == (-2, -2, 20, 20)
+    assert read_attributes["sampleCount"] == 64
+
+
+def test_data_window():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    layer.with_attributes({"dataWindow": (-3, 5, 2, 2)})
+    assert layer.data_window().to_tuple() == (-3, 5, 2, 2)
+    assert "dataWindow" not in layer.attributes()
+
+    other_layer = _create_test_layer("other_layer", _create_test_channels())
+    other_layer.with_data_window(4, 0, 2, 2)
+    image = _create_test_image([layer, other_layer], {})
+    assert image.data_window() == Box2i(-3, 0, 9, 7)
+    assert (image.data_window().x, image.data_window().width) == (-3, 9)
+
+    x, y, width, height = image.data_window()
+    assert (x, y, width, height) == (-3, 0, 9, 7)
+    assert image.data_window() == (-3, 0, 9, 7)
+    assert len(image.data_window()) == 4
+    assert image.data_window()[-1] == 7
+    assert hash(image.data_window()) == hash((-3, 0, 9, 7))
+    with pytest.raises(IndexError):
+        image.data_window()[4]
+
+    mosaic = image.assemble_tiles(image.data_window())
+    assert mosaic.data_window() == image.data_window()
+
+    read_image = ExrImage.load_from_buffer(
+        image.save_to_buffer(display_window=image.data_window())
+    )
+    assert read_image.layers()[0].data_window().to_tuple() == (-3, 5, 2, 2)
+    assert read_image.layers()[1].data_window().to_tuple() == (4, 0, 2, 2)
+
+    with pytest.raises(ValueError):
+        layer.with_data_window(0, 0, 3, 3)
+
+
+def test_screen_window():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    assert layer.screen_window() == {"center": (0.0, 0.0), "width": 1.0}
+
+    layer.with_attributes({"screenWindowCenter": [0.5, -0.25]})
+    assert layer.screen_window()["center"] == (0.5, -0.25)
+
+    layer.with_screen_window((0.125, 0.25), 2.0)
+    assert "screenWindowCenter" not in layer.attributes()
+    read_layer = ExrImage.load_from_buffer(
+        _create_test_image([layer], {}).save_to_buffer()
+    ).layers()[0]
+    assert read_layer.screen_window() == {"center": (0.125, 0.25), "width": 2.0}
+
+    with pytest.raises(ValueError):
+        layer.with_screen_window((0.0, 0.0), 0.0)
+
+
+def test_horizontal_density():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    assert layer.horizontal_density() is None
+
+    layer.with_attributes({"xDensity": 72.0})
+    assert layer.horizontal_density() == 72.0
+
+    layer.with_horizontal_density(118.0, "cm")
+    assert "xDensity" not in layer.attributes()
+    read_layer = ExrImage.load_from_buffer(
+        _create_test_image([layer], {}).save_to_buffer()
+    ).layers()[0]
+    assert read_layer.horizontal_density() == pytest.approx(299.72)
+    assert read_layer.horizontal_density("cm") == pytest.approx(118.0)
+
+    with pytest.raises(ValueError):
+        layer.with_horizontal_density(300.0, "mm")
+    with pytest.raises(ValueError):
+        layer.with_horizontal_density(0.0)
+
+
+def test_rename_layer():
+    layer = _create_test_layer("test_layer", _create_test_channels())
+    layer.with_name("beauty")
+    buffer = _create_test_image([layer], {}).save_to_buffer()
+
+    read_layer = ExrImage.load_from_buffer(buffer).layers()[0]
+    assert read_layer.name() == "beauty"
+
+    read_layer.with_name("renamed")
+    buffer = _create_test_image([read_layer], {}).save_to_buffer()
+    assert ExrImage.load_from_buffer(buffer).layers()[0].name() == "renamed"
+
+
+def test_attribute_keys():
+    with open("tests/fixtures/ACES-2065-1.exr", "rb") as f:
+        image = ExrImage.load_from_buffer(f.read())
+
+    assert image.attribute_keys() == sorted(image.attributes().keys())
+    assert "chromaticities" in image.attribute_keys()
+
+    layer = image.layers()[0]
+    assert layer.attribute_keys() == sorted(layer.attributes().keys())
+
+
+def test_fluent_chaining():
+    r, g, b = _create_test_channels()
+    layer = (
+        ExrLayer("chained")
+        .with_width(2)
+        .with_height(2)
+        .with_channel("R", r.flatten())
+        .with_channel("G", g.flatten())
+    )
+    other = _create_test_layer("other", (r, g, b))
+
+    image = ExrImage().with_layers([layer, other]).with_attributes({})
+    assert [layer.name() for layer in image.layers()] == ["chained", "other"]
+
+    read_image = ExrImage.load_from_buffer(image.save_to_buffer())
+    assert sorted(read_image.layers()[0].channels()) == ["G", "R"]
+
+
+def test_duplicate_layer_names_are_suffixed_on_load():
+    channels = _create_test_channels()
+    layers = [
+        _create_test_layer("beauty", channels),
+        _create_test_layer("beaut1", channels),
+        _create_test_layer("beaut2", channels),
+    ]
+    buffer = _create_test_image(layers, {}).save_to_buffer()
+    # The writer rejects duplicate names, so patch them into the header directly.
+    buffer = buffer.replace(b"beaut1", b"beauty").replace(b"beaut2", b"beauty")
+
+    image = ExrImage.load_from_buffer(buffer)
+    names = [layer.name() for layer in image.layers()]
+    assert names == ["beauty", "beauty.1", "beauty.2"]
+    assert image.warnings() == [
+        "Renamed duplicate layer beauty to beauty.1",
+        "Renamed duplicate layer beauty to beauty.2",
+    ]
+
+    resaved = ExrImage.load_from_buffer(image.save_to_buffer())
+    assert [layer.name() for layer in resaved.layers()] == names
+
+
+def test_layer_lookup_with_duplicate_names():
+    channels = _create_test_channels()
+    layers = [
+        _create_test_layer("beauty", channels),
+        _create_test_layer("diffuse", channels),
+        _create_test_layer("beaut1", channels).astype("float16"),
+    ]
+    buffer = _create_test_image(layers, {}).save_to_buffer()
+    buffer = buffer.replace(b"beaut1", b"beauty")
+
+    image = ExrImage.load_from_buffer(buffer)
+    assert len(image.layers()) == 3
+    assert image.layer("beauty").pixels()[0].dtype == np.float32
+    assert image.layer("beauty.1").file_name() == "beauty"
+    assert image.layer("beauty").file_name() is None
+
+    named = image.layers_named("beauty")
+    assert [layer.name() for layer in named] == ["beauty", "beauty.1"]
+    assert named[1].pixels()[0].dtype == np.float16
+    assert image.layers_named("specular") == []
+    with pytest.raises(KeyError):
+        image.layer("specular")
+
+    assert named[1].with_name("spec").file_name() is None
+
+
+def test_save_streaming_to_path(tmp_path):
+    r, g, b = _create_test_channels()
+    filled = [
+        _create_test_layer("beauty", (r, g, b)),
+        _create_test_layer("diffuse", (b, g, r)).astype("float16"),
+    ]
+
+    image = ExrImage()
+    for layer in filled:
+        # The templates declare each channel's dtype without holding any pixels.
+        schema = [
+            (channel, pixels.dtype.name)
+            for channel, pixels in zip(layer.channels(), layer.pixels())
+        ]
+        image.with_layer(ExrLayer.from_schema(layer.name(), 2, 2, schema))
+
+    requested = []
+
+    def fill_layer(index):
+        requested.append(index)
+        return filled[index]
+
+    path = tmp_path / "streamed.exr"
+    image.save_streaming_to_path(path, fill_layer)
+    assert requested == [0, 1]
+
+    read_layers = ExrImage.load_from_buffer(path.read_bytes()).layers()
+    assert [layer.name() for layer in read_layers] == ["beauty", "diffuse"]
+    for read_layer, layer in zip(read_layers, filled):
+        for channel, pixels in zip(layer.channels(), layer.pixels()):
+            read_pixels = read_layer.pixels()[read_layer.channels().index(channel)]
+            np.testing.assert_array_equal(read_pixels, pixels)
+
+    with pytest.raises(ValueError):
+        image.save_streaming_to_path(tmp_path / "bad.exr", lambda index: filled[0])
+
+
+def test_failed_save_keeps_existing_file(tmp_path):
+    image = _create_test_image([_create_test_layer("test_layer", _create_test_channels())], {})
+    path = tmp_path / "image.exr"
+    image.save_to_path(path)
+    original = path.read_bytes()
+
+    with pytest.raises(MemoryError):
+        image.save_to_path(path, memory_limit=1)
+    unfilled = ExrLayer.from_schema("beauty", 2, 2, [("R", "f32")])
+    with pytest.raises(IOError):
+        _create_test_image([unfilled], {}).save_to_path(path)
+
+    def fail_render(index):
+        raise RuntimeError("render failed")
+
+    with pytest.raises(RuntimeError):
+        image.save_streaming_to_path(path, fail_render)
+
+    assert path.read_bytes() == original
+    assert [entry.name for entry in tmp_path.iterdir()] == ["image.exr"]
+
+
+def test_save_to_file(tmp_path):
+    image = _create_test_image([_create_test_layer("test_layer", _create_test_channels())], {})
+    expected = image.save_to_buffer()
+
+    buffer = io.BytesIO()
+    buffer.write(b"prefix")
+    image.save_to_file(buffer)
+    assert buffer.getvalue()[len(b"prefix") :] == expected
+
+    with open(tmp_path / "image.exr", "wb") as f:
+        image.save_to_file(f, long_names=True)
+    assert (tmp_path / "image.exr").read_bytes() == image.save_to_buffer(long_names=True)
+
+    class Stream:
+        def __init__(self):
+            self.chunks = []
+
+        def write(self, data):
+            self.chunks.append(bytes(data))
+
+    stream = Stream()
+    image.save_to_file(stream)
+    assert b"".join(stream.chunks) == expected
+
+    class Failing(io.BytesIO):
+        def write(self, data):
+            raise ValueError("disk full")
+
+    with pytest.raises(IOError, match="disk full"):
+        image.save_to_file(Failing())
+
+
+def test_register_text_enum():
+    class Stage(Enum):
+        LIGHTING = "lighting"
+        COMP = "comp"
+
+    register_text_enum("stage", {stage.value: stage for stage in Stage})
+    try:
+        layer = _create_test_layer("test_layer", _create_test_channels())
+        layer.with_attributes({"stage": Stage.COMP, "note": "comp"})
+        buffer = _create_test_image([layer], {}).save_to_buffer()
+
+        attributes = ExrImage.load_from_buffer(buffer).layers()[0].attributes()
+        assert attributes["stage"] is Stage.COMP
+        assert attributes["note"] == "comp"
+    finally:
+        register_text_enum("stage", {})
+
+    attributes = ExrImage.load_from_buffer(buffer).layers()[0].attributes()
+    assert attributes["stage"] == "comp"
+
+
+def test_default_chromaticities():
+    assert default_chromaticities()[0] == pytest.approx((0.64, 0.33))
+
+    set_default_chromaticities("AP1")
+    try:
+        assert default_chromaticities()[0] == pytest.approx((0.713, 0.293))
+        set_default_chromaticities("aces")
+        assert default_chromaticities()[2] == pytest.approx((0.0001, -0.077))
+    finally:
+        set_default_chromaticities("Rec.709")
+
+    with pytest.raises(ValueError):
+        set_default_chromaticities("P3")
+
+
+def test_load_tensor():
+    path = "tests/fixtures/Mipmap.exr"
+    layer = ExrImage.load_mip(path, 0).layers()[0]
+    shape = (layer.height(), layer.width())
+
+    tensor, names = ExrImage.load_tensor(path)
+    assert names == ["B", "G", "R"]
+    assert (tensor.shape, tensor.dtype) == ((8, 16, 3), np.float32)
+    for index, pixels in enumerate(layer.pixels()):
+        np.testing.assert_array_equal(tensor[..., index], pixels.reshape(shape))
+
+    tensor, names = ExrImage.load_tensor(path, 0, ["R"])
+    assert tensor.shape == (8, 16, 1)
+    np.testing.assert_array_equal(tensor[..., 0], layer.pixels()[2].reshape(shape))
+
+    with pytest.raises(ValueError):
+        ExrImage.load_tensor(path, channels=[])
+    with pytest.raises(KeyError):
+        ExrImage.load_tensor(path, channels=["A"])
@@ -1,9 +1,26 @@
+import gzip
+import sys
 import tempfile
+import warnings
+from dataclasses import replace
 from typing import Any, Optional
 
 import numpy as np
-
-from exrio.image import Colorspace, ExrChannel, ExrImage, ExrLayer, load
+import pytest
+
+from exrio.image import (
+    PRIMARY_CHROMATICITIES,
+    Chromaticities,
+    Colorspace,
+    ExrChannel,
+    ExrImage,
+    ExrLayer,
+    compare,
+    from_imageio,
+    load,
+    set_default_chromaticities,
+    to_imageio,
+)
 
 
 def _create_image(
@@ -154,3 +171,763 @@ def test_infer_LinearRGB_from_exrio_attribute():
     buffer = image.to_buffer()
     image_out = load(buffer)
     assert image_out.inferred_colorspace == Colorspace.LinearRGB
+
+
+def test_extend_layers():
+    image = ExrImage.from_pixels(
+        np.zeros((32, 32, 3), dtype=np.float32), layer_names=["beauty"]
+    )
+    other = ExrImage.from_pixels(
+        np.ones((2, 32, 32, 1), dtype=np.float32), layer_names=["depth", "beauty"]
+    )
+
+    with pytest.raises(ValueError):
+        image.extend(other)
+
+    image.extend(other, namespace_collisions=True)
+    assert [layer.name for layer in image.layers] == ["beauty", "depth", "beauty.1"]
+
+    mismatched = ExrImage.from_pixels(
+        np.zeros((16, 16, 3), dtype=np.float32), layer_names=["small"]
+    )
+    with pytest.raises(ValueError):
+        image.extend(mismatched)
+
+    image.extend(mismatched, allow_mismatched_dimensions=True)
+    assert image.layers[-1].name == "small"
+
+
+def test_load_normalize_names():
+    image = _create_image(np.zeros((16, 16), dtype=np.float32))
+    image.layers[0].channels[0].name = "alpha"
+    buffer = image.to_buffer()
+
+    rt_image = load(buffer, normalize_names=True)
+    assert rt_image.layers[0].channels[0].name == "A"
+    assert rt_image.layers[0].raw_channel_names() == ["alpha"]
+
+    rt_image = load(buffer, normalize_names=True, channel_aliases={"alpha": "mask"})
+    assert rt_image.layers[0].channels[0].name == "mask"
+
+    rt_image = load(buffer)
+    assert rt_image.layers[0].channels[0].name == "alpha"
+
+
+def test_force_dtype():
+    image = _create_image(np.full((16, 16), 2.75, dtype=np.float32))
+
+    rt_image = load(image.to_buffer(force_dtype="f16"))
+    assert rt_image.layers[0].channels[0].pixels.dtype == np.float16
+
+    rt_image = load(image.to_buffer(force_dtype="u32"))
+    assert rt_image.layers[0].channels[0].pixels.dtype == np.uint32
+    assert rt_image.layers[0].channels[0].pixels[0, 0] == 2
+
+    with pytest.raises(ValueError):
+        image.to_buffer(force_dtype="f64")
+
+
+def test_channel_dtypes():
+    pixels = np.full((16, 16), 2.75, dtype=np.float32)
+    channels = [
+        ExrChannel(name=name, width=16, height=16, pixels=pixels)
+        for name in ["R", "depth", "id"]
+    ]
+    layer = ExrLayer(name="testl", width=16, height=16, channels=channels)
+    image = ExrImage(layers=[layer], attributes={})
+
+    buffer = image.to_buffer(force_dtype="f16", channel_dtypes={"depth": "f32", "id": "u32"})
+    dtypes = {channel.name: channel.pixels.dtype for channel in load(buffer).layers[0].channels}
+    assert dtypes == {"R": np.float16, "depth": np.float32, "id": np.uint32}
+
+    buffer = image.to_buffer(channel_dtypes={"R": "f16"})
+    dtypes = {channel.name: channel.pixels.dtype for channel in load(buffer).layers[0].channels}
+    assert dtypes == {"R": np.float16, "depth": np.float32, "id": np.float32}
+
+    with pytest.raises(ValueError):
+        image.to_buffer(channel_dtypes={"depth": "f64"})
+
+
+def test_pixel_aspect_ratio_override():
+    image = _create_image(np.ones((16, 16), dtype=np.float32))
+
+    rt_image = load(image.to_buffer(pixel_aspect_ratio=2.0))
+    assert rt_image.attributes["pixel_aspect_ratio"] == 2.0
+    assert "pixel_aspect_ratio" not in image.attributes
+
+    rt_image = load(image.to_buffer())
+    assert rt_image.attributes["pixel_aspect_ratio"] == 1.0
+
+
+def test_load_mmap():
+    path = "tests/fixtures/AllHalfValues.exr"
+    image = ExrImage.load_mmap(path)
+    expected = load(path)
+
+    assert image.layers[0].width == expected.layers[0].width
+    for channel, expected_channel in zip(image.layers[0].channels, expected.layers[0].channels):
+        assert channel.name == expected_channel.name
+        np.testing.assert_array_equal(channel.pixels, expected_channel.pixels)
+
+
+def test_thumbnail():
+    thumbnail = ExrImage.thumbnail("tests/fixtures/AllHalfValues.exr", 64)
+    assert thumbnail.shape == (64, 64, 3)
+    assert thumbnail.dtype == np.uint8
+
+    # 64x50 data window, scaled to fit 32 pixels on the long side.
+    thumbnail = ExrImage.thumbnail("tests/fixtures/ACES-2065-1.exr", 32)
+    assert thumbnail.shape == (25, 32, 3)
+
+    image = _create_image(np.full((40, 20), 0.5, dtype=np.float32))
+    with tempfile.NamedTemporaryFile(suffix=".exr") as f:
+        image.to_path(f.name)
+        thumbnail = ExrImage.thumbnail(f.name, 10)
+    # A single channel is repeated across R, G, and B.
+    assert thumbnail.shape == (10, 5, 3)
+    assert np.all(thumbnail == 128)
+
+
+def test_chunk_count():
+    image = load("tests/fixtures/AllHalfValues.exr")
+    assert ExrImage.chunk_count("tests/fixtures/AllHalfValues.exr") > 0
+    assert ExrImage.part_count("tests/fixtures/AllHalfValues.exr") == 1
+
+    with tempfile.NamedTemporaryFile(suffix=".exr") as f:
+        image.to_path(f.name)
+        # PIZ compression stores 32 scanlines per block.
+        assert ExrImage.chunk_count(f.name) == 256 // 32
+
+        # PIZ tiles are 256x256, so the 256x256 image is a single tile.
+        image.to_path(f.name, encoding="small_fast_lossless")
+        assert ExrImage.chunk_count(f.name) == 1
+        with pytest.deprecated_call():
+            assert ExrImage.block_count(f.name) == 1
+
+    # 16x8 pixels in 8x8 tiles: two at full resolution and four more in the smaller levels.
+    assert ExrImage.chunk_count("tests/fixtures/Mipmap.exr") == 2
+
+
+def test_roundtrip_negative_layer_position():
+    image = _create_image(np.ones((32, 32), dtype=np.float32))
+    image.layers[0].position = (-16, -16)
+
+    buffer = image.to_buffer(display_window=(0, 0, 16, 16))
+    rt_image = load(buffer)
+
+    assert rt_image.layers[0].position == (-16, -16)
+    assert rt_image.attributes["display_window"].to_tuple() == (0, 0, 16, 16)
+
+
+def test_load_first_layer():
+    input_pixels = np.random.rand(2, 32, 32, 1).astype(np.float32)
+    image = ExrImage.from_pixels(input_pixels, layer_names=["first", "second"])
+
+    with tempfile.NamedTemporaryFile(suffix=".exr") as f:
+        image.to_path(f.name)
+        layer = ExrImage.load_first_layer(f.name)
+
+    assert layer.name == "first"
+    np.testing.assert_allclose(layer.channels[0].pixels, input_pixels[0, :, :, 0])
+
+
+def test_load_part():
+    input_pixels = np.random.rand(3, 16, 8, 1).astype(np.float32)
+    image = ExrImage.from_pixels(input_pixels, layer_names=["a", "b", "c"])
+
+    with tempfile.NamedTemporaryFile(suffix=".exr") as f:
+        image.to_path(f.name)
+        layer = ExrImage.load_part(f.name, 2)
+        with pytest.raises(IndexError):
+            ExrImage.load_part(f.name, 3)
+
+    assert (layer.name, layer.width, layer.height) == ("c", 8, 16)
+    np.testing.assert_array_equal(layer.channels[0].pixels, input_pixels[2, :, :, 0])
+
+
+def test_to_path_clamp():
+    pixels = np.array([[-1.0, 0.5], [2.0, 80000.0]], dtype=np.float32)
+    image = _create_image(pixels)
+
+    with tempfile.NamedTemporaryFile(suffix=".exr") as f:
+        assert image.to_path(f.name, clamp=(0.0, 1.0))
+        clamped = load(f.name).layers[0].channels[0].pixels
+        assert not image.to_path(f.name, clamp=(-1.0, 80000.0))
+        with pytest.raises(ValueError):
+            image.to_path(f.name, clamp=(1.0, 0.0))
+
+    np.testing.assert_array_equal(clamped, [[0.0, 0.5], [1.0, 1.0]])
+    np.testing.assert_array_equal(image.layers[0].channels[0].pixels, pixels)
+
+    buffer = image.to_buffer(clamp=(0.0, 1.0))
+    np.testing.assert_array_equal(load(buffer).layers[0].channels[0].pixels, clamped)
+
+
+def test_tile_description():
+    layer = load("tests/fixtures/Mipmap.exr").layers[0]
+    assert (layer.tile_size, layer.level_mode) == ((8, 8), "mipmap")
+    assert layer.tile_description() == (8, 8, "mipmap")
+    assert load("tests/fixtures/sRGB.exr").layers[0].tile_description() is None
+
+
+def test_prune_constant_layers():
+    pixels = np.zeros((2, 4, 4, 1), dtype=np.float32)
+    pixels[0, 1, 2, 0] = 1.0
+    image = ExrImage.from_pixels(pixels, layer_names=["beauty", "empty"])
+
+    assert image.prune_constant_layers() == ["empty"]
+    assert [layer.name for layer in image.layers] == ["beauty"]
+
+
+def test_screen_window():
+    image = _create_image(np.zeros((2, 2), dtype=np.float32))
+    layer = image.layers[0]
+    assert layer.screen_window == {"center": (0, 0), "width": 1}
+
+    layer.screen_window = {"center": (0.5, 0.25), "width": 1.5}
+    read_layer = load(image.to_buffer()).layers[0]
+    assert read_layer.screen_window == {"center": (0.5, 0.25), "width": 1.5}
+
+
+def test_horizontal_density():
+    image = _create_image(np.zeros((2, 2), dtype=np.float32))
+    assert image.layers[0].horizontal_density() is None
+
+    image.layers[0].set_horizontal_density(300)
+    read_layer = load(image.to_buffer()).layers[0]
+    assert read_layer.horizontal_density() == 300
+    assert read_layer.horizontal_density("cm") == pytest.approx(300 / 2.54)
+
+    read_layer.set_horizontal_density(100, "cm")
+    assert read_layer.horizontal_density() == pytest.approx(254)
+    with pytest.raises(ValueError, match="Unsupported density unit"):
+        read_layer.horizontal_density("mm")
+    with pytest.raises(ValueError, match="must be positive"):
+        read_layer.set_horizontal_density(0)
+
+
+def test_load_tolerant(tmp_path):
+    with open("tests/fixtures/ACES-2065-1.exr", "rb") as f:
+        (tmp_path / "partial.exr").write_bytes(f.read()[:-100])
+
+    with pytest.raises(OSError):
+        load(tmp_path / "partial.exr")
+    image = load(tmp_path / "partial.exr", tolerant=True)
+    assert image.partial
+    assert image.warnings
+
+
+@pytest.mark.skipif(sys.version_info < (3, 12), reason="__buffer__ needs Python 3.12")
+def test_channel_buffer_protocol():
+    image = load("tests/fixtures/AllHalfValues.exr")
+    channel = image.layers[0].channels[0]
+
+    view = memoryview(channel)
+    assert view.format == "e"
+    assert view.shape == (channel.height, channel.width)
+    assert np.shares_memory(np.asarray(view), channel.pixels)
+
+    array = np.arange(12, dtype=np.float32).reshape(3, 4)
+    view = memoryview(ExrChannel(name="Y", width=4, height=3, pixels=array))
+    assert view.format == "f"
+    assert view.tolist()[2] == [8, 9, 10, 11]
+
+
+def test_imageio_roundtrip():
+    image = load("tests/fixtures/sRGB.exr")
+    array, meta = to_imageio(image)
+    assert array.dtype == np.float32
+    assert array.shape == (49, 64, 4)
+    assert meta["channel_names"] == ["R", "G", "B", "A"]
+
+    rebuilt = load(from_imageio(array, meta).to_buffer())
+    np.testing.assert_array_equal(to_imageio(rebuilt)[0], array)
+    assert rebuilt.chromaticities == image.chromaticities
+
+    pixels = np.array([[[-1.0, 0.5, 2.0]]], dtype=np.float32)
+    array, _ = to_imageio(ExrImage.from_array(pixels, ["R", "G", "B"]), dtype="uint8")
+    np.testing.assert_array_equal(array, [[[0, 128, 255]]])
+
+    gray = from_imageio(np.full((2, 3), 255, dtype=np.uint8))
+    assert [c.name for c in gray.layers[0].channels] == ["L"]
+    assert gray.layers[0].channels[0].pixels.max() == 1.0
+
+
+def test_layer_from_schema():
+    layer = ExrLayer.from_schema("beauty", 3, 2, [("R", "f16"), ("Z", "f32")])
+    image = ExrImage(layers=[layer])
+    layer.set_channel("R", np.ones((2, 3), dtype=np.float16))
+    with pytest.raises(OSError):
+        image.to_buffer()
+
+    with pytest.raises(ValueError, match="declared as float32"):
+        layer.set_channel("Z", np.zeros((2, 3), dtype=np.float16))
+    with pytest.raises(ValueError, match="needs 3x2 pixels"):
+        layer.set_channel("Z", np.zeros(5, dtype=np.float32))
+    with pytest.raises(KeyError):
+        layer.set_channel("A", np.zeros((2, 3), dtype=np.float32))
+    layer.set_channel("Z", np.zeros((2, 3), dtype=np.float64))
+    assert layer.channels[1].pixels.dtype == np.float32
+    read_layer = load(image.to_buffer()).layers[0]
+    assert [c.pixels.dtype for c in read_layer.channels] == [np.float16, np.float32]
+
+
+def test_alpha():
+    image = load("tests/fixtures/sRGB.exr")
+    alpha = image.layers[0].alpha()
+    assert alpha is not None
+    assert alpha.shape == (49, 64)
+    assert _create_image(np.zeros((2, 2), dtype=np.float32)).layers[0].alpha() is None
+
+
+def test_to_path_threads(tmp_path):
+    pixels = np.random.default_rng(0).random((64, 48, 3)).astype(np.float32)
+    image = ExrImage.from_array(pixels, ["R", "G", "B"])
+    image.to_path(tmp_path / "single.exr")
+    image.to_path(tmp_path / "threaded.exr", threads=4)
+    assert (tmp_path / "threaded.exr").read_bytes() == (tmp_path / "single.exr").read_bytes()
+
+    with pytest.raises(ValueError):
+        image.to_path(tmp_path / "none.exr", threads=0)
+
+
+def test_to_path_require_sorted_channels(tmp_path):
+    pixels = np.random.default_rng(0).random((8, 4, 3)).astype(np.float32)
+    image = ExrImage.from_array(pixels, ["R", "G", "B"])
+    image.to_path(tmp_path / "sorted.exr")
+    assert [c.name for c in load(tmp_path / "sorted.exr").layers[0].channels] == ["B", "G", "R"]
+
+    with pytest.raises(ValueError, match="alphabetical"):
+        image.to_path(tmp_path / "unsorted.exr", require_sorted_channels=True)
+
+    image = ExrImage.from_array(pixels[..., ::-1].copy(), ["B", "G", "R"])
+    image.to_path(tmp_path / "presorted.exr", require_sorted_channels=True)
+    assert (tmp_path / "presorted.exr").read_bytes() == (tmp_path / "sorted.exr").read_bytes()
+
+
+def test_save_options(tmp_path):
+    image = _create_image(np.zeros((2, 2), dtype=np.float32))
+    assert image.to_buffer(threads=2, require_sorted_channels=True) == image.to_buffer()
+
+    with pytest.raises(TypeError, match="compresion"):
+        image.to_buffer(compresion="zip")
+    with pytest.raises(TypeError, match="compresion"):
+        image.to_path(tmp_path / "typo.exr", compresion="zip")
+    assert not (tmp_path / "typo.exr").exists()
+
+
+def test_layer_astype_u32():
+    pixels = np.array([[0.4, 2.6], [-1.0, np.nan]], dtype=np.float32)
+    layer = _create_image(pixels).layers[0]
+    ids = layer.astype("u32").channels[0].pixels
+    assert ids.dtype == np.uint32
+    np.testing.assert_array_equal(ids.reshape(-1), [0, 2, 0, 0])
+    np.testing.assert_array_equal(layer._to_rust().astype("u32").pixels()[0], ids.reshape(-1))
+
+
+def test_version_flags():
+    image = load("tests/fixtures/Mixed.exr")
+    assert image.version_flags is not None
+    assert image.version_flags["multipart"]
+    assert ExrImage.from_array(np.zeros((2, 2, 1)), ["Y"]).version_flags is None
+
+
+def test_perceptually_linear_roundtrip():
+    image = ExrImage.from_array(np.zeros((2, 2, 2), dtype=np.float32), ["R", "id"])
+    image.layers[0].channels[1].perceptually_linear = True
+    read_image = ExrImage.from_buffer(image.to_buffer())
+    flags = {channel.name: channel.perceptually_linear for channel in read_image.layers[0].channels}
+    assert flags == {"R": False, "id": True}
+
+
+def test_channel_default_value_roundtrip():
+    image = ExrImage.from_array(np.zeros((2, 2, 2), dtype=np.float32), ["R", "Z"])
+    image.layers[0].channels[1].default_value = 1e9
+    read_image = ExrImage.from_buffer(image.to_buffer())
+    defaults = {channel.name: channel.default_value for channel in read_image.layers[0].channels}
+    assert defaults == {"R": None, "Z": 1e9}
+    assert "defaultValue.Z" not in read_image.layers[0].attributes
+
+    ids = ExrImage.from_array(np.zeros((2, 2, 1), dtype=np.uint32), ["id"])
+    ids.layers[0].channels[0].perceptually_linear = True
+    with pytest.raises(ValueError, match="u32"):
+        ids.to_buffer()
+
+
+def test_half_overflow(tmp_path):
+    pixels = np.array([[1.0, 70000.0], [-1e6, 65504.0]], dtype=np.float32)
+    image = _create_image(pixels)
+
+    with pytest.warns(UserWarning, match="2 sample"):
+        buffer = image.to_buffer(force_dtype="f16")
+    assert np.isinf(ExrImage.from_buffer(buffer).layers[0].channels[0].pixels).sum() == 2
+
+    with pytest.raises(ValueError, match="2 sample"):
+        image.to_path(tmp_path / "half.exr", channel_dtypes={"testc": "f16"}, half_overflow="error")
+    assert not (tmp_path / "half.exr").exists()
+
+    with warnings.catch_warnings():
+        warnings.simplefilter("error")
+        image.to_buffer(force_dtype="f16", half_overflow="ignore")
+        image.to_path(tmp_path / "clamped.exr", force_dtype="f16", clamp=(-1000.0, 1000.0))
+        image.to_buffer()
+
+
+def test_auto_crop():
+    alpha = np.zeros((4, 4), dtype=np.float32)
+    alpha[1:3, 2] = 0.5
+    layer = ExrImage.from_array(np.stack([alpha, alpha * 2], axis=-1), ["A", "R"]).layers[0]
+
+    cropped, offset = layer.auto_crop()
+    assert offset == (2, 1)
+    assert (cropped.width, cropped.height, cropped.position) == (1, 2, (2, 1))
+    assert [channel.name for channel in cropped.channels] == ["A", "R"]
+    np.testing.assert_array_equal(cropped.channels[1].pixels.reshape(-1), [1.0, 1.0])
+
+
+def test_set_attributes():
+    image = _create_image(np.ones((2, 2), dtype=np.float32), {})
+    image.chromaticities = Chromaticities(
+        red=(0.68, 0.32), green=(0.265, 0.69), blue=(0.15, 0.06), white=(0.3127, 0.329)
+    )
+    image.set_attributes({"note": "slate"})
+    read_image = ExrImage.from_buffer(image.to_buffer())
+    assert read_image.chromaticities.red == pytest.approx((0.68, 0.32))
+    assert read_image.attributes["note"] == "slate"
+
+    image.set_attributes({"owner": "exrio"}, merge=False)
+    assert image.attributes == {"owner": "exrio"}
+    assert image.chromaticities is None
+
+
+def test_assemble_tiles():
+    top = _create_image(np.ones((2, 4), dtype=np.float32)).layers[0]
+    bottom = _create_image(np.full((2, 4), 2.0, dtype=np.float32)).layers[0]
+    bottom.position = (0, 2)
+    image = ExrImage(layers=[top, bottom])
+
+    mosaic = image.assemble_tiles((0, 0, 4, 4), policy="error")
+    assert (mosaic.width, mosaic.height) == (4, 4)
+    np.testing.assert_array_equal(mosaic.channels[0].pixels[:2], 1.0)
+    np.testing.assert_array_equal(mosaic.channels[0].pixels[2:], 2.0)
+
+    with pytest.raises(ValueError):
+        image.assemble_tiles((0, 0, 4, 5), policy="error")
+
+
+def test_load_warnings():
+    image = load("tests/fixtures/UnsupportedAttributes.exr")
+    assert image.layers[0].attributes["note"] == "kept"
+    assert [w.split(",")[0] for w in image.warnings] == [
+        "Skipped attribute vendorData of layer 0",
+        "Skipped attribute world_to_camera of layer 0",
+    ]
+
+
+def test_compare(tmp_path):
+    pixels = np.zeros((4, 8), dtype=np.float32)
+    pixels[0, 0] = np.nan
+    _create_image(pixels).to_path(tmp_path / "a.exr")
+    _create_image(pixels.copy()).to_path(tmp_path / "b.exr")
+    changed = pixels.copy()
+    changed[3, 7] = 0.5
+    _create_image(changed).to_path(tmp_path / "c.exr")
+    _create_image(np.zeros((4, 4), dtype=np.float32)).to_path(tmp_path / "d.exr")
+
+    same = compare(tmp_path / "a.exr", tmp_path / "b.exr")
+    assert same and same.differences == []
+    assert same.channels == {"testl.testc": (0.0, 0.0)}
+
+    different = compare(tmp_path / "a.exr", tmp_path / "c.exr", tolerance=0.1)
+    assert not different and different.differences == []
+    assert different.channels["testl.testc"] == (0.5, 0.5 / 32)
+    assert compare(tmp_path / "a.exr", tmp_path / "c.exr", tolerance=0.5)
+
+    resized = compare(tmp_path / "a.exr", tmp_path / "d.exr")
+    assert not resized
+    assert resized.differences == ["testl is 8x4 vs 4x4"]
+    assert resized.channels == {}
+
+
+def test_resave_preserves_line_order():
+    image = _create_image(np.random.rand(64, 64).astype(np.float32))
+    decreasing_buffer = image.to_buffer(line_order="decreasing")
+
+    decreasing_image = load(decreasing_buffer)
+    assert decreasing_image.layers[0].line_order == "decreasing"
+
+    rt_image = load(decreasing_image.to_buffer())
+    assert rt_image.layers[0].line_order == "decreasing"
+    np.testing.assert_array_equal(
+        rt_image.layers[0].channels[0].pixels, image.layers[0].channels[0].pixels
+    )
+
+    rt_image = load(decreasing_image.to_buffer(line_order="increasing"))
+    assert rt_image.layers[0].line_order == "increasing"
+
+
+def test_from_array():
+    array = np.random.rand(8, 4, 5).astype(np.float32)
+    names = ["R", "G", "B", "A", "depth"]
+    image = ExrImage.from_array(array, names, layer_name="beauty")
+
+    rt_image = load(image.to_buffer())
+    layer = rt_image.layers[0]
+    assert layer.name == "beauty"
+    assert (layer.width, layer.height) == (4, 8)
+    for channel in layer.channels:
+        np.testing.assert_array_equal(channel.pixels, array[..., names.index(channel.name)])
+
+    with pytest.raises(ValueError):
+        ExrImage.from_array(array, names[:4])
+    with pytest.raises(ValueError):
+        ExrImage.from_array(array[..., 0], ["Y"])
+
+
+def test_from_array_infers_dtype():
+    dtypes = {np.float16: np.float16, np.float64: np.float32, np.int64: np.uint32}
+    for dtype, expected in dtypes.items():
+        image = ExrImage.from_array(np.ones((2, 2, 1), dtype=dtype), ["Y"])
+        rt_image = load(image.to_buffer())
+        assert rt_image.layers[0].channels[0].pixels.dtype == expected
+
+    image = ExrImage.from_array(np.ones((2, 2, 1), dtype=np.float32), ["Y"], dtype="f16")
+    assert image.layers[0].channels[0].pixels.dtype == np.float16
+
+    with pytest.raises(ValueError):
+        ExrImage.from_array(-np.ones((2, 2, 1), dtype=np.int32), ["id"])
+
+
+def test_flip_y():
+    pixels = np.arange(12, dtype=np.float32).reshape(4, 3)
+    image = _create_image(pixels)
+
+    flipped = load(image.to_buffer(), flip_y=True)
+    np.testing.assert_array_equal(flipped.layers[0].channels[0].pixels, pixels[::-1])
+
+    rt_image = load(image.to_buffer(flip_y=True))
+    np.testing.assert_array_equal(rt_image.layers[0].channels[0].pixels, pixels[::-1])
+
+    rt_image = load(flipped.to_buffer(flip_y=True))
+    np.testing.assert_array_equal(rt_image.layers[0].channels[0].pixels, pixels)
+
+
+def test_read_blocks():
+    path = "tests/fixtures/AllHalfValues.exr"
+    expected = load(path).layers[0]
+    blocks = []
+
+    def on_block(layer_index, bounds, channels):
+        x, y, width, height = bounds
+        assert layer_index == 0
+        for channel in expected.channels:
+            assert channels[channel.name].dtype == np.float16
+            np.testing.assert_array_equal(
+                channels[channel.name], channel.pixels[y : y + height, x : x + width]
+            )
+        blocks.append(bounds)
+
+    ExrImage.read_blocks(path, on_block)
+    assert len(blocks) == ExrImage.chunk_count(path)
+    assert sum(height for _, _, _, height in blocks) == expected.height
+
+    def fail(layer_index, bounds, channels):
+        raise RuntimeError("stop")
+
+    with pytest.raises(RuntimeError):
+        ExrImage.read_blocks(path, fail)
+
+
+def test_iter_tiles(tmp_path):
+    pixels = np.random.default_rng(0).random((12, 20, 2)).astype(np.float32)
+    image = ExrImage.from_array(pixels, ["R", "Z"])
+    image.layers[0].tile_size = (8, 8)
+    path = tmp_path / "tiled.exr"
+    image.to_path(path)
+
+    tiles = {}
+    for tile_x, tile_y, tile in ExrImage.iter_tiles(path, 0, "Z"):
+        x, y = tile_x * 8, tile_y * 8
+        np.testing.assert_array_equal(tile, pixels[y : y + 8, x : x + 8, 1])
+        tiles[(tile_x, tile_y)] = tile.shape
+    assert len(tiles) == 6
+    assert tiles[(0, 0)] == (8, 8)
+    assert tiles[(2, 1)] == (4, 4)
+
+    with pytest.raises(KeyError):
+        ExrImage.iter_tiles(path, 0, "G")
+    with pytest.raises(IndexError):
+        ExrImage.iter_tiles(path, 1, "Z")
+    with pytest.raises(ValueError):
+        ExrImage.iter_tiles("tests/fixtures/Mixed.exr", 1, "Z")
+
+
+def test_load_tensor(tmp_path):
+    pixels = np.random.default_rng(0).random((12, 20, 3)).astype(np.float16)
+    path = tmp_path / "rgb.exr"
+    ExrImage.from_array(pixels, ["R", "G", "B"]).to_path(path)
+
+    tensor, names = ExrImage.load_tensor(path)
+    assert names == ["B", "G", "R"]
+    assert (tensor.shape, tensor.dtype) == ((12, 20, 3), np.float32)
+    assert tensor.flags["C_CONTIGUOUS"]
+    np.testing.assert_array_equal(tensor, pixels[..., ::-1].astype(np.float32))
+
+    tensor, names = ExrImage.load_tensor(path, 0, ["R", "G", "B", "R"])
+    assert names == ["R", "G", "B", "R"]
+    np.testing.assert_array_equal(tensor[..., :3], pixels.astype(np.float32))
+    np.testing.assert_array_equal(tensor[..., 3], tensor[..., 0])
+
+    with pytest.raises(KeyError):
+        ExrImage.load_tensor(path, channels=["A"])
+    with pytest.raises(IndexError):
+        ExrImage.load_tensor(path, 1)
+
+
+def test_to_rgba_and_from_rgba():
+    rgb = np.random.default_rng(0).random((4, 5, 3)).astype(np.float16)
+    image = ExrImage.from_array(rgb, ["R", "G", "B"])
+
+    rgba = image.layers[0].to_rgba()
+    assert (rgba.shape, rgba.dtype) == ((4, 5, 4), np.float32)
+    np.testing.assert_array_equal(rgba[..., :3], rgb.astype(np.float32))
+    assert np.all(rgba[..., 3] == 1.0)
+    assert np.all(image.layers[0].to_rgba(fill_alpha=0.5)[..., 3] == 0.5)
+    with pytest.raises(KeyError, match="Channel A"):
+        image.layers[0].to_rgba(allow_missing=False)
+
+    mask = ExrImage.from_array(rgb[..., :1], ["alpha"]).layers[0].to_rgba(allow_missing=True)
+    assert np.all(mask[..., :3] == 0.0)
+    np.testing.assert_array_equal(mask[..., 3], rgb[..., 0].astype(np.float32))
+
+    image = ExrImage.from_rgba(rgb, fill_alpha=0.25)
+    layer = image.layers[0]
+    assert [channel.name for channel in layer.channels] == ["R", "G", "B", "A"]
+    assert layer.channels[3].pixels.dtype == np.float16
+    np.testing.assert_array_equal(layer.to_rgba(allow_missing=False)[..., 3], 0.25)
+    with pytest.raises(ValueError):
+        ExrImage.from_rgba(rgb, allow_missing=False)
+    with pytest.raises(ValueError):
+        ExrImage.from_rgba(rgb[..., :2])
+
+
+def test_as_structured():
+    shape = (2, 3)
+    names = ["diffuse.B", "diffuse.G", "diffuse.R", "depth", "spec.A", "spec.Y"]
+    channels = [
+        ExrChannel(name=name, width=3, height=2, pixels=np.full(shape, index, np.float32))
+        for index, name in enumerate(names)
+    ]
+    layer = ExrLayer(width=3, height=2, channels=channels)
+
+    structured = layer.as_structured()
+    assert sorted(structured.keys()) == ["default", "diffuse", "spec"]
+    assert structured["diffuse"].shape == (2, 3, 3)
+    np.testing.assert_array_equal(structured["diffuse"][0, 0], [2, 1, 0])
+    np.testing.assert_array_equal(structured["spec"][0, 0], [4, 5])
+    assert structured["default"].shape == (2, 3, 1)
+
+    assert "beauty" in layer.as_structured(default_key="beauty")
+
+
+def test_channels_nested():
+    names = ["diffuse.R", "diffuse.G", "light.key.R", "depth"]
+    channels = [
+        ExrChannel(name=name, width=3, height=2, pixels=np.full((2, 3), index, np.float32))
+        for index, name in enumerate(names)
+    ]
+    layer = ExrLayer(width=3, height=2, channels=channels)
+
+    nested = layer.channels_nested()
+    assert sorted(nested.keys()) == ["default", "diffuse", "light"]
+    assert sorted(nested["diffuse"].keys()) == ["G", "R"]
+    assert nested["light"]["key"]["R"] is channels[2].pixels
+    assert nested["default"]["depth"] is channels[3].pixels
+
+    layer.channels.append(ExrChannel(name="light.key", width=3, height=2, pixels=np.zeros(6)))
+    with pytest.raises(ValueError):
+        layer.channels_nested()
+
+
+def test_interleaved():
+    channels = [
+        ExrChannel(name=name, width=3, height=2, pixels=np.full((2, 3), index, np.float16))
+        for index, name in enumerate(["A", "B", "G", "R"])
+    ]
+    layer = ExrLayer(width=3, height=2, channels=channels)
+
+    rgba = layer.interleaved(["R", "G", "B", "A"])
+    assert (rgba.shape, rgba.dtype) == ((2, 3, 4), np.float32)
+    np.testing.assert_array_equal(rgba[1, 2], [3, 2, 1, 0])
+
+    with pytest.raises(KeyError):
+        layer.interleaved(["Z"])
+
+
+def test_layer_lookup_with_duplicate_names():
+    layers = [
+        replace(_create_image(np.full((2, 2), value, np.float32)).layers[0], name=name)
+        for value, name in [(1.0, "beauty"), (2.0, "diffuse"), (3.0, "beaut1")]
+    ]
+    buffer = ExrImage(layers=layers).to_buffer().replace(b"beaut1", b"beauty")
+
+    image = ExrImage.from_buffer(buffer)
+    assert [layer.name for layer in image.layers] == ["beauty", "diffuse", "beauty.1"]
+    assert image.layer("beauty").channels[0].pixels[0, 0] == 1.0
+    assert [layer.file_name for layer in image.layers_named("beauty")] == [None, "beauty"]
+    assert image.layers_named("beauty")[1].channels[0].pixels[0, 0] == 3.0
+    assert image.layer("beauty.1") is image.layers[2]
+    with pytest.raises(KeyError):
+        image.layer("specular")
+
+
+def test_load_gzip_wrapped():
+    pixels = np.arange(12, dtype=np.float32).reshape(4, 3)
+    buffer = _create_image(pixels).to_buffer()
+
+    with tempfile.TemporaryDirectory() as directory:
+        path = f"{directory}/image.exr.gz"
+        with open(path, "wb") as file:
+            file.write(gzip.compress(buffer))
+
+        image = load(path)
+        np.testing.assert_array_equal(image.layers[0].channels[0].pixels, pixels)
+
+
+def test_path_readers_unwrap_gzip(tmp_path):
+    pixels = np.random.default_rng(0).random((12, 20, 2)).astype(np.float32)
+    image = ExrImage.from_array(pixels, ["R", "Z"])
+    image.layers[0].tile_size = (8, 8)
+    path = tmp_path / "tiled.exr.gz"
+    path.write_bytes(gzip.compress(image.to_buffer()))
+
+    assert ExrImage.part_count(path) == 1
+    assert ExrImage.chunk_count(path) == 6
+    for loaded in [ExrImage.load_mmap(path), ExrImage.load_mip(path, 0)]:
+        np.testing.assert_array_equal(loaded.layers[0].channels[1].pixels, pixels[..., 1])
+    for layer in [ExrImage.load_first_layer(path), ExrImage.load_part(path, 0)]:
+        np.testing.assert_array_equal(layer.channels[0].pixels, pixels[..., 0])
+    np.testing.assert_array_equal(ExrImage.load_tensor(path)[0], pixels)
+
+    blocks = []
+    ExrImage.read_blocks(path, lambda index, bounds, channels: blocks.append(bounds))
+    assert len(blocks) == 6
+    tiles = list(ExrImage.iter_tiles(path, 0, "Z"))
+    np.testing.assert_array_equal(tiles[0][2], pixels[:8, :8, 1])
+    assert sorted(tmp_path.iterdir()) == [path]
+
+    rgb_path = tmp_path / "rgb.exr.gz"
+    with open("tests/fixtures/AllHalfValues.exr", "rb") as f:
+        rgb_path.write_bytes(gzip.compress(f.read()))
+    assert ExrImage.thumbnail(rgb_path, 64).shape == (64, 64, 3)
+
+
+def test_default_chromaticities():
+    assert Chromaticities().is_close_to(PRIMARY_CHROMATICITIES["sRGB"])
+
+    set_default_chromaticities("AP1")
+    try:
+        assert Chromaticities().is_close_to(PRIMARY_CHROMATICITIES["AP1"])
+        assert Chromaticities(white=(0.3127, 0.329)).red == Chromaticities().red
+    finally:
+        set_default_chromaticities("sRGB")